@@ -1,75 +1,784 @@
+use std::path::{Path, PathBuf};
+use std::pin::pin;
 use std::sync::Arc;
+use std::time::Duration;
 
+use compio::signal::ctrl_c;
+use compio::time::sleep;
+use futures::future::{Either, select};
 use snafu::Snafu;
 use snafu::prelude::*;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
 
+use crate::application::GraphDump;
+use crate::application::RunLock;
+use crate::application::RunLockError;
 use crate::application::RuntimeConfig;
+use crate::application::TargetFromCwdError;
+use crate::application::TaskList;
+use crate::application::WatchedInputs;
+use crate::application::WhyReport;
+use crate::application::data::ReportOutput;
+use crate::application::resolve_target_from_cwd;
+use crate::config::rc_config::RcConfigError;
+use crate::config::task_registry::TASK_FILE_NAME;
 use crate::config::task_registry::TaskRegistry;
 use crate::config::task_registry::TaskRegistryCreationError;
+use crate::config::task_registry::get_task_file_path;
 use crate::executor::DependencyGraph;
+use crate::executor::DependencyGraphError;
 use crate::executor::ExecutionError;
 use crate::executor::Executor;
 use crate::executor::ExecutorCreationError;
+use crate::executor::RunReport;
+use crate::executor::TaskStatus;
+use crate::ext::CancellationToken;
 use crate::file_dependencies::DependencyTracker;
+use crate::file_dependencies::FingerprintStrategy;
+use crate::file_dependencies::get_standard_dependency_file_path;
+use crate::file_dependencies::glob_base;
+use crate::file_dependencies::is_glob;
+use crate::file_dependencies::is_url;
+use crate::tasks::TaskTrait;
+
+/// Task file written by `tessy init`, as a starting point for new projects
+const TASK_FILE_TEMPLATE: &str = r#"# Run a task with `tessy <task>`, or just `tessy` to run the default one
+default: build
+tasks:
+  build:
+    description: Builds the project
+    command: echo "Replace this with the build command"
+    # Files and directories whose changes make the task run again
+    inputs: [src/]
+    # Tasks which have to succeed first
+    dependsOn: [test]
+  test:
+    description: Runs the tests
+    command: echo "Replace this with the test command"
+    inputs: [src/, tests/]
+"#;
 
 pub struct Application;
 
 impl Application {
-    pub async fn run(app_config: impl Into<RuntimeConfig>) -> Result<(), ApplicationError> {
+    /// Runs the configured target and returns a report describing the outcome of every task.
+    /// Failing tasks are recorded in the report instead of being returned as an error.
+    /// Ctrl-C stops the run, killing the running commands and saving the finished tasks
+    pub async fn run(app_config: impl Into<RuntimeConfig>) -> Result<RunReport, ApplicationError> {
+        let stop = CancellationToken::new();
+        let run = pin!(Self::run_until(app_config, stop.clone()));
+        let interrupted = pin!(ctrl_c());
+        match select(run, interrupted).await {
+            Either::Left((report, _)) => report,
+            Either::Right((signal, run)) => {
+                match signal {
+                    Ok(()) => {
+                        warn!("Interrupted, stopping the running tasks");
+                        stop.cancel();
+                    }
+                    Err(e) => warn!("Can't listen for Ctrl-C, it won't stop the run: {}", e),
+                }
+                run.await
+            }
+        }
+    }
+
+    /// Runs the configured target like [`Application::run`], cancelling the running tasks
+    /// once the token is cancelled. Tasks which finished before still get saved
+    pub async fn run_until(
+        app_config: impl Into<RuntimeConfig>,
+        stop: CancellationToken,
+    ) -> Result<RunReport, ApplicationError> {
         let app_config: RuntimeConfig = app_config.into();
-        let config = TaskRegistry::read(&app_config.root)
+        // Held until the run returns, which releases the lock
+        let _run_lock = RunLock::acquire(&app_config.root, app_config.wait_for_lock)
             .await
-            .context(TaskRegistrySnafu)?;
+            .context(RunLockSnafu)?;
+        let config = Self::load_task_registry(&app_config).await?;
         debug!("Loaded config: {:?}", config);
 
+        let target = Self::resolve_target(&app_config, &config)?;
+        info!("Running target '{}'", target);
+
         let arc_app_config = Arc::new(app_config);
         let saved_dependencies_fut = DependencyTracker::read(arc_app_config.root.as_ref());
-        let dependency_graph = DependencyGraph::from_config(&config, &arc_app_config.target);
+        let dependency_graph =
+            DependencyGraph::from_config(&config, &target).context(DependencyGraphSnafu)?;
 
         let arc_config = Arc::new(config);
         let arc_dependency_graph = Arc::new(dependency_graph);
         let mut arc_saved_dependencies = Arc::new(saved_dependencies_fut.await);
 
-        let executed_tasks = Executor::new(
+        let executor = Executor::new(
             arc_config.clone(),
             arc_dependency_graph,
             arc_app_config.clone(),
             arc_saved_dependencies.clone(),
         )
-        .context(ExecutorCreationSnafu)?
-        .execute()
-        .await
+        .context(ExecutorCreationSnafu)?;
+        let mut report = {
+            let execution = pin!(executor.execute());
+            let stopped = pin!(stop.cancelled());
+            match select(execution, stopped).await {
+                Either::Left((report, _)) => report,
+                Either::Right((_, execution)) => {
+                    info!("Stopping the run");
+                    executor.cancellation().cancel();
+                    execution.await
+                }
+            }
+        }
         .context(ApplicationExecutionSnafu)?;
-        info!("Executed tasks: {:?}", executed_tasks);
+        drop(executor);
+        report.set_time_saved(
+            report
+                .skipped()
+                .filter_map(|task| arc_saved_dependencies.last_task_duration(&task.task_id))
+                .sum(),
+        );
+        info!("Run report: {:?}", report);
+        Self::write_reports(&arc_app_config.reports, &report).await?;
+
+        if arc_app_config.dry_run {
+            debug!("Dry run, leaving the saved dependencies untouched");
+            return Ok(report);
+        }
 
         info!("Updating saved dependencies");
-        let tasks_iter = executed_tasks
-            .iter()
-            .map(|task_id| arc_config.get_task_by_id(task_id).unwrap());
+        let tasks_iter = report
+            .executed()
+            .chain(report.skipped())
+            .map(|task_report| arc_config.get_task_by_id(&task_report.task_id).unwrap());
         if let Some(saved_dependencies) = Arc::get_mut(&mut arc_saved_dependencies) {
+            for task_report in report.tasks() {
+                let executed = task_report.status == TaskStatus::Executed;
+                if executed {
+                    saved_dependencies
+                        .record_task_duration(&task_report.task_id, task_report.duration);
+                }
+                if (executed || task_report.status == TaskStatus::SkippedUpToDate)
+                    && let Some(task) = arc_config.get_task_by_id(&task_report.task_id)
+                {
+                    saved_dependencies.record_virtual_artifacts(task, executed);
+                }
+            }
             saved_dependencies
                 .add_tasks_dependencies(tasks_iter, &arc_app_config.root)
                 .await;
-            saved_dependencies.write(&arc_app_config.root).await;
+            let task_ids = arc_config.get_tasks_iter().map(|task| task.id()).collect();
+            let pruned = saved_dependencies.prune(&task_ids);
+            if pruned > 0 {
+                info!(
+                    "Removed {} tasks which no longer exist from the saved dependencies",
+                    pruned
+                );
+            }
+            saved_dependencies
+                .write(&arc_app_config.root, arc_app_config.cache_compression)
+                .await;
         } else {
             error!(
                 "Failed to get mutable reference to saved dependencies. The dependencies will not be updated."
             );
         }
 
+        Ok(report)
+    }
+
+    /// Lists the tasks of the task file, without running anything
+    pub async fn list_tasks(
+        app_config: impl Into<RuntimeConfig>,
+    ) -> Result<TaskList, ApplicationError> {
+        let app_config: RuntimeConfig = app_config.into();
+        let config = Self::load_task_registry(&app_config).await?;
+        Ok(TaskList::new(&config))
+    }
+
+    /// Deletes the dependency cache under the root, returning the number of bytes freed,
+    /// or `None` if there was no cache to delete
+    pub async fn clean(
+        app_config: impl Into<RuntimeConfig>,
+    ) -> Result<Option<u64>, ApplicationError> {
+        let app_config: RuntimeConfig = app_config.into();
+        // Held, so a concurrent run doesn't write the cache back right after
+        let _run_lock = RunLock::acquire(&app_config.root, app_config.wait_for_lock)
+            .await
+            .context(RunLockSnafu)?;
+
+        let path = get_standard_dependency_file_path(&app_config.root);
+        let size = match compio::fs::metadata(&path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(ApplicationError::CleanError { path, source }),
+        };
+        compio::fs::remove_file(&path)
+            .await
+            .context(CleanSnafu { path: path.clone() })?;
+        info!("Deleted the dependency cache '{}'", path.display());
+        Ok(Some(size))
+    }
+
+    /// Writes a starter task file into the root, returning its path.
+    /// An existing task file is only overwritten with `force`
+    pub async fn init(
+        app_config: impl Into<RuntimeConfig>,
+        force: bool,
+    ) -> Result<PathBuf, ApplicationError> {
+        let app_config: RuntimeConfig = app_config.into();
+        let path = app_config.root.join(
+            app_config
+                .task_file
+                .as_deref()
+                .unwrap_or(Path::new(TASK_FILE_NAME)),
+        );
+        if !force && compio::fs::metadata(&path).await.is_ok() {
+            return TaskFileExistsSnafu { path }.fail();
+        }
+
+        if let Some(parent) = path.parent() {
+            compio::fs::create_dir_all(parent)
+                .await
+                .context(InitSnafu { path: path.clone() })?;
+        }
+        let write_result = compio::fs::write(&path, TASK_FILE_TEMPLATE).await;
+        write_result.0.context(InitSnafu { path: path.clone() })?;
+        info!("Wrote the task file '{}'", path.display());
+        Ok(path)
+    }
+
+    /// Forgets the saved dependencies of a single task, so only it runs again next time.
+    /// Returns false if the task had nothing saved
+    pub async fn forget(
+        app_config: impl Into<RuntimeConfig>,
+        task: &str,
+    ) -> Result<bool, ApplicationError> {
+        let app_config: RuntimeConfig = app_config.into();
+        let _run_lock = RunLock::acquire(&app_config.root, app_config.wait_for_lock)
+            .await
+            .context(RunLockSnafu)?;
+        let config = Self::load_task_registry(&app_config).await?;
+        let task_id = config.resolve_alias(task);
+
+        let mut saved_dependencies = DependencyTracker::read(&app_config.root).await;
+        if !saved_dependencies.forget(task_id) {
+            return Ok(false);
+        }
+        saved_dependencies
+            .write(&app_config.root, app_config.cache_compression)
+            .await;
+        info!("Forgot the saved dependencies of task '{}'", task_id);
+        Ok(true)
+    }
+
+    /// Explains why the task would run, without running anything
+    pub async fn why(
+        app_config: impl Into<RuntimeConfig>,
+        task: &str,
+    ) -> Result<WhyReport, ApplicationError> {
+        let app_config: RuntimeConfig = app_config.into();
+        let config = Self::load_task_registry(&app_config).await?;
+        let task_id = config.resolve_alias(task).to_string();
+        if config.get_task_by_id(&task_id).is_none() {
+            return UnknownTaskSnafu { task_id }.fail();
+        }
+
+        let dependency_graph =
+            DependencyGraph::from_config(&config, &task_id).context(DependencyGraphSnafu)?;
+        let tracker = DependencyTracker::read(&app_config.root).await;
+        Ok(WhyReport::explain(&config, &dependency_graph, &tracker, &app_config.root).await)
+    }
+
+    /// Builds the dependency graph of the target without running anything. Unless all tasks
+    /// are requested, only the tasks the target needs are included
+    pub async fn dump_graph(
+        app_config: impl Into<RuntimeConfig>,
+        include_all: bool,
+    ) -> Result<GraphDump, ApplicationError> {
+        let app_config: RuntimeConfig = app_config.into();
+        let config = Self::load_task_registry(&app_config).await?;
+        let target = Self::resolve_target(&app_config, &config)?;
+
+        let dependency_graph = if include_all {
+            DependencyGraph::with_all_tasks(&config, &target)
+        } else {
+            DependencyGraph::from_config(&config, &target)
+        }
+        .context(DependencyGraphSnafu)?;
+        Ok(GraphDump::new(&dependency_graph, &config))
+    }
+
+    /// Waits until an input of any task needed for the target changes, by fingerprinting the
+    /// inputs once and comparing them again every interval. Unlike file system events, polling
+    /// works on any file system, at the cost of noticing changes only after up to one interval
+    pub async fn wait_for_changes(
+        app_config: impl Into<RuntimeConfig>,
+        interval: Duration,
+    ) -> Result<(), ApplicationError> {
+        let app_config: RuntimeConfig = app_config.into();
+        let config = Self::load_task_registry(&app_config).await?;
+        let target = Self::resolve_target(&app_config, &config)?;
+        let dependency_graph =
+            DependencyGraph::from_config(&config, &target).context(DependencyGraphSnafu)?;
+        let watched_tasks: Vec<_> = dependency_graph
+            .get_task_parents_iter()
+            .filter_map(|(task_id, _)| config.get_task_by_id(task_id))
+            .collect();
+
+        let mut snapshot = DependencyTracker::default();
+        snapshot
+            .add_tasks_dependencies(watched_tasks.iter().copied(), &app_config.root)
+            .await;
+        info!(
+            "Polling the inputs of {} tasks for changes every {:?}",
+            watched_tasks.len(),
+            interval
+        );
+
+        loop {
+            sleep(interval).await;
+            for task in &watched_tasks {
+                if !snapshot.is_task_up_to_date(task, &app_config.root).await {
+                    info!("Inputs of task '{}' changed", task.id());
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Collects the inputs of every task needed for the target, along with the task file,
+    /// for watching them for changes
+    pub async fn watched_inputs(
+        app_config: impl Into<RuntimeConfig>,
+    ) -> Result<WatchedInputs, ApplicationError> {
+        let app_config: RuntimeConfig = app_config.into();
+        let config = Self::load_task_registry(&app_config).await?;
+        let target = Self::resolve_target(&app_config, &config)?;
+        let dependency_graph =
+            DependencyGraph::from_config(&config, &target).context(DependencyGraphSnafu)?;
+        let root = &app_config.root;
+        let absolute = |path: PathBuf| std::path::absolute(&path).unwrap_or(path);
+
+        let mut inputs = WatchedInputs {
+            ignored: vec![absolute(
+                get_standard_dependency_file_path(root)
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(|| root.clone()),
+            )],
+            ..WatchedInputs::default()
+        };
+        inputs.files.insert(absolute(
+            app_config
+                .task_file
+                .clone()
+                .unwrap_or_else(|| get_task_file_path(root)),
+        ));
+        for task in dependency_graph
+            .get_task_parents_iter()
+            .filter_map(|(task_id, _)| config.get_task_by_id(task_id))
+        {
+            let fingerprints = DependencyTracker::fingerprint_task_inputs(task, root, None).await;
+            inputs.files.extend(
+                fingerprints
+                    .into_keys()
+                    .filter(|path| !is_url(&path.to_string_lossy()))
+                    .map(absolute),
+            );
+            for input in task.inputs().iter().filter(|input| !is_url(&input.path)) {
+                let directory = if is_glob(&input.path) {
+                    root.join(glob_base(&input.path))
+                } else {
+                    root.join(&input.path)
+                };
+                if directory.is_dir() {
+                    inputs.directories.push(absolute(directory));
+                }
+            }
+        }
+        Ok(inputs)
+    }
+
+    async fn write_reports(
+        reports: &[ReportOutput],
+        report: &RunReport,
+    ) -> Result<(), ApplicationError> {
+        for output in reports {
+            match output {
+                ReportOutput::Junit(path) => {
+                    info!("Writing JUnit report to {}", path.display());
+                    let write_result = compio::fs::write(path, report.to_junit_xml("tessy")).await;
+                    write_result
+                        .0
+                        .context(ReportWriteSnafu { path: path.clone() })?;
+                }
+            }
+        }
         Ok(())
     }
+
+    async fn load_task_registry(
+        app_config: &RuntimeConfig,
+    ) -> Result<TaskRegistry, ApplicationError> {
+        let mut config = match &app_config.task_file {
+            Some(task_file) => TaskRegistry::from_path(app_config.root.join(task_file)).await,
+            None => TaskRegistry::read(&app_config.root).await,
+        }
+        .context(TaskRegistrySnafu)?;
+        if app_config.hash_inputs {
+            config.force_strategy(FingerprintStrategy::Hash);
+        }
+        Ok(config)
+    }
+
+    /// Selects the task to run like [`Application::select_target`], failing if it doesn't
+    /// exist, as a graph built around a missing task would never finish
+    fn resolve_target(
+        app_config: &RuntimeConfig,
+        config: &TaskRegistry,
+    ) -> Result<String, ApplicationError> {
+        let target = Self::select_target(app_config, config)?;
+        if config.get_task_by_id(&target).is_none() {
+            return UnknownTargetSnafu {
+                target,
+                available: Self::available_tasks(config),
+            }
+            .fail();
+        }
+        Ok(target)
+    }
+
+    /// Selects the task to run: the given target, the task owning the current directory,
+    /// or the default of the task file, with aliases resolved
+    fn select_target(
+        app_config: &RuntimeConfig,
+        config: &TaskRegistry,
+    ) -> Result<String, ApplicationError> {
+        if let Some(target) = &app_config.target {
+            return Ok(config.resolve_alias(target).to_string());
+        }
+
+        if app_config.target_from_cwd {
+            let cwd = std::env::current_dir().context(CurrentDirSnafu)?;
+            let cwd = cwd.canonicalize().unwrap_or(cwd);
+            let root = app_config
+                .root
+                .canonicalize()
+                .unwrap_or_else(|_| app_config.root.clone());
+            return resolve_target_from_cwd(config.get_tasks_iter(), &root, &cwd)
+                .context(TargetFromCwdSnafu);
+        }
+
+        if let Some(default_target) = config.default_target() {
+            debug!("No target given, running the default '{}'", default_target);
+            return Ok(config.resolve_alias(default_target).to_string());
+        }
+
+        Err(ApplicationError::MissingTargetError {
+            available: Self::available_tasks(config),
+        })
+    }
+
+    /// Ids of all tasks of the task file, sorted
+    fn available_tasks(config: &TaskRegistry) -> Vec<String> {
+        let mut available: Vec<_> = config.get_tasks_iter().map(|task| task.id()).collect();
+        available.sort();
+        available
+    }
 }
 
 #[derive(Debug, Snafu)]
 pub enum ApplicationError {
+    #[snafu(display("Failed to load the .tessyrc file"))]
+    RcConfigError { source: RcConfigError },
+    #[snafu(display("Failed to lock the root for this run"))]
+    RunLockError { source: RunLockError },
     #[snafu(display("Critical failure encountered during configuration stage"))]
     TaskRegistryError { source: TaskRegistryCreationError },
+    #[snafu(display("Failed to build the dependency graph"))]
+    DependencyGraphError { source: DependencyGraphError },
     #[snafu(display("Critical failure encountered during executor creation"))]
     ExecutorCreationError { source: ExecutorCreationError },
     #[snafu(display("Critical failure encountered during application execution"))]
     ApplicationExecutionError { source: ExecutionError },
+    #[snafu(display(
+        "No target given. Pass one of the tasks ({}), use --target-from-cwd or set `default` in the task file",
+        available.join(", ")
+    ))]
+    MissingTargetError { available: Vec<String> },
+    #[snafu(display(
+        "Task '{}' does not exist. Available tasks: {}",
+        target,
+        available.join(", ")
+    ))]
+    UnknownTargetError {
+        target: String,
+        available: Vec<String>,
+    },
+    #[snafu(display("Failed to read the current directory"))]
+    CurrentDirError { source: std::io::Error },
+    #[snafu(display("Failed to find the task for the current directory"))]
+    TargetFromCwdError { source: TargetFromCwdError },
+    #[snafu(display("Task '{}' does not exist", task_id))]
+    UnknownTaskError { task_id: String },
+    #[snafu(display("Failed to write the report '{}'", path.display()))]
+    ReportWriteError {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Run interrupted, cancelled task(s): {}", task_ids.join(", ")))]
+    InterruptedError { task_ids: Vec<String> },
+    #[snafu(display("Task(s) failed: {}", task_ids.join(", ")))]
+    TaskFailureError {
+        task_ids: Vec<String>,
+        /// Exit code of the command, when a single task failed because of it
+        exit_code: Option<i32>,
+    },
+    #[snafu(display("Failed to delete the dependency cache '{}'", path.display()))]
+    CleanError {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display(
+        "The task file '{}' already exists, pass --force to overwrite it",
+        path.display()
+    ))]
+    TaskFileExistsError { path: std::path::PathBuf },
+    #[snafu(display("Failed to write the task file '{}'", path.display()))]
+    InitError {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Exit status of a run interrupted by Ctrl-C, as shells report it for SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+impl ApplicationError {
+    /// Error of an unsuccessful run: the failed tasks, or the cancelled ones if the run was
+    /// interrupted before any task failed
+    pub fn from_report(report: &RunReport) -> Self {
+        let failed: Vec<_> = report.failed().map(|task| task.task_id.clone()).collect();
+        if failed.is_empty() {
+            return ApplicationError::InterruptedError {
+                task_ids: report
+                    .cancelled()
+                    .map(|task| task.task_id.clone())
+                    .collect(),
+            };
+        }
+        ApplicationError::TaskFailureError {
+            task_ids: failed,
+            exit_code: report.failure_exit_code(),
+        }
+    }
+
+    /// Exit status of the process failing with the error. A single task whose command failed
+    /// passes its exit code on, so scripts can tell failures apart. An interrupted run gives
+    /// 130, any other error 1
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ApplicationError::InterruptedError { .. } => INTERRUPTED_EXIT_CODE,
+            ApplicationError::TaskFailureError {
+                exit_code: Some(exit_code),
+                ..
+            } if (1..=255).contains(exit_code) => *exit_code,
+            _ => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use rstest::rstest;
+
+    use crate::cli::Cli;
+    use crate::executor::TaskReport;
+
+    fn resolve(args: &[&str], task_file: &str) -> Result<String, ApplicationError> {
+        let app_config = RuntimeConfig::from(Cli::try_parse_from(args).unwrap());
+        let config = TaskRegistry::try_from(task_file).unwrap();
+        Application::resolve_target(&app_config, &config)
+    }
+
+    const TASK_FILE: &str = r#"
+tasks:
+  build:
+    command: cargo build
+  test:
+    command: cargo test
+"#;
+
+    #[compio::test]
+    async fn test_clean_deletes_the_dependency_cache() {
+        let root = tempfile::TempDir::new().unwrap();
+        let cache_path = get_standard_dependency_file_path(root.path());
+        std::fs::create_dir_all(cache_path.parent().unwrap()).unwrap();
+        std::fs::write(&cache_path, [0; 10]).unwrap();
+        let app_config = || {
+            let mut app_config =
+                RuntimeConfig::from(Cli::try_parse_from(["tessy", "clean"]).unwrap());
+            app_config.root = root.path().to_path_buf();
+            app_config
+        };
+
+        assert_eq!(Application::clean(app_config()).await.unwrap(), Some(10));
+        assert!(!cache_path.exists());
+        assert_eq!(Application::clean(app_config()).await.unwrap(), None);
+    }
+
+    #[compio::test]
+    async fn test_init_writes_a_task_file_only_once() {
+        let root = tempfile::TempDir::new().unwrap();
+        let app_config = || {
+            let mut app_config =
+                RuntimeConfig::from(Cli::try_parse_from(["tessy", "init"]).unwrap());
+            app_config.root = root.path().to_path_buf();
+            app_config
+        };
+
+        let path = Application::init(app_config(), false).await.unwrap();
+
+        assert_eq!(path, root.path().join("tasks.yaml"));
+        let config = TaskRegistry::from_path(path.clone()).await.unwrap();
+        assert_eq!(Application::available_tasks(&config), vec!["build", "test"]);
+        assert!(matches!(
+            Application::init(app_config(), false).await,
+            Err(ApplicationError::TaskFileExistsError { .. })
+        ));
+
+        std::fs::write(&path, "tasks: {}").unwrap();
+        Application::init(app_config(), true).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), TASK_FILE_TEMPLATE);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_failed_command_exit_code_is_reported() {
+        let root = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            root.path().join("tasks.yaml"),
+            "tasks:\n  check:\n    command: exit 3",
+        )
+        .unwrap();
+        let mut app_config = RuntimeConfig::from(Cli::try_parse_from(["tessy", "check"]).unwrap());
+        app_config.root = root.path().to_path_buf();
+
+        let report = Application::run_until(app_config, CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!(report.failure_exit_code(), Some(3));
+    }
+
+    #[rstest]
+    #[case(Some(3), 3)]
+    #[case(Some(-1), 1)]
+    #[case(None, 1)]
+    fn test_task_failure_exit_code(#[case] exit_code: Option<i32>, #[case] expected: i32) {
+        let error = ApplicationError::TaskFailureError {
+            task_ids: vec!["check".to_string()],
+            exit_code,
+        };
+
+        assert_eq!(error.exit_code(), expected);
+    }
+
+    fn report_of(statuses: &[(&str, TaskStatus)]) -> RunReport {
+        let mut report = RunReport::default();
+        for (task_id, status) in statuses {
+            report.push(TaskReport {
+                task_id: task_id.to_string(),
+                status: *status,
+                duration: Duration::ZERO,
+                exit_code: None,
+                error: None,
+                stderr_tail: None,
+            });
+        }
+        report
+    }
+
+    #[test]
+    fn test_interrupted_run_is_not_a_task_failure() {
+        let report = report_of(&[
+            ("build", TaskStatus::Executed),
+            ("test", TaskStatus::Cancelled),
+        ]);
+
+        let error = ApplicationError::from_report(&report);
+
+        assert!(matches!(
+            error,
+            ApplicationError::InterruptedError { ref task_ids } if task_ids == &["test"]
+        ));
+        assert_eq!(error.exit_code(), 130);
+    }
+
+    #[test]
+    fn test_failed_run_reports_the_failed_tasks() {
+        let report = report_of(&[
+            ("build", TaskStatus::Failed),
+            ("test", TaskStatus::Cancelled),
+        ]);
+
+        let error = ApplicationError::from_report(&report);
+
+        assert!(matches!(
+            error,
+            ApplicationError::TaskFailureError { ref task_ids, .. } if task_ids == &["build"]
+        ));
+        assert_eq!(error.exit_code(), 1);
+    }
+
+    #[test]
+    fn test_explicit_target_wins_over_default() {
+        let task_file = format!("default: build\n{}", TASK_FILE);
+
+        assert_eq!(resolve(&["tessy", "test"], &task_file).unwrap(), "test");
+    }
+
+    #[test]
+    fn test_default_target_is_used_without_target() {
+        let task_file = format!("default: build\n{}", TASK_FILE);
+
+        assert_eq!(resolve(&["tessy"], &task_file).unwrap(), "build");
+    }
+
+    #[test]
+    fn test_missing_target_lists_available_tasks() {
+        let error = resolve(&["tessy"], TASK_FILE).unwrap_err();
+
+        assert!(matches!(
+            &error,
+            ApplicationError::MissingTargetError { available } if available == &["build", "test"]
+        ));
+        assert!(error.to_string().contains("(build, test)"));
+    }
+
+    #[test]
+    fn test_unknown_target_lists_available_tasks() {
+        let error = resolve(&["tessy", "biuld"], TASK_FILE).unwrap_err();
+
+        assert!(matches!(
+            &error,
+            ApplicationError::UnknownTargetError { target, available }
+                if target == "biuld" && available == &["build", "test"]
+        ));
+        assert!(error.to_string().contains("Available tasks: build, test"));
+    }
+
+    #[test]
+    fn test_unknown_default_target_is_rejected() {
+        let task_file = format!("default: deploy\n{}", TASK_FILE);
+
+        assert!(matches!(
+            resolve(&["tessy"], &task_file),
+            Err(ApplicationError::UnknownTargetError { target, .. }) if target == "deploy"
+        ));
+    }
 }
@@ -0,0 +1,234 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use compio::fs;
+use serde_json::json;
+
+use crate::{
+    ext::BestEffortPathExt,
+    file_dependencies::{DependencyTracker, FileFingerprint, get_standard_dependency_file_path},
+};
+
+/// Summary of the saved dependency cache, used to diagnose its size and rebuilds
+#[derive(Debug, Clone)]
+pub struct CacheReport {
+    path: PathBuf,
+    /// Size of the cache file, or `None` if it doesn't exist
+    size_on_disk: Option<u64>,
+    tasks: Vec<TaskCacheSummary>,
+}
+
+/// Saved dependencies of a single task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskCacheSummary {
+    pub task_id: String,
+    pub tracked_files: usize,
+    pub metadata_stamps: usize,
+    pub content_hashes: usize,
+}
+
+impl CacheReport {
+    /// Reads the cache from the standard path under the root
+    pub async fn read(root: &Path) -> Self {
+        let path = get_standard_dependency_file_path(root);
+        let size_on_disk = fs::metadata(&path)
+            .await
+            .ok()
+            .map(|metadata| metadata.len());
+        let tracker = DependencyTracker::read_from_path(&path).await;
+        Self::from_tracker(path, size_on_disk, &tracker)
+    }
+
+    fn from_tracker(path: PathBuf, size_on_disk: Option<u64>, tracker: &DependencyTracker) -> Self {
+        let mut tasks: Vec<_> = tracker
+            .tracked_tasks()
+            .map(|(task_id, fingerprints)| {
+                let content_hashes = fingerprints
+                    .values()
+                    .filter(|fingerprint| {
+                        matches!(
+                            fingerprint.without_permissions(),
+                            FileFingerprint::Hash(_)
+                                | FileFingerprint::StampedHash { .. }
+                                | FileFingerprint::Remote(_)
+                        )
+                    })
+                    .count();
+                TaskCacheSummary {
+                    task_id: task_id.clone(),
+                    tracked_files: fingerprints.len(),
+                    metadata_stamps: fingerprints.len() - content_hashes,
+                    content_hashes,
+                }
+            })
+            .collect();
+        tasks.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+
+        Self {
+            path,
+            size_on_disk,
+            tasks,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let tasks: Vec<_> = self
+            .tasks
+            .iter()
+            .map(|task| {
+                json!({
+                    "id": task.task_id,
+                    "trackedFiles": task.tracked_files,
+                    "fingerprints": {
+                        "metadata": task.metadata_stamps,
+                        "hash": task.content_hashes,
+                    },
+                })
+            })
+            .collect();
+
+        json!({
+            "path": self.path.best_effort_path_display(),
+            "sizeOnDisk": self.size_on_disk,
+            "tasks": tasks,
+        })
+        .to_string()
+    }
+}
+
+impl fmt::Display for CacheReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(size) = self.size_on_disk else {
+            return write!(
+                f,
+                "No dependency cache at {}",
+                self.path.best_effort_path_display()
+            );
+        };
+
+        writeln!(
+            f,
+            "Dependency cache at {} ({} on disk)",
+            self.path.best_effort_path_display(),
+            format_size(size)
+        )?;
+        write!(
+            f,
+            "{} tasks, {} tracked files",
+            self.tasks.len(),
+            self.tasks
+                .iter()
+                .map(|task| task.tracked_files)
+                .sum::<usize>()
+        )?;
+        for task in &self.tasks {
+            write!(
+                f,
+                "\n  {}: {} files ({} metadata, {} hash)",
+                task.task_id, task.tracked_files, task.metadata_stamps, task.content_hashes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 KiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    async fn report_for(files: &[(&str, &str)]) -> CacheReport {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for (name, content) in files {
+            std::fs::write(temp_dir.path().join(name), content).unwrap();
+        }
+        let task_yaml = r#"
+tasks:
+  build:
+    command: make
+    inputs: [a.txt, { path: b.txt, strategy: hash }]
+  empty:
+    command: "true"
+"#;
+        let registry = crate::config::task_registry::TaskRegistry::try_from(task_yaml).unwrap();
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(registry.get_tasks_iter(), temp_dir.path())
+            .await;
+
+        CacheReport::from_tracker(PathBuf::from("cache"), Some(2048), &tracker)
+    }
+
+    #[compio::test]
+    async fn test_report_counts_fingerprint_kinds_per_task() {
+        let report = report_for(&[("a.txt", "a"), ("b.txt", "b")]).await;
+
+        assert_eq!(
+            report.tasks,
+            vec![
+                TaskCacheSummary {
+                    task_id: "build".to_string(),
+                    tracked_files: 2,
+                    metadata_stamps: 1,
+                    content_hashes: 1,
+                },
+                TaskCacheSummary {
+                    task_id: "empty".to_string(),
+                    tracked_files: 0,
+                    metadata_stamps: 0,
+                    content_hashes: 0,
+                },
+            ]
+        );
+    }
+
+    #[compio::test]
+    async fn test_report_formats_as_text_and_json() {
+        let report = report_for(&[("a.txt", "a"), ("b.txt", "b")]).await;
+
+        let text = report.to_string();
+        assert!(text.contains("(2.0 KiB on disk)"));
+        assert!(text.contains("build: 2 files (1 metadata, 1 hash)"));
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["sizeOnDisk"], 2048);
+        assert_eq!(json["tasks"][0]["fingerprints"]["hash"], 1);
+    }
+
+    #[compio::test]
+    async fn test_report_without_cache_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+
+        let report = CacheReport::read(temp_dir.path()).await;
+
+        assert!(report.to_string().starts_with("No dependency cache"));
+        assert!(report.tasks.is_empty());
+    }
+
+    #[rstest]
+    #[case(512, "512 B")]
+    #[case(1536, "1.5 KiB")]
+    #[case(3 * 1024 * 1024, "3.0 MiB")]
+    fn test_format_size(#[case] bytes: u64, #[case] expected: &str) {
+        assert_eq!(format_size(bytes), expected);
+    }
+}
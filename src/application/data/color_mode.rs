@@ -0,0 +1,52 @@
+use clap::ValueEnum;
+
+/// When task ids in the output are colored
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Default)]
+pub enum ColorMode {
+    /// Color if the terminal supports truecolor and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Decides whether to color the output, given whether `NO_COLOR` is set to a non-empty
+    /// value and whether stdout supports truecolor
+    pub fn is_enabled(&self, no_color: bool, supports_truecolor: bool) -> bool {
+        match self {
+            ColorMode::Auto => !no_color && supports_truecolor,
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+
+    /// Decides whether to color the output, based on the environment of the current process
+    pub fn resolve(&self) -> bool {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let supports_truecolor = supports_color::on_cached(supports_color::Stream::Stdout)
+            .is_some_and(|support| support.has_16m);
+        self.is_enabled(no_color, supports_truecolor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(ColorMode::Auto, false, true, true)]
+    #[case(ColorMode::Auto, false, false, false)]
+    #[case(ColorMode::Auto, true, true, false)]
+    #[case(ColorMode::Always, true, false, true)]
+    #[case(ColorMode::Never, false, true, false)]
+    fn test_color_mode_is_enabled(
+        #[case] mode: ColorMode,
+        #[case] no_color: bool,
+        #[case] supports_truecolor: bool,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(mode.is_enabled(no_color, supports_truecolor), expected);
+    }
+}
@@ -0,0 +1,10 @@
+use clap::ValueEnum;
+
+/// Format in which the dependency graph is dumped
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum GraphFormat {
+    /// Nodes, edges, leaves, roots and targets, for programmatic consumption
+    Json,
+    /// Graphviz graph with edges from each dependency to its dependents, for visualizing
+    Dot,
+}
@@ -1,6 +1,6 @@
 use clap::ValueEnum;
 
-#[derive(Debug, Clone, ValueEnum, Default)]
+#[derive(Debug, Clone, PartialEq, ValueEnum, Default)]
 pub enum LogLevel {
     Debug,
     Info,
@@ -1,3 +1,11 @@
+mod color_mode;
+mod graph_format;
 mod log_level;
+mod output_prefix;
+mod report_output;
 
+pub use color_mode::ColorMode;
+pub use graph_format::GraphFormat;
 pub use log_level::LogLevel;
+pub use output_prefix::OutputPrefix;
+pub use report_output::ReportOutput;
@@ -0,0 +1,43 @@
+use clap::ValueEnum;
+
+/// When task output lines are prefixed with the id of their task
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Default)]
+pub enum OutputPrefix {
+    /// Prefix unless a single task runs with output not going to a terminal
+    #[default]
+    Auto,
+    Always,
+    None,
+}
+
+impl OutputPrefix {
+    /// Decides whether to prefix the output for a run of the given number of tasks
+    pub fn is_enabled(&self, task_count: usize, is_terminal: bool) -> bool {
+        match self {
+            OutputPrefix::Auto => task_count > 1 || is_terminal,
+            OutputPrefix::Always => true,
+            OutputPrefix::None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(OutputPrefix::Auto, 1, false, false)]
+    #[case(OutputPrefix::Auto, 1, true, true)]
+    #[case(OutputPrefix::Auto, 3, false, true)]
+    #[case(OutputPrefix::Always, 1, false, true)]
+    #[case(OutputPrefix::None, 3, true, false)]
+    fn test_output_prefix_is_enabled(
+        #[case] prefix: OutputPrefix,
+        #[case] task_count: usize,
+        #[case] is_terminal: bool,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(prefix.is_enabled(task_count, is_terminal), expected);
+    }
+}
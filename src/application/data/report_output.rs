@@ -0,0 +1,49 @@
+use std::{path::PathBuf, str::FromStr};
+
+/// A file to write a report of the run into, given as `<format>=<path>`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportOutput {
+    /// JUnit XML, which CI systems show as test results
+    Junit(PathBuf),
+}
+
+impl FromStr for ReportOutput {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (format, path) = value
+            .split_once('=')
+            .ok_or_else(|| format!("expected <format>=<path>, got '{}'", value))?;
+        if path.is_empty() {
+            return Err(format!("missing the path of the '{}' report", format));
+        }
+
+        match format {
+            "junit" => Ok(ReportOutput::Junit(PathBuf::from(path))),
+            _ => Err(format!(
+                "unknown report format '{}', expected 'junit'",
+                format
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_junit_report() {
+        assert_eq!(
+            "junit=results.xml".parse(),
+            Ok(ReportOutput::Junit(PathBuf::from("results.xml")))
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_reports() {
+        assert!("results.xml".parse::<ReportOutput>().is_err());
+        assert!("junit=".parse::<ReportOutput>().is_err());
+        assert!("html=report.html".parse::<ReportOutput>().is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use serde_json::json;
+
+use crate::{config::task_registry::TaskRegistry, executor::DependencyGraph, tasks::TaskTrait};
+
+/// Dependency graph in a form external tools can consume, such as editors or visualizers
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphDump {
+    /// Ids and types of the tasks, ordered by id
+    nodes: Vec<(String, &'static str)>,
+    /// Pairs of a task and one of its dependencies
+    edges: Vec<(String, String)>,
+    /// Tasks without dependencies, which can run first
+    leaves: Vec<String>,
+    /// Tasks no other task depends on
+    roots: Vec<String>,
+    targets: Vec<String>,
+}
+
+impl GraphDump {
+    pub fn new(dependency_graph: &DependencyGraph, config: &TaskRegistry) -> Self {
+        let mut nodes: Vec<_> = dependency_graph
+            .get_task_parents_iter()
+            .filter_map(|(task_id, _)| config.get_task_by_id(task_id))
+            .map(|task| (task.id(), task.kind()))
+            .collect();
+        nodes.sort();
+
+        let mut edges = Vec::new();
+        let mut leaves = Vec::new();
+        let mut roots = Vec::new();
+        for (task_id, _) in &nodes {
+            let mut dependencies = dependency_graph
+                .dependencies_of(task_id)
+                .unwrap_or_default()
+                .to_vec();
+            dependencies.sort();
+            if dependencies.is_empty() {
+                leaves.push(task_id.clone());
+            }
+            if dependency_graph
+                .dependents_of(task_id)
+                .unwrap_or_default()
+                .is_empty()
+            {
+                roots.push(task_id.clone());
+            }
+            edges.extend(
+                dependencies
+                    .into_iter()
+                    .map(|dependency_id| (task_id.clone(), dependency_id)),
+            );
+        }
+
+        Self {
+            nodes,
+            edges,
+            leaves,
+            roots,
+            targets: vec![dependency_graph.target().to_string()],
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|(id, kind)| json!({ "id": id, "kind": kind }))
+            .collect();
+        let edges: Vec<_> = self
+            .edges
+            .iter()
+            .map(|(task_id, dependency_id)| {
+                json!({
+                    "task": task_id,
+                    "dependsOn": dependency_id,
+                })
+            })
+            .collect();
+
+        json!({
+            "nodes": nodes,
+            "edges": edges,
+            "leaves": self.leaves,
+            "roots": self.roots,
+            "targets": self.targets,
+        })
+        .to_string()
+    }
+
+    /// Graphviz DOT of the graph, with one node per task and edges pointing from every
+    /// dependency to the tasks depending on it
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tasks {\n");
+        for (id, _) in &self.nodes {
+            dot.push_str(&format!("    {};\n", dot_id(id)));
+        }
+        for (task_id, dependency_id) in &self.edges {
+            dot.push_str(&format!(
+                "    {} -> {};\n",
+                dot_id(dependency_id),
+                dot_id(task_id)
+            ));
+        }
+        dot.push('}');
+        dot
+    }
+}
+
+/// Quotes the task id as a DOT identifier, escaping the characters which would end it
+fn dot_id(id: &str) -> String {
+    let escaped = id
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> TaskRegistry {
+        r#"
+tasks:
+  lint:
+    command: cargo clippy
+  test:
+    command: cargo test
+    dependsOn: [lint]
+  build:
+    command: cargo build
+    dependsOn: [lint]
+"#
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_dump_of_pruned_graph() {
+        let config = registry();
+        let graph = DependencyGraph::from_config(&config, &"build".to_string()).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&GraphDump::new(&graph, &config).to_json()).unwrap();
+
+        assert_eq!(
+            json["nodes"],
+            json!([
+                { "id": "build", "kind": "execute" },
+                { "id": "lint", "kind": "execute" },
+            ])
+        );
+        assert_eq!(
+            json["edges"],
+            json!([{ "task": "build", "dependsOn": "lint" }])
+        );
+        assert_eq!(json["leaves"], json!(["lint"]));
+        assert_eq!(json["roots"], json!(["build"]));
+        assert_eq!(json["targets"], json!(["build"]));
+    }
+
+    #[test]
+    fn test_dot_has_edges_from_dependencies_to_dependents() {
+        let config = registry();
+        let graph = DependencyGraph::with_all_tasks(&config, &"build".to_string()).unwrap();
+
+        let dot = GraphDump::new(&graph, &config).to_dot();
+
+        assert_eq!(
+            dot,
+            "digraph tasks {\n    \"build\";\n    \"lint\";\n    \"test\";\n    \
+             \"lint\" -> \"build\";\n    \"lint\" -> \"test\";\n}"
+        );
+    }
+
+    #[test]
+    fn test_dot_escapes_task_ids() {
+        assert_eq!(dot_id(r#"say "hi" \ bye"#), r#""say \"hi\" \\ bye""#);
+    }
+
+    #[test]
+    fn test_dump_of_all_tasks() {
+        let config = registry();
+        let graph = DependencyGraph::with_all_tasks(&config, &"build".to_string()).unwrap();
+
+        let dump = GraphDump::new(&graph, &config);
+
+        assert_eq!(dump.nodes.len(), 3);
+        assert_eq!(
+            dump.edges,
+            vec![
+                ("build".to_string(), "lint".to_string()),
+                ("test".to_string(), "lint".to_string())
+            ]
+        );
+        assert_eq!(dump.roots, vec!["build", "test"]);
+        assert_eq!(dump.targets, vec!["build"]);
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::{Path, PathBuf},
+    pin::pin,
+    time::Duration,
+};
+
+use compio::time::sleep;
+use futures::{
+    StreamExt,
+    future::{Either, select},
+};
+use futures_channel::mpsc;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tracing::{debug, warn};
+
+use crate::ext::BestEffortPathExt;
+
+/// Files and directories whose changes make the target run again
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WatchedInputs {
+    /// Fingerprinted input files, watched through the directories containing them,
+    /// so files replaced by editors saving into a new file are still noticed
+    pub files: HashSet<PathBuf>,
+    /// Input directories, in which any file counts as an input
+    pub directories: Vec<PathBuf>,
+    /// Paths never counting as inputs, like the dependency file written after every run
+    pub ignored: Vec<PathBuf>,
+}
+
+impl WatchedInputs {
+    /// Returns true if a change of the path affects an input
+    pub fn is_relevant(&self, path: &Path) -> bool {
+        !self.ignored.iter().any(|ignored| path.starts_with(ignored))
+            && (self.files.contains(path)
+                || self
+                    .directories
+                    .iter()
+                    .any(|directory| path.starts_with(directory)))
+    }
+}
+
+/// Waits for file system events changing the watched inputs
+pub struct InputWatcher {
+    // Events stop arriving once the watcher is dropped
+    _watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<PathBuf>,
+    inputs: WatchedInputs,
+}
+
+impl InputWatcher {
+    pub fn new(inputs: WatchedInputs) -> notify::Result<Self> {
+        let (sender, events) = mpsc::unbounded();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+                Ok(event) => {
+                    for path in event.paths {
+                        let _ = sender.unbounded_send(path);
+                    }
+                }
+                Err(e) => warn!("Error while watching the inputs: {}", e),
+            })?;
+
+        for directory in &inputs.directories {
+            watcher.watch(directory, RecursiveMode::Recursive)?;
+        }
+        let parents: BTreeSet<_> = inputs
+            .files
+            .iter()
+            .filter(|file| {
+                !inputs
+                    .directories
+                    .iter()
+                    .any(|directory| file.starts_with(directory))
+            })
+            .filter_map(|file| file.parent())
+            .collect();
+        for parent in parents {
+            if let Err(e) = watcher.watch(parent, RecursiveMode::NonRecursive) {
+                warn!(
+                    "Can't watch directory '{}': {}",
+                    parent.best_effort_path_display(),
+                    e
+                );
+            }
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            inputs,
+        })
+    }
+
+    /// Waits until an input changes, then until no further change happens for the debounce
+    /// period, so a burst of saves causes a single run. Returns the first changed path
+    pub async fn changed(&mut self, debounce: Duration) -> Option<PathBuf> {
+        let changed_path = loop {
+            let path = self.events.next().await?;
+            if self.inputs.is_relevant(&path) {
+                break path;
+            }
+        };
+        debug!(
+            "Input '{}' changed, waiting for further changes",
+            changed_path.best_effort_path_display()
+        );
+
+        loop {
+            let event = self.events.next();
+            let quiet = pin!(sleep(debounce));
+            match select(event, quiet).await {
+                Either::Left((Some(_), _)) => continue,
+                Either::Left((None, _)) | Either::Right(_) => return Some(changed_path),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relevant_paths() {
+        let inputs = WatchedInputs {
+            files: HashSet::from([PathBuf::from("/project/Cargo.toml")]),
+            directories: vec![PathBuf::from("/project/src")],
+            ignored: vec![PathBuf::from("/project/src/.tessy")],
+        };
+
+        assert!(inputs.is_relevant(Path::new("/project/Cargo.toml")));
+        assert!(inputs.is_relevant(Path::new("/project/src/main.rs")));
+        assert!(!inputs.is_relevant(Path::new("/project/README.md")));
+        assert!(!inputs.is_relevant(Path::new("/project/src/.tessy/dependencies")));
+    }
+}
@@ -1,7 +1,21 @@
 pub mod data;
 
 mod application_impl;
+mod cache_report;
+mod graph_dump;
+mod input_watcher;
+mod run_lock;
 mod runtime_config;
+mod target_from_cwd;
+mod task_list;
+mod why_report;
 
 pub use application_impl::*;
+pub use cache_report::*;
+pub use graph_dump::*;
+pub use input_watcher::*;
+pub use run_lock::*;
 pub use runtime_config::*;
+pub use target_from_cwd::*;
+pub use task_list::*;
+pub use why_report::*;
@@ -0,0 +1,133 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use compio::time::sleep;
+use snafu::{ResultExt, Snafu};
+use tracing::{debug, info};
+
+const RUN_LOCK_FILE_PATH: &str = ".tessy/run.lock";
+/// How often a waiting run checks whether the lock was released
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Advisory lock preventing concurrent runs in the same root. The lock file holds the PID
+/// of the run owning it. The operating system releases the lock once the file is closed,
+/// so it is also released when the process exits on an error or gets interrupted
+#[derive(Debug)]
+pub struct RunLock {
+    _file: File,
+}
+
+impl RunLock {
+    /// Acquires the lock of the root. If another run holds it, either waits until it is
+    /// released, or fails naming the PID of the holding run
+    pub async fn acquire(root: &Path, wait: bool) -> Result<Self, RunLockError> {
+        let path = root.join(RUN_LOCK_FILE_PATH);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context(OpenSnafu { path: path.clone() })?;
+        }
+        // Not truncated, so the PID of the current holder stays readable until it is replaced
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .context(OpenSnafu { path: path.clone() })?;
+
+        let mut announced_wait = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) if wait => {
+                    if !announced_wait {
+                        info!(
+                            "Waiting for the run with PID {} to finish",
+                            Self::holder_pid(&mut file)
+                        );
+                        announced_wait = true;
+                    }
+                    sleep(LOCK_RETRY_INTERVAL).await;
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return AlreadyRunningSnafu {
+                        pid: Self::holder_pid(&mut file),
+                    }
+                    .fail();
+                }
+                Err(TryLockError::Error(source)) => {
+                    return Err(RunLockError::LockError { path, source });
+                }
+            }
+        }
+
+        file.set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| write!(file, "{}", std::process::id()))
+            .and_then(|()| file.flush())
+            .context(WriteSnafu { path })?;
+        debug!("Acquired the run lock");
+
+        Ok(Self { _file: file })
+    }
+
+    /// Reads the PID written by the run holding the lock
+    fn holder_pid(file: &mut File) -> String {
+        let mut pid = String::new();
+        match file.rewind().and_then(|()| file.read_to_string(&mut pid)) {
+            Ok(_) if !pid.trim().is_empty() => pid.trim().to_string(),
+            _ => "unknown".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum RunLockError {
+    #[snafu(display("Failed to open the run lock file '{}'", path.display()))]
+    OpenError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Failed to lock the run lock file '{}'", path.display()))]
+    LockError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Failed to write the run lock file '{}'", path.display()))]
+    WriteError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display(
+        "Another run with PID {} is in progress in this root. Pass --wait to wait for it",
+        pid
+    ))]
+    AlreadyRunning { pid: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[compio::test]
+    async fn test_second_run_fails_while_lock_is_held() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let lock = RunLock::acquire(temp_dir.path(), false).await.unwrap();
+        let error = RunLock::acquire(temp_dir.path(), false).await.unwrap_err();
+
+        match error {
+            RunLockError::AlreadyRunning { pid } => {
+                assert_eq!(pid, std::process::id().to_string())
+            }
+            other => panic!("Unexpected error: {other:?}"),
+        }
+
+        drop(lock);
+        assert!(RunLock::acquire(temp_dir.path(), false).await.is_ok());
+    }
+}
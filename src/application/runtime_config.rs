@@ -1,18 +1,93 @@
-use std::path::PathBuf;
+use std::{num::NonZeroUsize, path::PathBuf};
 
-use crate::cli::Cli;
+use crate::{
+    application::data::{ColorMode, OutputPrefix, ReportOutput},
+    cli::Cli,
+    ext::expand_path,
+    file_dependencies::DEFAULT_COMPRESSION_LEVEL,
+};
 
 #[derive(Debug, Clone)]
 pub struct RuntimeConfig {
-    pub target: String,
+    pub target: Option<String>,
+    pub target_from_cwd: bool,
     pub root: PathBuf,
+    /// Task file path relative to the root, if it differs from the standard one
+    pub task_file: Option<PathBuf>,
+    pub batch_output: bool,
+    pub raw_output: bool,
+    pub output_prefix: OutputPrefix,
+    pub color: ColorMode,
+    pub max_output_lines: Option<usize>,
+    pub status_line: bool,
+    pub seed: Option<u64>,
+    /// Run one task at a time in a fixed topological order
+    pub serial: bool,
+    /// Maximum number of tasks running at once, the number of CPUs if not set
+    pub jobs: Option<NonZeroUsize>,
+    /// Run tasks even if they are up to date
+    pub force: bool,
+    /// Hash the contents of every input, regardless of its strategy
+    pub hash_inputs: bool,
+    /// Print the tasks which would run instead of running them
+    pub dry_run: bool,
+    /// Keep running the tasks independent of a failed one
+    pub keep_going: bool,
+    /// Wait for another run in the same root to finish, instead of failing
+    pub wait_for_lock: bool,
+    /// Files to write reports of the run into
+    pub reports: Vec<ReportOutput>,
+    /// zstd level of the dependency cache, clamped to 0-22 when it is written
+    pub cache_compression: i32,
 }
 
 impl From<Cli> for RuntimeConfig {
     fn from(cli: Cli) -> Self {
         Self {
-            target: cli.target,
-            root: cli.root,
+            target: cli.target().map(str::to_string),
+            target_from_cwd: cli.target_from_cwd,
+            // Paths from the rc file and environment variables aren't expanded by a shell
+            root: cli
+                .root
+                .as_deref()
+                .map(expand_path)
+                .unwrap_or_else(|| PathBuf::from(".")),
+            task_file: cli.task_file.as_deref().map(expand_path),
+            batch_output: cli.batch_output,
+            raw_output: cli.raw_output,
+            output_prefix: cli.output_prefix.unwrap_or_default(),
+            color: cli.color.unwrap_or_default(),
+            max_output_lines: cli.max_output_lines,
+            // Raw chunks can end mid-line, where the status line can't be drawn.
+            // A dry run prints its plan instead of running anything
+            status_line: !cli.no_status_line && !cli.raw_output && !cli.dry_run,
+            seed: cli.seed,
+            serial: cli.serial,
+            jobs: cli.jobs,
+            force: cli.force,
+            hash_inputs: cli.hash_inputs,
+            dry_run: cli.dry_run,
+            keep_going: cli.keep_going,
+            wait_for_lock: cli.wait,
+            reports: cli.report,
+            cache_compression: cli.cache_compression.unwrap_or(DEFAULT_COMPRESSION_LEVEL),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(&["tessy", "build", "--root", "project"], "project")]
+    #[case(&["tessy", "build", "-r", "/tmp/project"], "/tmp/project")]
+    #[case(&["tessy", "build"], ".")]
+    fn test_root_flows_into_runtime_config(#[case] args: &[&str], #[case] expected: &str) {
+        let config = RuntimeConfig::from(Cli::try_parse_from(args).unwrap());
+
+        assert_eq!(config.root, PathBuf::from(expected));
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::{Component, Path, PathBuf};
+
+use snafu::Snafu;
+use tracing::debug;
+
+use crate::tasks::TaskTrait;
+
+/// Finds the task "owning" the current working directory.
+///
+/// A task owns the directory when one of its inputs contains it, or is contained in it.
+/// The task with the most specific (deepest) such match wins.
+pub fn resolve_target_from_cwd<'a, T: TaskTrait + 'a>(
+    tasks: impl Iterator<Item = &'a T>,
+    root: &Path,
+    cwd: &Path,
+) -> Result<String, TargetFromCwdError> {
+    let relative_cwd =
+        cwd.strip_prefix(root)
+            .map(normalize)
+            .map_err(|_| TargetFromCwdError::OutsideRoot {
+                cwd: cwd.to_path_buf(),
+            })?;
+
+    let mut best_score = 0;
+    let mut best_tasks: Vec<String> = Vec::new();
+    for task in tasks {
+        let score = task
+            .inputs()
+            .iter()
+            .filter_map(|input| relative_input(&input.path, root))
+            .map(|input| match_score(&input, &relative_cwd))
+            .max()
+            .unwrap_or(0);
+        debug!(
+            "Task '{}' matches the current directory with score {}",
+            task.id(),
+            score
+        );
+
+        if score > best_score {
+            best_score = score;
+            best_tasks = vec![task.id()];
+        } else if score == best_score && score > 0 {
+            best_tasks.push(task.id());
+        }
+    }
+
+    match best_tasks.len() {
+        0 => Err(TargetFromCwdError::NoMatchingTask {
+            cwd: cwd.to_path_buf(),
+        }),
+        1 => Ok(best_tasks.remove(0)),
+        _ => {
+            best_tasks.sort();
+            Err(TargetFromCwdError::AmbiguousTask {
+                cwd: cwd.to_path_buf(),
+                candidates: best_tasks,
+            })
+        }
+    }
+}
+
+/// Returns the input path relative to the root, dropping inputs located outside of it
+fn relative_input(input: &str, root: &Path) -> Option<PathBuf> {
+    let input = Path::new(input);
+    if input.is_absolute() {
+        input.strip_prefix(root).ok().map(normalize)
+    } else {
+        Some(normalize(input))
+    }
+}
+
+/// Depth of the shared prefix when one path contains the other, zero otherwise
+fn match_score(input: &Path, relative_cwd: &Path) -> usize {
+    if relative_cwd.starts_with(input) {
+        input.components().count()
+    } else if input.starts_with(relative_cwd) {
+        relative_cwd.components().count()
+    } else {
+        0
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+#[derive(Debug, Snafu)]
+pub enum TargetFromCwdError {
+    #[snafu(display("The current directory {} is outside of the project root", cwd.display()))]
+    OutsideRoot { cwd: PathBuf },
+    #[snafu(display("No task owns the current directory {}", cwd.display()))]
+    NoMatchingTask { cwd: PathBuf },
+    #[snafu(display(
+        "Multiple tasks own the current directory {}: {}",
+        cwd.display(),
+        candidates.join(", ")
+    ))]
+    AmbiguousTask {
+        cwd: PathBuf,
+        candidates: Vec<String>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::task_registry::TaskRegistry;
+
+    const CONFIG: &str = r#"
+tasks:
+  frontend:
+    command: "npm run build"
+    inputs: ["packages/frontend"]
+  backend:
+    command: "cargo build"
+    inputs: ["./packages/backend/src", "Cargo.toml"]
+  backend_tests:
+    command: "cargo test"
+    inputs: ["packages/backend/tests"]
+"#;
+
+    fn resolve(cwd: &str) -> Result<String, TargetFromCwdError> {
+        let registry: TaskRegistry = CONFIG.try_into().unwrap();
+        let root = Path::new("/repo");
+        resolve_target_from_cwd(registry.get_tasks_iter(), root, &root.join(cwd))
+    }
+
+    #[test]
+    fn test_resolves_task_whose_input_contains_cwd() {
+        assert_eq!(resolve("packages/frontend/src").unwrap(), "frontend");
+    }
+
+    #[test]
+    fn test_resolves_task_whose_input_is_inside_cwd() {
+        assert_eq!(resolve("packages/backend/src").unwrap(), "backend");
+    }
+
+    #[test]
+    fn test_errors_when_multiple_tasks_match_equally() {
+        let result = resolve("packages/backend");
+        match result {
+            Err(TargetFromCwdError::AmbiguousTask { candidates, .. }) => {
+                assert_eq!(candidates, vec!["backend", "backend_tests"]);
+            }
+            other => panic!("Expected AmbiguousTask, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_errors_when_no_task_matches() {
+        assert!(matches!(
+            resolve("docs"),
+            Err(TargetFromCwdError::NoMatchingTask { .. })
+        ));
+    }
+
+    #[test]
+    fn test_errors_when_cwd_is_outside_root() {
+        let registry: TaskRegistry = CONFIG.try_into().unwrap();
+        let result = resolve_target_from_cwd(
+            registry.get_tasks_iter(),
+            Path::new("/repo"),
+            Path::new("/elsewhere"),
+        );
+        assert!(matches!(
+            result,
+            Err(TargetFromCwdError::OutsideRoot { .. })
+        ));
+    }
+}
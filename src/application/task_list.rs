@@ -0,0 +1,104 @@
+use std::fmt;
+
+use crate::{config::task_registry::TaskRegistry, tasks::TaskTrait};
+
+/// Tasks defined in the task file, sorted by id, along with their descriptions and dependencies
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskList {
+    tasks: Vec<(String, Option<String>, Vec<String>)>,
+}
+
+impl TaskList {
+    pub fn new(config: &TaskRegistry) -> Self {
+        let mut tasks: Vec<_> = config
+            .get_tasks_iter()
+            .map(|task| {
+                (
+                    task.id(),
+                    task.description()
+                        .map(|description| description.to_string()),
+                    task.dependencies().clone(),
+                )
+            })
+            .collect();
+        tasks.sort();
+        Self { tasks }
+    }
+}
+
+impl fmt::Display for TaskList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tasks.is_empty() {
+            return write!(f, "(no tasks defined)");
+        }
+
+        let lines: Vec<_> = self
+            .tasks
+            .iter()
+            .map(|(task_id, description, dependencies)| {
+                let mut line = task_id.clone();
+                if let Some(description) = description {
+                    line.push_str(&format!(" - {}", description));
+                }
+                if !dependencies.is_empty() {
+                    line.push_str(&format!(" (depends on {})", dependencies.join(", ")));
+                }
+                line
+            })
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_list_is_sorted_with_dependencies() {
+        let config: TaskRegistry = r#"
+tasks:
+  test:
+    command: cargo test
+  build:
+    command: cargo build
+    dependsOn: [test, lint]
+  lint:
+    command: cargo clippy
+"#
+        .try_into()
+        .unwrap();
+
+        assert_eq!(
+            TaskList::new(&config).to_string(),
+            "build (depends on test, lint)\nlint\ntest"
+        );
+    }
+
+    #[test]
+    fn test_task_list_shows_descriptions() {
+        let config: TaskRegistry = r#"
+tasks:
+  build:
+    command: cargo build
+    description: Compiles the project
+    dependsOn: [lint]
+  lint:
+    command: cargo clippy
+"#
+        .try_into()
+        .unwrap();
+
+        assert_eq!(
+            TaskList::new(&config).to_string(),
+            "build - Compiles the project (depends on lint)\nlint"
+        );
+    }
+
+    #[test]
+    fn test_empty_task_list() {
+        let config: TaskRegistry = "tasks: {}".try_into().unwrap();
+
+        assert_eq!(TaskList::new(&config).to_string(), "(no tasks defined)");
+    }
+}
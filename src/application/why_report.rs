@@ -0,0 +1,187 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use crate::{
+    config::task_registry::TaskRegistry,
+    executor::DependencyGraph,
+    file_dependencies::{DependencyTracker, OutOfDateReason},
+    tasks::TaskTrait,
+};
+
+/// Explanation of why a task would run, as chains of reasons leading through its dependencies,
+/// e.g. "build may run because compile will run because src/lib.rs changed"
+#[derive(Debug, Clone)]
+pub struct WhyReport {
+    target: String,
+    chains: Vec<String>,
+}
+
+/// Why a single task would run
+#[derive(Debug, Clone)]
+struct TaskReasons {
+    /// Reasons found by comparing the task's own inputs with the saved ones
+    own: Vec<OutOfDateReason>,
+    /// Virtual artifacts the task consumes, along with their producers running in this run
+    produced_artifacts: Vec<(String, String)>,
+    /// Dependencies which will run, and may change the inputs of the task
+    running_dependencies: Vec<String>,
+}
+
+impl TaskReasons {
+    fn will_run(&self) -> bool {
+        !self.own.is_empty() || !self.produced_artifacts.is_empty()
+    }
+
+    fn may_run(&self) -> bool {
+        self.will_run() || !self.running_dependencies.is_empty()
+    }
+}
+
+impl WhyReport {
+    /// Checks every task needed for the target against the saved dependencies, dependencies first
+    pub async fn explain(
+        config: &TaskRegistry,
+        dependency_graph: &DependencyGraph,
+        tracker: &DependencyTracker,
+        root: &Path,
+    ) -> Self {
+        let mut reasons: HashMap<String, TaskReasons> = HashMap::new();
+        for task_id in dependency_graph.topo_order().unwrap_or_default() {
+            let Some(task) = config.get_task_by_id(&task_id) else {
+                continue;
+            };
+
+            let will_run = |id: &String| reasons.get(id).is_some_and(TaskReasons::will_run);
+            let running_dependencies = dependency_graph
+                .dependencies_of(&task_id)
+                .unwrap_or_default()
+                .iter()
+                .filter(|dependency_id| {
+                    reasons
+                        .get(*dependency_id)
+                        .is_some_and(TaskReasons::may_run)
+                })
+                .cloned()
+                .collect();
+            // The executor runs consumers of artifacts produced in the same run unconditionally
+            let mut produced_artifacts: Vec<_> = task
+                .consumes()
+                .iter()
+                .flat_map(|artifact| {
+                    reasons
+                        .keys()
+                        .filter(|producer_id| will_run(*producer_id))
+                        .filter_map(|producer_id| config.get_task_by_id(producer_id))
+                        .filter(|producer| producer.produces().contains(artifact))
+                        .map(|producer| (artifact.clone(), producer.id()))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            produced_artifacts.sort();
+            let task_reasons = TaskReasons {
+                own: tracker.out_of_date_reasons(task, root).await,
+                produced_artifacts,
+                running_dependencies,
+            };
+            reasons.insert(task_id, task_reasons);
+        }
+
+        let target = dependency_graph.target().to_string();
+        let mut chains = Self::chains(&target, &reasons);
+        chains.dedup();
+        Self { target, chains }
+    }
+
+    /// Builds every chain of reasons leading to the task
+    fn chains(task_id: &str, reasons: &HashMap<String, TaskReasons>) -> Vec<String> {
+        let Some(task_reasons) = reasons.get(task_id) else {
+            return Vec::new();
+        };
+
+        let verb = if task_reasons.will_run() {
+            "will run"
+        } else {
+            "may run"
+        };
+        let mut chains: Vec<_> = task_reasons
+            .own
+            .iter()
+            .map(|reason| format!("{} {} because {}", task_id, verb, reason))
+            .collect();
+        for (artifact, producer_id) in &task_reasons.produced_artifacts {
+            chains.extend(Self::chains(producer_id, reasons).into_iter().map(|chain| {
+                format!(
+                    "{} {} because it consumes {} and {}",
+                    task_id, verb, artifact, chain
+                )
+            }));
+        }
+        for dependency_id in &task_reasons.running_dependencies {
+            chains.extend(
+                Self::chains(dependency_id, reasons)
+                    .into_iter()
+                    .map(|chain| format!("{} {} because {}", task_id, verb, chain)),
+            );
+        }
+        chains
+    }
+}
+
+impl fmt::Display for WhyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.chains.is_empty() {
+            return write!(f, "{} is up to date and will not run", self.target);
+        }
+        write!(f, "{}", self.chains.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[compio::test]
+    async fn test_why_report_follows_running_dependencies() {
+        let root = TempDir::new().unwrap();
+        let source = root.path().join("lib.rs");
+        std::fs::write(&source, "fn main() {}").unwrap();
+        let config = TaskRegistry::try_from(
+            format!(
+                r#"
+tasks:
+  compile:
+    command: cc
+    inputs: ["{}"]
+  build:
+    command: link
+    dependsOn: [compile]
+"#,
+                source.display()
+            )
+            .as_str(),
+        )
+        .unwrap();
+        let graph = DependencyGraph::from_config(&config, &"build".to_string()).unwrap();
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(config.get_tasks_iter(), root.path())
+            .await;
+        let up_to_date = WhyReport::explain(&config, &graph, &tracker, root.path()).await;
+        assert_eq!(
+            up_to_date.to_string(),
+            "build is up to date and will not run"
+        );
+
+        std::fs::write(&source, "fn main() { changed(); }").unwrap();
+        let report = WhyReport::explain(&config, &graph, &tracker, root.path()).await;
+
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "build may run because compile will run because {} changed",
+                source.display()
+            )
+        );
+    }
+}
@@ -1,17 +1,458 @@
-use std::path::PathBuf;
+use std::{num::NonZeroUsize, path::PathBuf};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
-use crate::application::data::LogLevel;
+use crate::{
+    application::data::{ColorMode, GraphFormat, LogLevel, OutputPrefix, ReportOutput},
+    config::rc_config::RcConfig,
+    ext::expand_path,
+};
 
 #[derive(Parser, Debug, Clone)]
 #[command(version)]
 pub struct Cli {
-    pub target: String,
-    #[clap(long, short, default_value = "warn", value_enum)]
-    pub log_level: LogLevel,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
 
-    /// The root directory of the project
-    #[clap(long, short, default_value = ".")]
-    pub root: PathBuf,
+    /// The task to run [default: the `default` of the task file]. Shorthand for `run <TARGET>`,
+    /// which is needed for tasks named like a subcommand, e.g. `tessy run clean`
+    pub target: Option<String>,
+    /// Print every task defined in the task file along with its dependencies, and exit.
+    /// Same as the `list` subcommand
+    #[clap(long)]
+    pub list: bool,
+    /// Run the task owning the current directory, when no target is given
+    #[clap(long)]
+    pub target_from_cwd: bool,
+    /// Verbosity of the logs [default: warn]
+    #[clap(long, short, value_enum, env = "TESSY_LOG_LEVEL")]
+    pub log_level: Option<LogLevel>,
+
+    /// The root directory of the project [default: .]
+    #[clap(long, short, env = "TESSY_ROOT")]
+    pub root: Option<PathBuf>,
+
+    /// Path to the task file, relative to the root [default: tasks.yaml]
+    #[clap(long, env = "TESSY_TASK_FILE")]
+    pub task_file: Option<PathBuf>,
+
+    /// Write task output in batches, which is faster for tasks printing many lines
+    #[clap(long, env = "TESSY_BATCH_OUTPUT")]
+    pub batch_output: bool,
+
+    /// Copy task output as raw chunks instead of line by line, which avoids buffering very long lines.
+    /// Implies --no-status-line and ignores --batch-output
+    #[clap(long, env = "TESSY_RAW_OUTPUT")]
+    pub raw_output: bool,
+
+    /// When to prefix task output lines with the task id [default: auto]
+    #[clap(long, value_enum, env = "TESSY_OUTPUT_PREFIX")]
+    pub output_prefix: Option<OutputPrefix>,
+
+    /// When to color the task ids in the output. `auto` also honors `NO_COLOR` [default: auto]
+    #[clap(long, value_enum, value_name = "WHEN", env = "TESSY_COLOR")]
+    pub color: Option<ColorMode>,
+
+    /// Print only the last N output lines of every task stream, once the stream ends.
+    /// Tasks can override it with `maxOutputLines`
+    #[clap(long, env = "TESSY_MAX_OUTPUT_LINES")]
+    pub max_output_lines: Option<usize>,
+
+    /// After running the target, watch its inputs and the task file, and run it again whenever
+    /// they change. A change during a run stops it and starts the next one
+    #[clap(long, short, conflicts_with = "watch_poll")]
+    pub watch: bool,
+
+    /// After running the target, keep polling its inputs and run it again whenever they change.
+    /// Checks every given number of milliseconds [default: 500]
+    #[clap(long, value_name = "MILLISECONDS", num_args = 0..=1, default_missing_value = "500")]
+    pub watch_poll: Option<u64>,
+
+    /// Don't show the live status line of running tasks, which is shown on terminals
+    #[clap(long)]
+    pub no_status_line: bool,
+
+    /// Write a report of the run into a file, e.g. `junit=results.xml`. Can be repeated
+    #[clap(long, value_name = "FORMAT=PATH")]
+    pub report: Vec<ReportOutput>,
+
+    /// If another run is in progress in the same root, wait for it to finish instead of failing
+    #[clap(long, env = "TESSY_WAIT")]
+    pub wait: bool,
+
+    /// Fingerprint every input by hashing its contents, ignoring the declared strategies.
+    /// Slower, as every input is read, but files touched without being changed, e.g. by
+    /// `git checkout`, no longer cause tasks to run
+    #[clap(long, env = "TESSY_HASH_INPUTS")]
+    pub hash_inputs: bool,
+
+    /// Run every task needed for the target, even the ones which are up to date.
+    /// Their fingerprints are still saved afterwards
+    #[clap(long, short, env = "TESSY_FORCE")]
+    pub force: bool,
+
+    /// Print the tasks which would run and their commands, in the order they would run,
+    /// without running them or checking whether they are up to date
+    #[clap(long, short = 'n')]
+    pub dry_run: bool,
+
+    /// Keep running the tasks which don't depend on a failed one, like `make -k`,
+    /// and report all failures at the end
+    #[clap(long, short, env = "TESSY_KEEP_GOING")]
+    pub keep_going: bool,
+
+    /// Run at most N tasks at once [default: number of CPUs]
+    #[clap(long, short, value_name = "N", env = "TESSY_JOBS")]
+    pub jobs: Option<NonZeroUsize>,
+
+    /// Run one task at a time, in a fixed order respecting the dependencies,
+    /// so the output is the same on every run. Useful for debugging
+    #[clap(long, env = "TESSY_SERIAL")]
+    pub serial: bool,
+
+    /// Seed for randomized behavior, such as the task colors, to make runs reproducible.
+    /// Without it the default colors are used
+    #[clap(long, visible_alias = "color-seed", env = "TESSY_SEED")]
+    pub seed: Option<u64>,
+
+    /// zstd level the dependency cache is compressed with, from 0, storing it almost
+    /// uncompressed, to 22, making it smallest at the cost of speed [default: 3]
+    #[clap(
+        long,
+        value_name = "LEVEL",
+        allow_negative_numbers = true,
+        env = "TESSY_CACHE_COMPRESSION"
+    )]
+    pub cache_compression: Option<i32>,
+
+    /// Print the dependency graph of the target in the given format and exit, without running it
+    #[clap(long, value_enum, value_name = "FORMAT")]
+    pub dump_graph: Option<GraphFormat>,
+
+    /// Include every task of the task file in the dumped graph, not only the ones the target needs
+    #[clap(long, requires = "dump_graph")]
+    pub dump_graph_all: bool,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum CliCommand {
+    /// Run a task, even one named like a subcommand
+    Run {
+        /// The task to run
+        target: String,
+    },
+    /// Print every task defined in the task file along with its dependencies
+    List,
+    /// Inspect the dependency cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+    /// Explain why a task would run, following the chain of reasons through its dependencies
+    Why {
+        /// The task to explain
+        task: String,
+    },
+    /// Delete the dependency cache, so the next run starts cold
+    Clean,
+    /// Write a starter task file with a `build` and a `test` task into the root
+    Init {
+        /// Overwrite the task file if it already exists
+        #[clap(long)]
+        force: bool,
+    },
+    /// Print the dependency graph as Graphviz DOT, e.g. `tessy graph build | dot -Tsvg`
+    Graph {
+        /// The task whose graph is printed [default: the target]
+        task: Option<String>,
+        /// Include every task of the task file, not only the ones the task needs
+        #[clap(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum CacheCommand {
+    /// Show the tracked inputs of every task and the size of the cache
+    Show {
+        /// Print the report as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Forget the saved dependencies of a task, so it runs again even if nothing changed
+    Forget {
+        /// The task to forget
+        task: String,
+    },
+}
+
+impl Cli {
+    /// The task to run, given through the `run` subcommand or as the bare target
+    pub fn target(&self) -> Option<&str> {
+        match &self.command {
+            Some(CliCommand::Run { target }) => Some(target),
+            _ => self.target.as_deref(),
+        }
+    }
+
+    /// Returns true if the tasks are to be listed instead of running any
+    pub fn lists_tasks(&self) -> bool {
+        self.list || self.command == Some(CliCommand::List)
+    }
+
+    /// Directory in which the `.tessyrc` file is looked up
+    pub fn rc_dir(&self) -> PathBuf {
+        self.root
+            .as_deref()
+            .map(expand_path)
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    /// Fills in the values, which were given neither on the command line nor through
+    /// environment variables, with the ones from the `.tessyrc` file
+    pub fn with_rc_config(mut self, rc_config: RcConfig) -> Self {
+        let rc_dir = self.rc_dir();
+        self.root = self.root.or(rc_config.root.map(|root| rc_dir.join(root)));
+        self.task_file = self.task_file.or(rc_config.task_file);
+        self.target = self.target.or(rc_config.target);
+        self.log_level = self.log_level.or(rc_config.log_level);
+        self.batch_output = self.batch_output || rc_config.batch_output.unwrap_or(false);
+        self.jobs = self.jobs.or(rc_config.jobs);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn rc_config() -> RcConfig {
+        RcConfig {
+            root: Some(PathBuf::from("project")),
+            task_file: Some(PathBuf::from("ci/tasks.yaml")),
+            target: Some("build".to_string()),
+            log_level: Some(LogLevel::Debug),
+            batch_output: Some(true),
+            jobs: NonZeroUsize::new(4),
+        }
+    }
+
+    #[test]
+    fn test_rc_config_fills_missing_values() {
+        let cli = Cli::try_parse_from(["tessy"])
+            .unwrap()
+            .with_rc_config(rc_config());
+
+        assert_eq!(cli.root, Some(PathBuf::from(".").join("project")));
+        assert_eq!(cli.task_file, Some(PathBuf::from("ci/tasks.yaml")));
+        assert_eq!(cli.target.as_deref(), Some("build"));
+        assert_eq!(cli.log_level, Some(LogLevel::Debug));
+        assert!(cli.batch_output);
+        assert_eq!(cli.jobs, NonZeroUsize::new(4));
+    }
+
+    #[test]
+    fn test_cli_values_take_precedence_over_rc_config() {
+        let cli = Cli::try_parse_from([
+            "tessy", "test", "--root", "other", "-l", "error", "--jobs", "2",
+        ])
+        .unwrap()
+        .with_rc_config(rc_config());
+
+        assert_eq!(cli.root, Some(PathBuf::from("other")));
+        assert_eq!(cli.target.as_deref(), Some("test"));
+        assert_eq!(cli.log_level, Some(LogLevel::Error));
+        assert_eq!(cli.jobs, NonZeroUsize::new(2));
+    }
+
+    #[test]
+    fn test_cache_show_subcommand() {
+        let cli = Cli::try_parse_from(["tessy", "cache", "show", "--json"]).unwrap();
+
+        assert_eq!(
+            cli.command,
+            Some(CliCommand::Cache {
+                action: CacheCommand::Show { json: true }
+            })
+        );
+        assert_eq!(cli.target, None);
+    }
+
+    #[test]
+    fn test_cache_forget_subcommand() {
+        let cli = Cli::try_parse_from(["tessy", "cache", "forget", "build"]).unwrap();
+
+        assert_eq!(
+            cli.command,
+            Some(CliCommand::Cache {
+                action: CacheCommand::Forget {
+                    task: "build".to_string()
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_clean_subcommand() {
+        let cli = Cli::try_parse_from(["tessy", "--root", "other", "clean"]).unwrap();
+
+        assert_eq!(cli.command, Some(CliCommand::Clean));
+        assert_eq!(cli.target, None);
+    }
+
+    #[rstest]
+    #[case(&["tessy", "build"], Some("build"))]
+    #[case(&["tessy", "run", "build"], Some("build"))]
+    #[case(&["tessy", "run", "clean"], Some("clean"))]
+    #[case(&["tessy", "run", "init"], Some("init"))]
+    #[case(&["tessy"], None)]
+    fn test_run_subcommand(#[case] args: &[&str], #[case] expected: Option<&str>) {
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        assert_eq!(cli.target(), expected);
+    }
+
+    #[test]
+    fn test_task_named_like_a_subcommand_needs_run() {
+        let cli = Cli::try_parse_from(["tessy", "clean"]).unwrap();
+
+        assert_eq!(cli.command, Some(CliCommand::Clean));
+        assert_eq!(cli.target(), None);
+    }
+
+    #[rstest]
+    #[case(&["tessy", "list"])]
+    #[case(&["tessy", "--list"])]
+    fn test_list_subcommand_and_flag(#[case] args: &[&str]) {
+        assert!(Cli::try_parse_from(args).unwrap().lists_tasks());
+    }
+
+    #[rstest]
+    #[case(&["tessy", "init"], false)]
+    #[case(&["tessy", "init", "--force"], true)]
+    fn test_init_subcommand(#[case] args: &[&str], #[case] force: bool) {
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        assert_eq!(cli.command, Some(CliCommand::Init { force }));
+    }
+
+    #[rstest]
+    #[case(&["tessy", "graph"], None, false)]
+    #[case(&["tessy", "graph", "build", "--all"], Some("build"), true)]
+    fn test_graph_subcommand(#[case] args: &[&str], #[case] task: Option<&str>, #[case] all: bool) {
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        assert_eq!(
+            cli.command,
+            Some(CliCommand::Graph {
+                task: task.map(str::to_string),
+                all
+            })
+        );
+    }
+
+    #[test]
+    fn test_output_prefix_none() {
+        let cli = Cli::try_parse_from(["tessy", "build", "--output-prefix=none"]).unwrap();
+
+        assert_eq!(cli.output_prefix, Some(OutputPrefix::None));
+    }
+
+    #[test]
+    fn test_color_never() {
+        let cli = Cli::try_parse_from(["tessy", "build", "--color", "never"]).unwrap();
+
+        assert_eq!(cli.color, Some(ColorMode::Never));
+    }
+
+    #[test]
+    fn test_color_seed_is_an_alias_of_seed() {
+        let cli = Cli::try_parse_from(["tessy", "--color-seed", "42"]).unwrap();
+
+        assert_eq!(cli.seed, Some(42));
+    }
+
+    #[test]
+    fn test_max_output_lines() {
+        let cli = Cli::try_parse_from(["tessy", "build", "--max-output-lines", "200"]).unwrap();
+
+        assert_eq!(cli.max_output_lines, Some(200));
+    }
+
+    #[rstest]
+    #[case(&["tessy", "build"], None)]
+    #[case(&["tessy", "build", "--watch-poll"], Some(500))]
+    #[case(&["tessy", "build", "--watch-poll=2000"], Some(2000))]
+    fn test_watch_poll_interval(#[case] args: &[&str], #[case] expected: Option<u64>) {
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        assert_eq!(cli.watch_poll, expected);
+    }
+
+    #[rstest]
+    #[case(&["tessy", "build"], None)]
+    #[case(&["tessy", "build", "-j", "4"], NonZeroUsize::new(4))]
+    #[case(&["tessy", "build", "--jobs=1"], NonZeroUsize::new(1))]
+    fn test_jobs(#[case] args: &[&str], #[case] expected: Option<NonZeroUsize>) {
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        assert_eq!(cli.jobs, expected);
+        assert!(Cli::try_parse_from(["tessy", "build", "--jobs", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_force_short_flag() {
+        let cli = Cli::try_parse_from(["tessy", "build", "-f"]).unwrap();
+
+        assert!(cli.force);
+    }
+
+    #[test]
+    fn test_watch_conflicts_with_polling() {
+        assert!(Cli::try_parse_from(["tessy", "build", "-w"]).unwrap().watch);
+        assert!(Cli::try_parse_from(["tessy", "build", "--watch", "--watch-poll"]).is_err());
+    }
+
+    #[test]
+    fn test_keep_going_short_flag() {
+        let cli = Cli::try_parse_from(["tessy", "build", "-k"]).unwrap();
+
+        assert!(cli.keep_going);
+    }
+
+    #[test]
+    fn test_dry_run_short_flag() {
+        let cli = Cli::try_parse_from(["tessy", "build", "-n"]).unwrap();
+
+        assert!(cli.dry_run);
+    }
+
+    #[test]
+    fn test_list_does_not_need_a_target() {
+        let cli = Cli::try_parse_from(["tessy", "--list"]).unwrap();
+
+        assert!(cli.list);
+        assert_eq!(cli.target, None);
+    }
+
+    #[test]
+    fn test_dump_graph() {
+        let cli = Cli::try_parse_from(["tessy", "build", "--dump-graph=json", "--dump-graph-all"])
+            .unwrap();
+
+        assert_eq!(cli.dump_graph, Some(GraphFormat::Json));
+        assert!(cli.dump_graph_all);
+        assert!(Cli::try_parse_from(["tessy", "build", "--dump-graph-all"]).is_err());
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let cli = Cli::try_parse_from(["tessy", "test", "--report", "junit=results.xml"]).unwrap();
+
+        assert_eq!(
+            cli.report,
+            vec![ReportOutput::Junit(PathBuf::from("results.xml"))]
+        );
+    }
 }
@@ -1 +1,3 @@
+pub mod rc_config;
 pub mod task_registry;
+mod variables;
@@ -0,0 +1,210 @@
+use std::{
+    borrow::Cow,
+    io::ErrorKind,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use compio::fs;
+use saphyr::{LoadableYamlNode, Scalar, Yaml};
+use snafu::prelude::*;
+use tracing::debug;
+
+use crate::{application::data::LogLevel, ext::BestEffortPathExt};
+
+const RC_FILE_NAME: &str = ".tessyrc";
+
+fn get_rc_file_path(dir: &Path) -> PathBuf {
+    dir.join(RC_FILE_NAME)
+}
+
+/// Project-wide defaults read from the `.tessyrc` file.
+/// Values given on the command line or through environment variables take precedence
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RcConfig {
+    pub root: Option<PathBuf>,
+    pub task_file: Option<PathBuf>,
+    pub target: Option<String>,
+    pub log_level: Option<LogLevel>,
+    pub batch_output: Option<bool>,
+    pub jobs: Option<NonZeroUsize>,
+}
+
+impl RcConfig {
+    /// Reads the `.tessyrc` file from the given directory. A missing file yields empty defaults
+    pub async fn read(dir: &Path) -> Result<Self, RcConfigError> {
+        let path = get_rc_file_path(dir);
+        debug!("Reading rc file: {}", path.best_effort_path_display());
+
+        let bytes = match fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                debug!("No rc file found, using built-in defaults");
+                return Ok(Self::default());
+            }
+            Err(e) => {
+                return Err(e).context(RcReadSnafu {
+                    file_path: path.best_effort_path_display(),
+                });
+            }
+        };
+
+        let contents = String::from_utf8(bytes).map_err(|_| RcConfigError::InvalidEncoding {
+            file_path: path.best_effort_path_display(),
+        })?;
+        contents.as_str().try_into()
+    }
+}
+
+impl TryFrom<&str> for RcConfig {
+    type Error = RcConfigError;
+
+    fn try_from(contents: &str) -> Result<Self, Self::Error> {
+        let documents = Yaml::load_from_str(contents).context(RcParseSnafu)?;
+        let Some(document) = documents.first() else {
+            return Ok(Self::default());
+        };
+        let top_level = document
+            .as_mapping()
+            .ok_or(RcConfigError::RcTopLevelNotMap)?;
+
+        let get_str = |key: &'static str| {
+            top_level
+                .get(&Yaml::Value(Scalar::String(Cow::Borrowed(key))))
+                .and_then(|value| value.as_str())
+        };
+
+        let log_level = get_str("logLevel")
+            .map(|value| {
+                LogLevel::from_str(value, true).map_err(|_| RcConfigError::InvalidLogLevel {
+                    value: value.to_string(),
+                })
+            })
+            .transpose()?;
+
+        let batch_output = top_level
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("batchOutput"))))
+            .and_then(|value| value.as_bool());
+
+        let jobs = top_level
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("jobs"))))
+            .map(|value| {
+                value
+                    .as_integer()
+                    .and_then(|jobs| usize::try_from(jobs).ok())
+                    .and_then(NonZeroUsize::new)
+                    .ok_or_else(|| RcConfigError::InvalidJobs {
+                        value: value
+                            .as_integer()
+                            .map(|jobs| jobs.to_string())
+                            .or_else(|| value.as_str().map(str::to_string))
+                            .unwrap_or_default(),
+                    })
+            })
+            .transpose()?;
+
+        Ok(RcConfig {
+            root: get_str("root").map(PathBuf::from),
+            task_file: get_str("taskFile").map(PathBuf::from),
+            target: get_str("target").map(str::to_string),
+            log_level,
+            batch_output,
+            jobs,
+        })
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum RcConfigError {
+    #[snafu(display("Failed to read the rc file: {}", file_path))]
+    RcReadError {
+        file_path: String,
+        source: std::io::Error,
+    },
+    #[snafu(display("The rc file {} is not valid UTF-8", file_path))]
+    InvalidEncoding { file_path: String },
+    #[snafu(display("Failed to parse the rc file"))]
+    RcParseError { source: saphyr::ScanError },
+    #[snafu(display("Top level of the rc file should be a map"))]
+    RcTopLevelNotMap,
+    #[snafu(display("Unknown log level '{}' in the rc file", value))]
+    InvalidLogLevel { value: String },
+    #[snafu(display("Invalid jobs '{}' in the rc file, expected a positive number", value))]
+    InvalidJobs { value: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn rc_config_parses_all_fields() {
+        let contents = r#"
+root: ./project
+taskFile: build/tasks.yaml
+target: build
+logLevel: Debug
+batchOutput: true
+jobs: 4
+"#;
+        let rc: RcConfig = contents.try_into().unwrap();
+
+        assert_eq!(rc.root, Some(PathBuf::from("./project")));
+        assert_eq!(rc.task_file, Some(PathBuf::from("build/tasks.yaml")));
+        assert_eq!(rc.target.as_deref(), Some("build"));
+        assert_eq!(rc.log_level, Some(LogLevel::Debug));
+        assert_eq!(rc.batch_output, Some(true));
+        assert_eq!(rc.jobs, NonZeroUsize::new(4));
+    }
+
+    #[test]
+    fn rc_config_handles_empty_file() {
+        let rc: RcConfig = "".try_into().unwrap();
+        assert_eq!(rc, RcConfig::default());
+    }
+
+    #[test]
+    fn rc_config_rejects_unknown_log_level() {
+        let result: Result<RcConfig, _> = "logLevel: loud".try_into();
+        assert!(matches!(
+            result,
+            Err(RcConfigError::InvalidLogLevel { value }) if value == "loud"
+        ));
+    }
+
+    #[test]
+    fn rc_config_rejects_invalid_jobs() {
+        for contents in ["jobs: 0", "jobs: -2", "jobs: many"] {
+            let result: Result<RcConfig, _> = contents.try_into();
+            assert!(
+                matches!(result, Err(RcConfigError::InvalidJobs { .. })),
+                "{contents} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn rc_config_rejects_non_map_top_level() {
+        let result: Result<RcConfig, _> = "- root".try_into();
+        assert!(matches!(result, Err(RcConfigError::RcTopLevelNotMap)));
+    }
+
+    #[compio::test]
+    async fn rc_config_missing_file_yields_defaults() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let rc = RcConfig::read(temp_dir.path()).await.unwrap();
+        assert_eq!(rc, RcConfig::default());
+    }
+
+    #[compio::test]
+    async fn rc_config_reads_file_from_directory() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        std::fs::write(temp_dir.path().join(RC_FILE_NAME), "target: test")
+            .expect("Failed to write rc file");
+
+        let rc = RcConfig::read(temp_dir.path()).await.unwrap();
+        assert_eq!(rc.target.as_deref(), Some("test"));
+    }
+}
@@ -1,5 +1,6 @@
-use compio::{fs::File, io::AsyncReadExt, io::BufReader};
+use compio::{fs::File, io::AsyncReadExt, io::BufReader, runtime::spawn_blocking};
 use hashlink::LinkedHashMap;
+use ordered_float::OrderedFloat;
 use saphyr::{LoadableYamlNode, Scalar, Yaml};
 use snafu::prelude::*;
 use std::{
@@ -10,20 +11,53 @@ use std::{
 };
 use tracing::debug;
 
+use super::variables::{interpolate_yaml, scalar_text};
 use crate::{
     ext::BestEffortPathExt,
+    file_dependencies::FingerprintStrategy,
     tasks::{Task, TaskTrait},
 };
 
-const TASK_FILE_NAME: &str = "tasks.yaml";
+pub const TASK_FILE_NAME: &str = "tasks.yaml";
+const JSON_TASK_FILE_NAME: &str = "tasks.json";
+/// Fields a task inherits from the task it `extends`, unless it sets them itself.
+/// Fields of a group replace each other, like a single `command` and `commands`
+const INHERITED_KEYS: [&[&str]; 4] = [
+    &["command", "commands"],
+    &["env"],
+    &["inputs"],
+    &["dependsOn"],
+];
 
-fn get_task_file_path(root: &Path) -> PathBuf {
-    root.join(TASK_FILE_NAME)
+/// Path of the task file under the root, `tasks.yaml` unless only a `tasks.json` exists
+pub fn get_task_file_path(root: &Path) -> PathBuf {
+    let yaml_path = root.join(TASK_FILE_NAME);
+    let json_path = root.join(JSON_TASK_FILE_NAME);
+    if !yaml_path.exists() && json_path.exists() {
+        json_path
+    } else {
+        yaml_path
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct TaskRegistry {
     tasks: HashMap<String, Task>,
+    /// Short names for tasks, usable as the target of a run and in `dependsOn`
+    aliases: HashMap<String, String>,
+    /// Program and arguments wrapping the command of every task
+    command_wrapper: Vec<String>,
+    /// Task run when no target is given
+    default_target: Option<String>,
+}
+
+/// Contents of a single task file, before its includes are resolved
+struct TaskDocument {
+    tasks: Vec<Task>,
+    includes: Vec<PathBuf>,
+    aliases: HashMap<String, String>,
+    command_wrapper: Vec<String>,
+    default_target: Option<String>,
 }
 
 impl TaskRegistry {
@@ -31,9 +65,80 @@ impl TaskRegistry {
         Self::from_path(get_task_file_path(root)).await
     }
 
+    /// Reads the task file along with all files it includes
     pub async fn from_path(path: PathBuf) -> Result<Self, TaskRegistryCreationError> {
+        let mut registry = Self::from_path_with_includes(path, &mut Vec::new()).await?;
+        registry.validate_aliases()?;
+        registry.resolve_dependency_aliases();
+        Ok(registry)
+    }
+
+    /// Reads the task file and merges in the tasks of its includes.
+    /// Relative includes are resolved from the directory of the including file,
+    /// `include_chain` holds the files currently being included, to detect cycles
+    async fn from_path_with_includes(
+        path: PathBuf,
+        include_chain: &mut Vec<PathBuf>,
+    ) -> Result<Self, TaskRegistryCreationError> {
+        let contents = Self::read_file(&path).await?;
+        let document = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Self::parse_json_document(&contents)?
+        } else {
+            Self::parse_document(&contents)?
+        };
+        let includes = document.includes.clone();
+        let mut registry = Self::from_document(document)?;
+
+        include_chain.push(Self::canonical_path(&path).await);
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        for include in includes {
+            let include_path = base_dir.join(include);
+            let canonical_include = Self::canonical_path(&include_path).await;
+            if include_chain.contains(&canonical_include) {
+                return Err(TaskRegistryCreationError::IncludeCycle {
+                    file_path: include_path.best_effort_path_display(),
+                });
+            }
+
+            debug!(
+                "Including task file: {}",
+                include_path.best_effort_path_display()
+            );
+            let included =
+                Box::pin(Self::from_path_with_includes(include_path, include_chain)).await?;
+            for task in included.tasks.into_values() {
+                Self::insert_task(&mut registry.tasks, task)?;
+            }
+            for (alias, task_name) in included.aliases {
+                Self::insert_alias(&mut registry.aliases, alias, task_name)?;
+            }
+            if !included.command_wrapper.is_empty() {
+                debug!("Ignoring the command wrapper of an included task file");
+            }
+            if included.default_target.is_some() {
+                debug!("Ignoring the default target of an included task file");
+            }
+        }
+        include_chain.pop();
+
+        Ok(registry)
+    }
+
+    /// Canonical form of the path, so a file reached through different paths is recognized.
+    /// A path which can't be resolved, e.g. because it doesn't exist, is kept as it is
+    async fn canonical_path(path: &Path) -> PathBuf {
+        let owned_path = path.to_path_buf();
+        spawn_blocking(move || std::fs::canonicalize(&owned_path).unwrap_or(owned_path))
+            .await
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    async fn read_file(path: &Path) -> Result<String, TaskRegistryCreationError> {
         debug!("Opening config file: {}", path.best_effort_path_display());
-        let file = File::open(&path).await.context(ReadSnafu {
+        let file = File::open(path).await.context(ReadSnafu {
             file_path: path.best_effort_path_display(),
         })?;
 
@@ -49,7 +154,7 @@ impl TaskRegistry {
                 })?;
             }
         }
-        res.1.as_str().try_into()
+        Ok(res.1)
     }
 
     pub fn get_task_by_id(&self, id: impl AsRef<str>) -> Option<&Task> {
@@ -60,59 +165,410 @@ impl TaskRegistry {
         self.tasks.values()
     }
 
+    /// Fingerprints the inputs of every task with the strategy, overriding the declared ones
+    pub fn force_strategy(&mut self, strategy: FingerprintStrategy) {
+        for task in self.tasks.values_mut() {
+            task.force_strategy(strategy);
+        }
+    }
+
+    /// Returns the task id the alias stands for, or the name itself if it is not an alias
+    pub fn resolve_alias<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map(String::as_str).unwrap_or(name)
+    }
+
+    /// Program and arguments wrapping the command of every task which doesn't set `noWrapper`.
+    /// Only the wrapper of the main task file is used
+    pub fn command_wrapper(&self) -> &[String] {
+        &self.command_wrapper
+    }
+
+    /// Task to run when no target is given, set by `default` in the main task file
+    pub fn default_target(&self) -> Option<&str> {
+        self.default_target.as_deref()
+    }
+
     fn parse_tasks_from_yaml(
         top_level: &LinkedHashMap<Yaml, Yaml>,
+        vars: &HashMap<String, String>,
     ) -> Result<Vec<Task>, TaskRegistryCreationError> {
-        let tasks = top_level
+        let line_endings_key = Yaml::Value(Scalar::String(Cow::Borrowed("lineEndings")));
+        let command_key = Yaml::Value(Scalar::String(Cow::Borrowed("command")));
+        let commands_key = Yaml::Value(Scalar::String(Cow::Borrowed("commands")));
+        let env_key = Yaml::Value(Scalar::String(Cow::Borrowed("env")));
+        let line_endings = top_level.get(&line_endings_key);
+        let global_env = Self::parse_env_from_yaml(top_level)?;
+        let task_entries = top_level
             .get(&Yaml::Value(Scalar::String(Cow::Borrowed("tasks"))))
             .unwrap_or(&Yaml::Mapping(LinkedHashMap::new()))
             .as_mapping()
-            .ok_or(TaskRegistryCreationError::TasksNotMap)?
+            .ok_or(TaskRegistryCreationError::TasksNotMap)?;
+
+        let mut tasks = Vec::new();
+        for (key, value) in task_entries {
+            let (Yaml::Value(Scalar::String(task_name)), Yaml::Mapping(task_data)) = (key, value)
+            else {
+                debug!("Skipping invalid task entry: {:?}", key);
+                continue;
+            };
+
+            let mut task_data =
+                Self::resolve_extends(task_name, task_data, task_entries, &mut Vec::new())?;
+            // The task file's default is applied to tasks which don't set their own
+            if let Some(line_endings) = line_endings
+                && !task_data.contains_key(&line_endings_key)
+            {
+                task_data.insert(line_endings_key.clone(), line_endings.clone());
+            }
+            // The environment of the task file is merged under the task's own
+            if let Some(task_env) = task_data.get(&env_key) {
+                let task_env = task_env.as_mapping().ok_or_else(|| {
+                    TaskRegistryCreationError::TaskEnvNotMap {
+                        task_name: task_name.to_string(),
+                    }
+                })?;
+                let mut env = global_env.clone();
+                env.extend(
+                    task_env
+                        .iter()
+                        .map(|(name, value)| (name.clone(), value.clone())),
+                );
+                task_data.insert(env_key.clone(), Yaml::Mapping(env));
+            } else if !global_env.is_empty() {
+                task_data.insert(env_key.clone(), Yaml::Mapping(global_env.clone()));
+            }
+            for key in [&command_key, &commands_key, &env_key] {
+                if let Some(value) = task_data.get_mut(key) {
+                    interpolate_yaml(value, vars)?;
+                }
+            }
+            tasks.extend(Task::from_task_yaml(task_name, &task_data));
+        }
+
+        Ok(tasks)
+    }
+
+    /// Returns the task's data with the fields it inherits from the task it `extends`, which
+    /// may extend another task in turn. `extends_chain` holds the tasks being resolved
+    fn resolve_extends<'a>(
+        task_name: &str,
+        task_data: &LinkedHashMap<Yaml<'a>, Yaml<'a>>,
+        task_entries: &LinkedHashMap<Yaml<'a>, Yaml<'a>>,
+        extends_chain: &mut Vec<String>,
+    ) -> Result<LinkedHashMap<Yaml<'a>, Yaml<'a>>, TaskRegistryCreationError> {
+        let mut resolved = task_data.clone();
+        let Some(base_name) = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("extends"))))
+            .and_then(|v| v.as_str())
+        else {
+            return Ok(resolved);
+        };
+
+        extends_chain.push(task_name.to_string());
+        if extends_chain.iter().any(|name| name == base_name) {
+            return Err(TaskRegistryCreationError::ExtendsCycle {
+                task_name: task_name.to_string(),
+            });
+        }
+        let base_data = task_entries
+            .get(&Yaml::Value(Scalar::String(Cow::Owned(
+                base_name.to_string(),
+            ))))
+            .and_then(|v| v.as_mapping())
+            .ok_or_else(|| TaskRegistryCreationError::UnknownExtendsBase {
+                task_name: task_name.to_string(),
+                base_name: base_name.to_string(),
+            })?;
+        let base = Self::resolve_extends(base_name, base_data, task_entries, extends_chain)?;
+        extends_chain.pop();
+
+        for group in INHERITED_KEYS {
+            let keys: Vec<_> = group
+                .iter()
+                .map(|key| Yaml::Value(Scalar::String(Cow::Borrowed(*key))))
+                .collect();
+            if keys.iter().any(|key| resolved.contains_key(key)) {
+                continue;
+            }
+            for key in keys {
+                if let Some(value) = base.get(&key) {
+                    resolved.insert(key, value.clone());
+                }
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn parse_env_from_yaml<'a>(
+        top_level: &LinkedHashMap<Yaml<'a>, Yaml<'a>>,
+    ) -> Result<LinkedHashMap<Yaml<'a>, Yaml<'a>>, TaskRegistryCreationError> {
+        match top_level.get(&Yaml::Value(Scalar::String(Cow::Borrowed("env")))) {
+            Some(env) => env
+                .as_mapping()
+                .cloned()
+                .ok_or(TaskRegistryCreationError::EnvNotMap),
+            None => Ok(LinkedHashMap::new()),
+        }
+    }
+
+    fn parse_vars_from_yaml(
+        top_level: &LinkedHashMap<Yaml, Yaml>,
+    ) -> Result<HashMap<String, String>, TaskRegistryCreationError> {
+        let Some(vars) = top_level.get(&Yaml::Value(Scalar::String(Cow::Borrowed("vars")))) else {
+            return Ok(HashMap::new());
+        };
+
+        let mut parsed = HashMap::new();
+        for (name, value) in vars
+            .as_mapping()
+            .ok_or(TaskRegistryCreationError::VarsNotMap)?
+        {
+            match (name.as_str(), scalar_text(value)) {
+                (Some(name), Some(value)) => {
+                    parsed.insert(name.to_string(), value);
+                }
+                _ => debug!("Skipping invalid variable entry: {:?}", name),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn parse_includes_from_yaml(
+        top_level: &LinkedHashMap<Yaml, Yaml>,
+    ) -> Result<Vec<PathBuf>, TaskRegistryCreationError> {
+        let Some(includes) = top_level.get(&Yaml::Value(Scalar::String(Cow::Borrowed("include"))))
+        else {
+            return Ok(Vec::new());
+        };
+
+        let includes = includes
+            .as_sequence()
+            .ok_or(TaskRegistryCreationError::IncludeNotList)?
             .iter()
-            .filter_map(|(key, value)| {
-                if let Yaml::Value(Scalar::String(task_name)) = key
-                    && let Yaml::Mapping(task_data) = value
-                {
-                    return Some((task_name, task_data));
+            .filter_map(|include| {
+                let path = include.as_str().map(PathBuf::from);
+                if path.is_none() {
+                    debug!("Skipping invalid include entry: {:?}", include);
                 }
-                debug!("Skipping invalid task entry: {:?}", key);
-                None
+                path
             })
-            .filter_map(|(task_name, task_data)| Task::from_task_yaml(task_name, task_data))
-            .collect::<Vec<_>>();
+            .collect();
 
-        Ok(tasks)
+        Ok(includes)
     }
-}
 
-impl TryFrom<&str> for TaskRegistry {
-    type Error = TaskRegistryCreationError;
+    fn parse_aliases_from_yaml(
+        top_level: &LinkedHashMap<Yaml, Yaml>,
+    ) -> Result<HashMap<String, String>, TaskRegistryCreationError> {
+        let Some(aliases) = top_level.get(&Yaml::Value(Scalar::String(Cow::Borrowed("aliases"))))
+        else {
+            return Ok(HashMap::new());
+        };
 
-    fn try_from(contents: &str) -> Result<Self, Self::Error> {
+        let mut parsed = HashMap::new();
+        for (alias, task_name) in aliases
+            .as_mapping()
+            .ok_or(TaskRegistryCreationError::AliasesNotMap)?
+        {
+            match (alias.as_str(), task_name.as_str()) {
+                (Some(alias), Some(task_name)) => {
+                    Self::insert_alias(&mut parsed, alias.to_string(), task_name.to_string())?
+                }
+                _ => debug!("Skipping invalid alias entry: {:?}", alias),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn parse_command_wrapper_from_yaml(
+        top_level: &LinkedHashMap<Yaml, Yaml>,
+    ) -> Result<Vec<String>, TaskRegistryCreationError> {
+        let Some(wrapper) = top_level.get(&Yaml::Value(Scalar::String(Cow::Borrowed(
+            "commandWrapper",
+        )))) else {
+            return Ok(Vec::new());
+        };
+
+        wrapper
+            .as_sequence()
+            .ok_or(TaskRegistryCreationError::CommandWrapperNotList)?
+            .iter()
+            .map(|word| {
+                word.as_str()
+                    .map(str::to_string)
+                    .ok_or(TaskRegistryCreationError::CommandWrapperNotList)
+            })
+            .collect()
+    }
+
+    /// Parses a single task file, without resolving its includes
+    fn parse_document(contents: &str) -> Result<TaskDocument, TaskRegistryCreationError> {
         let contents_vec = Yaml::load_from_str(contents)
             .map_err(|e| TaskRegistryCreationError::ParseError { source: e })?;
         let contents = contents_vec
             .first()
             .ok_or(TaskRegistryCreationError::MalformedConfig)?;
+        Self::document_from_yaml(contents)
+    }
+
+    /// Parses a single JSON task file, by converting it into the YAML of an equivalent task file
+    fn parse_json_document(contents: &str) -> Result<TaskDocument, TaskRegistryCreationError> {
+        let contents: serde_json::Value = serde_json::from_str(contents).context(JsonParseSnafu)?;
+        Self::document_from_yaml(&json_to_yaml(contents))
+    }
 
+    fn document_from_yaml(contents: &Yaml) -> Result<TaskDocument, TaskRegistryCreationError> {
         let top_level = contents
             .as_mapping()
             .ok_or(TaskRegistryCreationError::TopLevelNotMap)?;
 
-        let tasks = Self::parse_tasks_from_yaml(top_level)?
-            .into_iter()
-            .map(|task| (task.id(), task))
-            .try_fold(HashMap::new(), |mut acc, (id, task)| {
-                if let std::collections::hash_map::Entry::Vacant(e) = acc.entry(id.clone()) {
-                    e.insert(task);
-                    Ok(acc)
-                } else {
-                    // For now unreachable, as Saphyr automatically prevents duplicate keys
-                    Err(TaskRegistryCreationError::DuplicateTask { task_name: id })
-                }
-            })?;
+        let vars = Self::parse_vars_from_yaml(top_level)?;
+        let default_target = top_level
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("default"))))
+            .map(|target| {
+                target
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or(TaskRegistryCreationError::DefaultNotString)
+            })
+            .transpose()?;
+
+        Ok(TaskDocument {
+            tasks: Self::parse_tasks_from_yaml(top_level, &vars)?,
+            includes: Self::parse_includes_from_yaml(top_level)?,
+            aliases: Self::parse_aliases_from_yaml(top_level)?,
+            command_wrapper: Self::parse_command_wrapper_from_yaml(top_level)?,
+            default_target,
+        })
+    }
 
-        Ok(TaskRegistry { tasks })
+    fn from_document(document: TaskDocument) -> Result<Self, TaskRegistryCreationError> {
+        let mut tasks = HashMap::new();
+        for task in document.tasks {
+            Self::insert_task(&mut tasks, task)?;
+        }
+        Ok(TaskRegistry {
+            tasks,
+            aliases: document.aliases,
+            command_wrapper: document.command_wrapper,
+            default_target: document.default_target,
+        })
+    }
+
+    fn insert_alias(
+        aliases: &mut HashMap<String, String>,
+        alias: String,
+        task_name: String,
+    ) -> Result<(), TaskRegistryCreationError> {
+        match aliases.get(&alias) {
+            Some(existing) if *existing != task_name => {
+                Err(TaskRegistryCreationError::DuplicateAlias { alias })
+            }
+            _ => {
+                aliases.insert(alias, task_name);
+                Ok(())
+            }
+        }
+    }
+
+    /// Checks that every alias stands for an existing task, and that no alias is named
+    /// like a task, which would make it ambiguous
+    fn validate_aliases(&self) -> Result<(), TaskRegistryCreationError> {
+        if let Some(alias) = self
+            .aliases
+            .keys()
+            .find(|alias| self.tasks.contains_key(*alias))
+        {
+            return Err(TaskRegistryCreationError::AliasShadowsTask {
+                alias: alias.clone(),
+            });
+        }
+        match self
+            .aliases
+            .iter()
+            .find(|(_, task_name)| !self.tasks.contains_key(*task_name))
+        {
+            Some((alias, task_name)) => Err(TaskRegistryCreationError::UnknownAliasTarget {
+                alias: alias.clone(),
+                task_name: task_name.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces the aliases in the dependencies of every task with the task ids
+    fn resolve_dependency_aliases(&mut self) {
+        let aliases = &self.aliases;
+        for task in self.tasks.values_mut() {
+            task.resolve_dependencies(|dependency| {
+                aliases
+                    .get(dependency)
+                    .cloned()
+                    .unwrap_or_else(|| dependency.to_string())
+            });
+        }
+    }
+
+    fn insert_task(
+        tasks: &mut HashMap<String, Task>,
+        task: Task,
+    ) -> Result<(), TaskRegistryCreationError> {
+        match tasks.entry(task.id()) {
+            std::collections::hash_map::Entry::Vacant(e) => {
+                e.insert(task);
+                Ok(())
+            }
+            // Identical definitions are merged, only differing ones conflict
+            std::collections::hash_map::Entry::Occupied(e) if *e.get() == task => Ok(()),
+            std::collections::hash_map::Entry::Occupied(e) => {
+                Err(TaskRegistryCreationError::DuplicateTask {
+                    task_name: e.key().clone(),
+                })
+            }
+        }
+    }
+}
+
+impl TryFrom<&str> for TaskRegistry {
+    type Error = TaskRegistryCreationError;
+
+    /// Parses the tasks of a single file. Includes are only resolved when reading from a path
+    fn try_from(contents: &str) -> Result<Self, Self::Error> {
+        let mut registry = Self::from_document(Self::parse_document(contents)?)?;
+        registry.validate_aliases()?;
+        registry.resolve_dependency_aliases();
+        Ok(registry)
+    }
+}
+
+/// Converts a JSON value into the YAML node it would be written as in a YAML task file
+fn json_to_yaml(value: serde_json::Value) -> Yaml<'static> {
+    match value {
+        serde_json::Value::Null => Yaml::Value(Scalar::Null),
+        serde_json::Value::Bool(flag) => Yaml::Value(Scalar::Boolean(flag)),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(integer) => Yaml::Value(Scalar::Integer(integer)),
+            None => Yaml::Value(Scalar::FloatingPoint(OrderedFloat(
+                number.as_f64().unwrap_or(f64::NAN),
+            ))),
+        },
+        serde_json::Value::String(text) => Yaml::Value(Scalar::String(Cow::Owned(text))),
+        serde_json::Value::Array(items) => {
+            Yaml::Sequence(items.into_iter().map(json_to_yaml).collect())
+        }
+        serde_json::Value::Object(entries) => Yaml::Mapping(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    (
+                        Yaml::Value(Scalar::String(Cow::Owned(key))),
+                        json_to_yaml(value),
+                    )
+                })
+                .collect(),
+        ),
     }
 }
 
@@ -125,6 +581,8 @@ pub enum TaskRegistryCreationError {
     },
     #[snafu(display("Failed to parse the config file"))]
     ParseError { source: saphyr::ScanError },
+    #[snafu(display("Failed to parse the JSON config file"))]
+    JsonParseError { source: serde_json::Error },
     #[snafu(display("Improperly formatted config file"))]
     MalformedConfig,
     #[snafu(display("Top level of config should be a map"))]
@@ -133,11 +591,45 @@ pub enum TaskRegistryCreationError {
     TasksNotMap,
     #[snafu(display("Task '{}' is defined multiple times", task_name))]
     DuplicateTask { task_name: String },
+    #[snafu(display("Include section should be a list of paths"))]
+    IncludeNotList,
+    #[snafu(display("Task file '{}' includes itself", file_path))]
+    IncludeCycle { file_path: String },
+    #[snafu(display("Aliases section should be a map"))]
+    AliasesNotMap,
+    #[snafu(display("Alias '{}' is defined multiple times", alias))]
+    DuplicateAlias { alias: String },
+    #[snafu(display("Alias '{}' refers to the unknown task '{}'", alias, task_name))]
+    UnknownAliasTarget { alias: String, task_name: String },
+    #[snafu(display("Alias '{}' has the same name as a task", alias))]
+    AliasShadowsTask { alias: String },
+    #[snafu(display("Command wrapper should be a list of strings"))]
+    CommandWrapperNotList,
+    #[snafu(display("Task '{}' extends the unknown task '{}'", task_name, base_name))]
+    UnknownExtendsBase {
+        task_name: String,
+        base_name: String,
+    },
+    #[snafu(display("Task '{}' extends itself through its base tasks", task_name))]
+    ExtendsCycle { task_name: String },
+    #[snafu(display("Default target should be a task name"))]
+    DefaultNotString,
+    #[snafu(display("Env section should be a map"))]
+    EnvNotMap,
+    #[snafu(display("Env of task '{}' should be a map", task_name))]
+    TaskEnvNotMap { task_name: String },
+    #[snafu(display("Vars section should be a map"))]
+    VarsNotMap,
+    #[snafu(display("Variable '{}' is used, but not defined in the vars section", name))]
+    UndefinedVariable { name: String },
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::file_dependencies::LineEndings;
+    use crate::tasks::TaskInput;
+    use rstest::rstest;
 
     #[compio::test]
     async fn config_returns_error_on_nonexistent_file() {
@@ -290,6 +782,66 @@ tasks:
         assert!(result.is_ok());
     }
 
+    #[compio::test]
+    async fn config_in_json_matches_the_same_config_in_yaml() {
+        let yaml = r#"
+vars:
+  profile: release
+env:
+  RUST_LOG: info
+tasks:
+  test:
+    command: cargo test
+    inputs: [src/, { path: Cargo.lock, strategy: hash }]
+  build:
+    command: cargo build --${profile}
+    dependsOn: [test]
+    timeout: 600
+    pty: true
+"#;
+        let json = r#"{
+  "vars": { "profile": "release" },
+  "env": { "RUST_LOG": "info" },
+  "tasks": {
+    "test": {
+      "command": "cargo test",
+      "inputs": ["src/", { "path": "Cargo.lock", "strategy": "hash" }]
+    },
+    "build": {
+      "command": "cargo build --${profile}",
+      "dependsOn": ["test"],
+      "timeout": 600,
+      "pty": true
+    }
+  }
+}"#;
+        let root = tempfile::TempDir::new().unwrap();
+        std::fs::write(root.path().join("tasks.json"), json).unwrap();
+
+        let from_json = TaskRegistry::read(root.path()).await.unwrap();
+        let from_yaml = TaskRegistry::try_from(yaml).unwrap();
+
+        let sorted_tasks = |registry: &TaskRegistry| {
+            let mut tasks: Vec<_> = registry.get_tasks_iter().cloned().collect();
+            tasks.sort_by_key(|task| task.id());
+            tasks
+        };
+        assert_eq!(sorted_tasks(&from_json), sorted_tasks(&from_yaml));
+    }
+
+    #[compio::test]
+    async fn config_returns_error_on_invalid_json() {
+        let root = tempfile::TempDir::new().unwrap();
+        std::fs::write(root.path().join("tasks.json"), "{ \"tasks\": ").unwrap();
+
+        let result = TaskRegistry::read(root.path()).await;
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::JsonParseError { .. })
+        ));
+    }
+
     #[compio::test]
     async fn config_handles_unicode_in_task_names() {
         let yaml_with_unicode = r#"
@@ -302,4 +854,392 @@ tasks:
         let result: Result<TaskRegistry, _> = yaml_with_unicode.try_into();
         assert!(result.is_ok());
     }
+
+    #[compio::test]
+    async fn config_tasks_with_same_id_are_equal_only_with_same_definition() {
+        let first: TaskRegistry = "tasks:\n  build:\n    command: make".try_into().unwrap();
+        let same: TaskRegistry = "tasks:\n  build:\n    command: make".try_into().unwrap();
+        let different: TaskRegistry = "tasks:\n  build:\n    command: make all"
+            .try_into()
+            .unwrap();
+
+        let task = first.get_task_by_id("build").unwrap();
+        assert_eq!(task, same.get_task_by_id("build").unwrap());
+        assert_ne!(task, different.get_task_by_id("build").unwrap());
+
+        let unique: std::collections::HashSet<_> = [
+            task,
+            same.get_task_by_id("build").unwrap(),
+            different.get_task_by_id("build").unwrap(),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(unique.len(), 2);
+    }
+
+    #[compio::test]
+    async fn config_resolves_nested_includes_relative_to_including_file() {
+        let root = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(root.path().join("ci")).unwrap();
+        std::fs::create_dir_all(root.path().join("shared")).unwrap();
+        std::fs::write(
+            root.path().join("tasks.yaml"),
+            "include: [ci/tasks.yaml]\ntasks:\n  build:\n    command: make",
+        )
+        .unwrap();
+        std::fs::write(
+            root.path().join("ci/tasks.yaml"),
+            "include: [steps.yaml, ../shared/common.yaml]\ntasks:\n  ci:\n    command: make ci",
+        )
+        .unwrap();
+        std::fs::write(
+            root.path().join("ci/steps.yaml"),
+            "tasks:\n  lint:\n    command: make lint",
+        )
+        .unwrap();
+        std::fs::write(
+            root.path().join("shared/common.yaml"),
+            "tasks:\n  fmt:\n    command: make fmt",
+        )
+        .unwrap();
+
+        let config = TaskRegistry::read(root.path()).await.unwrap();
+
+        for id in ["build", "ci", "lint", "fmt"] {
+            assert!(config.get_task_by_id(id).is_some(), "missing task '{}'", id);
+        }
+    }
+
+    #[compio::test]
+    async fn config_keeps_absolute_includes_absolute() {
+        let root = tempfile::TempDir::new().unwrap();
+        let other = tempfile::TempDir::new().unwrap();
+        let included = other.path().join("shared.yaml");
+        std::fs::write(&included, "tasks:\n  shared:\n    command: make").unwrap();
+        std::fs::create_dir_all(root.path().join("ci")).unwrap();
+        std::fs::write(
+            root.path().join("ci/tasks.yaml"),
+            format!("include: ['{}']", included.display()),
+        )
+        .unwrap();
+
+        let config = TaskRegistry::from_path(root.path().join("ci/tasks.yaml"))
+            .await
+            .unwrap();
+
+        assert!(config.get_task_by_id("shared").is_some());
+    }
+
+    #[compio::test]
+    async fn config_returns_error_on_include_cycle() {
+        let root = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(root.path().join("ci")).unwrap();
+        std::fs::write(root.path().join("tasks.yaml"), "include: [ci/steps.yaml]").unwrap();
+        std::fs::write(
+            root.path().join("ci/steps.yaml"),
+            "include: [../tasks.yaml]",
+        )
+        .unwrap();
+
+        let result = TaskRegistry::read(root.path()).await;
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::IncludeCycle { .. })
+        ));
+    }
+
+    #[compio::test]
+    async fn config_resolves_aliases_to_task_ids() {
+        let yaml = r#"
+aliases:
+  t: test
+  b: build
+tasks:
+  test:
+    command: cargo test
+  build:
+    command: cargo build
+"#;
+        let config: TaskRegistry = yaml.try_into().unwrap();
+
+        assert_eq!(config.resolve_alias("t"), "test");
+        assert_eq!(config.resolve_alias("build"), "build");
+        assert_eq!(config.resolve_alias("unknown"), "unknown");
+        // Aliases are resolved for the target only, not looked up as tasks
+        assert!(config.get_task_by_id("t").is_none());
+    }
+
+    #[compio::test]
+    async fn config_resolves_aliases_in_dependencies() {
+        let yaml = r#"
+aliases:
+  it: integration_test
+tasks:
+  integration_test:
+    command: cargo test --test integration
+  release:
+    command: cargo build --release
+    dependsOn: [it]
+"#;
+        let config: TaskRegistry = yaml.try_into().unwrap();
+
+        assert_eq!(
+            config.get_task_by_id("release").unwrap().dependencies(),
+            &vec!["integration_test".to_string()]
+        );
+        assert!(config.get_task_by_id("integration_test").is_some());
+    }
+
+    #[compio::test]
+    async fn config_returns_error_on_alias_named_like_a_task() {
+        let yaml = r#"
+aliases:
+  build: test
+tasks:
+  test:
+    command: cargo test
+  build:
+    command: cargo build
+"#;
+        let result: Result<TaskRegistry, _> = yaml.try_into();
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::AliasShadowsTask { ref alias }) if alias == "build"
+        ));
+    }
+
+    #[compio::test]
+    async fn config_returns_error_on_alias_to_unknown_task() {
+        let yaml = "aliases:\n  t: test\ntasks:\n  build:\n    command: cargo build";
+        let result: Result<TaskRegistry, _> = yaml.try_into();
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::UnknownAliasTarget { ref alias, ref task_name })
+                if alias == "t" && task_name == "test"
+        ));
+    }
+
+    #[compio::test]
+    async fn config_reads_command_wrapper() {
+        let config = TaskRegistry::try_from(
+            "commandWrapper: [docker, run, --rm, myimage]\ntasks:\n  build:\n    command: make",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.command_wrapper(),
+            ["docker", "run", "--rm", "myimage"]
+        );
+    }
+
+    #[compio::test]
+    async fn config_returns_error_when_command_wrapper_is_not_a_list_of_strings() {
+        let result = TaskRegistry::try_from("commandWrapper: [time, [nested]]\ntasks: {}");
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::CommandWrapperNotList)
+        ));
+    }
+
+    #[compio::test]
+    async fn config_applies_line_endings_to_tasks_without_their_own() {
+        let yaml = r#"
+lineEndings: normalize
+tasks:
+  build:
+    command: make
+    inputs: [src/]
+  assets:
+    command: cp
+    lineEndings: keep
+    inputs: [assets/]
+"#;
+        let config = TaskRegistry::try_from(yaml).unwrap();
+
+        let line_endings =
+            |task_id: &str| config.get_task_by_id(task_id).unwrap().inputs()[0].line_endings;
+        assert_eq!(line_endings("build"), Some(LineEndings::Normalize));
+        assert_eq!(line_endings("assets"), Some(LineEndings::Keep));
+    }
+
+    #[test]
+    fn config_forced_strategy_overrides_declared_ones() {
+        let mut config: TaskRegistry = r#"
+tasks:
+  build:
+    command: make
+    inputs:
+      - src
+      - { path: assets, strategy: mtime }
+"#
+        .try_into()
+        .unwrap();
+
+        config.force_strategy(FingerprintStrategy::Hash);
+
+        let inputs = config.get_task_by_id("build").unwrap().inputs();
+        assert!(
+            inputs
+                .iter()
+                .all(|input| input.strategy == Some(FingerprintStrategy::Hash))
+        );
+    }
+
+    #[compio::test]
+    async fn config_interpolates_vars_into_commands() {
+        let yaml = r#"
+vars:
+  version: 1.2.0
+  profile: release
+tasks:
+  package:
+    command: tar czf app-${version}.tgz target/${profile} $${HOME}/.config
+"#;
+        let config = TaskRegistry::try_from(yaml).unwrap();
+
+        assert_eq!(
+            config.get_task_by_id("package").unwrap().command(),
+            "tar czf app-1.2.0.tgz target/release ${HOME}/.config"
+        );
+    }
+
+    #[compio::test]
+    async fn config_returns_error_on_undefined_variable() {
+        let result = TaskRegistry::try_from(
+            "vars:\n  version: 1\ntasks:\n  tag:\n    command: git tag ${tag}",
+        );
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::UndefinedVariable { ref name }) if name == "tag"
+        ));
+    }
+
+    #[rstest]
+    #[case("env:\n  CI: 'true'\n")]
+    #[case("")]
+    fn config_returns_error_on_task_env_not_map(#[case] global_env: &str) {
+        let result = TaskRegistry::try_from(
+            format!(
+                "{}tasks:\n  build:\n    command: make\n    env: [CI=true]",
+                global_env
+            )
+            .as_str(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::TaskEnvNotMap { ref task_name }) if task_name == "build"
+        ));
+    }
+
+    #[compio::test]
+    async fn config_merges_global_env_under_the_task_env() {
+        let yaml = r#"
+vars:
+  profile: release
+env:
+  CARGO_TERM_COLOR: always
+  PROFILE: debug
+tasks:
+  build:
+    command: cargo build
+  release:
+    command: cargo build --release
+    env:
+      PROFILE: ${profile}
+"#;
+        let config = TaskRegistry::try_from(yaml).unwrap();
+
+        let env = |task_id: &str| config.get_task_by_id(task_id).unwrap().env().to_vec();
+        let pair = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(
+            env("build"),
+            vec![pair("CARGO_TERM_COLOR", "always"), pair("PROFILE", "debug")]
+        );
+        assert_eq!(
+            env("release"),
+            vec![
+                pair("CARGO_TERM_COLOR", "always"),
+                pair("PROFILE", "release")
+            ]
+        );
+    }
+
+    #[compio::test]
+    async fn config_extending_task_inherits_base_fields_it_does_not_set() {
+        let yaml = r#"
+tasks:
+  test-unit:
+    extends: test
+    inputs: [tests/unit]
+  test:
+    command: cargo test
+    inputs: [src, tests]
+    dependsOn: [build]
+  build:
+    command: cargo build
+"#;
+        let config = TaskRegistry::try_from(yaml).unwrap();
+
+        let task = config.get_task_by_id("test-unit").unwrap();
+        assert_eq!(task.command(), "cargo test");
+        assert_eq!(task.inputs(), &vec![TaskInput::new("tests/unit")]);
+        assert_eq!(task.dependencies(), &vec!["build".to_string()]);
+    }
+
+    #[compio::test]
+    async fn config_extending_task_with_commands_replaces_the_base_command() {
+        let yaml = r#"
+vars:
+  version: "1.0"
+tasks:
+  release:
+    extends: build
+    commands: [cargo build --release, "git tag v${version}"]
+  build:
+    command: cargo build
+"#;
+        let config = TaskRegistry::try_from(yaml).unwrap();
+
+        assert_eq!(
+            config.get_task_by_id("release").unwrap().command(),
+            "cargo build --release && git tag v1.0"
+        );
+    }
+
+    #[compio::test]
+    async fn config_returns_error_on_extends_cycle() {
+        let yaml = r#"
+tasks:
+  a:
+    extends: b
+  b:
+    extends: c
+  c:
+    command: make
+    extends: a
+"#;
+        let result = TaskRegistry::try_from(yaml);
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::ExtendsCycle { .. })
+        ));
+    }
+
+    #[compio::test]
+    async fn config_returns_error_on_unknown_extends_base() {
+        let result = TaskRegistry::try_from("tasks:\n  a:\n    extends: missing");
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::UnknownExtendsBase { ref base_name, .. })
+                if base_name == "missing"
+        ));
+    }
 }
@@ -0,0 +1,114 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use saphyr::{Scalar, Yaml};
+
+use super::task_registry::TaskRegistryCreationError;
+
+/// Replaces every `${name}` in the text with the value of the variable.
+/// `$${name}` escapes a reference, leaving `${name}` for the shell to expand
+pub fn interpolate(
+    text: &str,
+    vars: &HashMap<String, String>,
+) -> Result<String, TaskRegistryCreationError> {
+    let mut interpolated = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        // An unterminated `${` is kept as it is
+        let Some(length) = rest[start..].find('}').map(|end| end + 1) else {
+            break;
+        };
+        let before = &rest[..start];
+        let reference = &rest[start..start + length];
+
+        match before.strip_suffix('$') {
+            Some(before_escape) => {
+                interpolated.push_str(before_escape);
+                interpolated.push_str(reference);
+            }
+            None => {
+                let name = &reference[2..reference.len() - 1];
+                let value =
+                    vars.get(name)
+                        .ok_or_else(|| TaskRegistryCreationError::UndefinedVariable {
+                            name: name.to_string(),
+                        })?;
+                interpolated.push_str(before);
+                interpolated.push_str(value);
+            }
+        }
+        rest = &rest[start + length..];
+    }
+
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
+/// Interpolates the variables into the string, or into every string of the mapping or
+/// sequence, like a command with a variant per platform or given as its arguments
+pub fn interpolate_yaml(
+    value: &mut Yaml,
+    vars: &HashMap<String, String>,
+) -> Result<(), TaskRegistryCreationError> {
+    match value {
+        Yaml::Value(Scalar::String(text)) => {
+            *text = Cow::Owned(interpolate(text, vars)?);
+        }
+        Yaml::Mapping(mapping) => {
+            for value in mapping.values_mut() {
+                interpolate_yaml(value, vars)?;
+            }
+        }
+        Yaml::Sequence(sequence) => {
+            for value in sequence {
+                interpolate_yaml(value, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Text of a variable's value. Numbers and booleans are written the way YAML reads them
+pub fn scalar_text(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::Value(Scalar::String(text)) => Some(text.to_string()),
+        Yaml::Value(Scalar::Integer(number)) => Some(number.to_string()),
+        Yaml::Value(Scalar::FloatingPoint(number)) => Some(number.to_string()),
+        Yaml::Value(Scalar::Boolean(flag)) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn vars() -> HashMap<String, String> {
+        HashMap::from([
+            ("version".to_string(), "1.2.0".to_string()),
+            ("target".to_string(), "release".to_string()),
+        ])
+    }
+
+    #[rstest]
+    #[case("cargo build", "cargo build")]
+    #[case("cargo build --${target}", "cargo build --release")]
+    #[case("tag v${version}-${target}", "tag v1.2.0-release")]
+    #[case("echo $${HOME} $PATH", "echo ${HOME} $PATH")]
+    #[case("echo ${unterminated", "echo ${unterminated")]
+    fn test_interpolate(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(interpolate(text, &vars()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_undefined_variable_is_an_error() {
+        let result = interpolate("deploy ${environment}", &vars());
+
+        assert!(matches!(
+            result,
+            Err(TaskRegistryCreationError::UndefinedVariable { name }) if name == "environment"
+        ));
+    }
+}
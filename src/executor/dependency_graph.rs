@@ -1,7 +1,8 @@
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
-use snafu::location;
+use snafu::{Snafu, location};
 use tracing::debug;
 use tracing::error;
 
@@ -18,27 +19,112 @@ use crate::tasks::TaskTrait;
 /// This allows us to initialize the execution
 #[derive(Debug, Clone)]
 pub struct DependencyGraph {
+    target: String,
+    /// Tasks depending on each task, i.e. the tasks which can run once it has finished
     task_parents: HashMap<String, Vec<String>>,
+    /// Dependencies of each task, limited to the tasks in the graph
+    task_dependencies: HashMap<String, Vec<String>>,
 }
 
 impl DependencyGraph {
-    pub fn from_config(config: &TaskRegistry, final_task: &String) -> Self {
+    /// Builds the graph of the tasks needed to execute the final task, along with the
+    /// `after` hooks of those tasks. Fails if those tasks depend on each other in a cycle
+    /// or on tasks which don't exist, as they could never run
+    pub fn from_config(
+        config: &TaskRegistry,
+        final_task: &String,
+    ) -> Result<Self, DependencyGraphError> {
+        let graph_dependencies = Self::graph_dependencies(config);
         // First, collect all tasks that are needed to execute the final task
-        let needed_tasks = Self::collect_needed_tasks(config, final_task);
+        let needed_tasks = Self::collect_needed_tasks(config, &graph_dependencies, final_task)?;
         debug!("Needed tasks for {}: {:?}", final_task, needed_tasks);
 
+        Ok(Self::from_needed_tasks(
+            final_task,
+            &graph_dependencies,
+            needed_tasks,
+        ))
+    }
+
+    /// Builds the graph of every task in the registry, including the ones the final task
+    /// doesn't need
+    pub fn with_all_tasks(
+        config: &TaskRegistry,
+        final_task: &String,
+    ) -> Result<Self, DependencyGraphError> {
+        let graph_dependencies = Self::graph_dependencies(config);
+        let mut all_tasks = HashSet::new();
+        let mut stack = Vec::new();
+        for task in config.get_tasks_iter() {
+            Self::collect_dependencies_recursive(
+                config,
+                &graph_dependencies,
+                &task.id(),
+                &mut all_tasks,
+                &mut stack,
+            )?;
+        }
+
+        Ok(Self::from_needed_tasks(
+            final_task,
+            &graph_dependencies,
+            all_tasks,
+        ))
+    }
+
+    /// Tasks each task has to wait for: its dependencies and `before` hooks,
+    /// and the tasks declaring it as their `after` hook
+    fn graph_dependencies(config: &TaskRegistry) -> HashMap<String, Vec<String>> {
+        let mut graph_dependencies: HashMap<String, Vec<String>> = HashMap::new();
+        for task in config.get_tasks_iter() {
+            let dependencies = graph_dependencies.entry(task.id()).or_default();
+            for dependency in task.dependencies().iter().chain(task.before()) {
+                if !dependencies.contains(dependency) {
+                    dependencies.push(dependency.clone());
+                }
+            }
+        }
+
+        let mut hooked_tasks: Vec<_> = config
+            .get_tasks_iter()
+            .flat_map(|task| {
+                task.after()
+                    .iter()
+                    .map(move |hook| (hook.clone(), task.id()))
+            })
+            .collect();
+        hooked_tasks.sort();
+        for (hook, task_id) in hooked_tasks {
+            let dependencies = graph_dependencies.entry(hook).or_default();
+            if !dependencies.contains(&task_id) {
+                dependencies.push(task_id);
+            }
+        }
+
+        graph_dependencies
+    }
+
+    fn from_needed_tasks(
+        final_task: &String,
+        graph_dependencies: &HashMap<String, Vec<String>>,
+        needed_tasks: HashSet<String>,
+    ) -> Self {
         // Only initialize task_parents for tasks that are needed
         let mut task_parents = needed_tasks
             .iter()
             .map(|task_id| (task_id.clone(), Vec::new()))
             .collect::<HashMap<_, _>>();
+        let mut task_dependencies = task_parents.clone();
 
         // Build dependency graph only for needed tasks
         for task_id in &needed_tasks {
-            if let Some(task) = config.get_task_by_id(task_id) {
-                for dep_id in task.dependencies() {
+            if let Some(dependency_ids) = graph_dependencies.get(task_id) {
+                for dep_id in dependency_ids {
                     if let Some(parents) = task_parents.get_mut(dep_id) {
                         parents.push(task_id.clone());
+                        if let Some(dependencies) = task_dependencies.get_mut(task_id) {
+                            dependencies.push(dep_id.clone());
+                        }
                     } else {
                         error!(
                             "Assumption that all task IDs should be present in the task_parents map failed {}",
@@ -55,47 +141,168 @@ impl DependencyGraph {
         }
 
         debug!("Constructed dependency graph: {:?}", task_parents);
-        DependencyGraph { task_parents }
+        DependencyGraph {
+            target: final_task.clone(),
+            task_parents,
+            task_dependencies,
+        }
+    }
+
+    /// The task, which the user wants to execute
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Tasks depending on the given one, which can only run after it has finished
+    pub fn dependents_of(&self, task_id: impl AsRef<str>) -> Option<&[String]> {
+        self.task_parents.get(task_id.as_ref()).map(Vec::as_slice)
     }
 
-    pub fn get_parent_by_id(&self, task_id: impl AsRef<str>) -> Option<&Vec<String>> {
-        self.task_parents.get(task_id.as_ref())
+    /// Dependencies of the given task, which have to finish before it can run.
+    /// Only dependencies which are part of the graph are returned
+    pub fn dependencies_of(&self, task_id: impl AsRef<str>) -> Option<&[String]> {
+        self.task_dependencies
+            .get(task_id.as_ref())
+            .map(Vec::as_slice)
+    }
+
+    /// Orders the tasks so every task comes after all of its dependencies. Tasks which
+    /// become ready at the same time are ordered by id, so the order is deterministic.
+    /// Returns `None` if the graph contains a cycle
+    pub fn topo_order(&self) -> Option<Vec<String>> {
+        let mut counts = self.dependency_counts();
+        let mut ready: BTreeSet<&str> = self
+            .task_parents
+            .keys()
+            .filter(|task_id| counts[*task_id] == 0)
+            .map(String::as_str)
+            .collect();
+        let mut order = Vec::with_capacity(counts.len());
+
+        while let Some(task_id) = ready.pop_first() {
+            order.push(task_id.to_string());
+            for dependent_id in self.dependents_of(task_id).unwrap_or_default() {
+                if let Some(count) = counts.get_mut(dependent_id) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.insert(dependent_id.as_str());
+                    }
+                }
+            }
+        }
+
+        (order.len() == self.task_parents.len()).then_some(order)
     }
 
     pub fn get_task_parents_iter(&self) -> impl Iterator<Item = (&String, &Vec<String>)> {
         self.task_parents.iter()
     }
 
-    /// Recursively collect all tasks needed to execute the final task
-    fn collect_needed_tasks(config: &TaskRegistry, final_task: &String) -> HashSet<String> {
+    /// Counts the dependencies of every task in the graph. Only dependencies which are
+    /// part of the graph are counted, as only those will ever complete
+    pub fn dependency_counts(&self) -> HashMap<String, u32> {
+        let mut counts: HashMap<String, u32> = self
+            .task_parents
+            .keys()
+            .map(|task_id| (task_id.clone(), 0))
+            .collect();
+
+        for parent_id in self.task_parents.values().flatten() {
+            if let Some(count) = counts.get_mut(parent_id) {
+                *count += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Recursively collect all tasks needed to execute the final task, and the `after` hooks
+    /// of the collected tasks along with everything they need
+    fn collect_needed_tasks(
+        config: &TaskRegistry,
+        graph_dependencies: &HashMap<String, Vec<String>>,
+        final_task: &String,
+    ) -> Result<HashSet<String>, DependencyGraphError> {
         let mut needed_tasks = HashSet::new();
-        let mut visited = HashSet::new();
+        let mut stack = Vec::new();
+        let mut pending = vec![final_task.clone()];
 
-        Self::collect_dependencies_recursive(config, final_task, &mut needed_tasks, &mut visited);
+        // Hooks are collected once the task they follow is done, as they depend on it
+        while let Some(task_id) = pending.pop() {
+            Self::collect_dependencies_recursive(
+                config,
+                graph_dependencies,
+                &task_id,
+                &mut needed_tasks,
+                &mut stack,
+            )?;
+            for task in needed_tasks
+                .iter()
+                .filter_map(|id| config.get_task_by_id(id))
+            {
+                for hook in task.after() {
+                    if config.get_task_by_id(hook).is_none() {
+                        return MissingDependencySnafu {
+                            task: task.id(),
+                            missing: hook.clone(),
+                        }
+                        .fail();
+                    }
+                    if !needed_tasks.contains(hook) && !pending.contains(hook) {
+                        pending.push(hook.clone());
+                    }
+                }
+            }
+        }
 
-        needed_tasks
+        Ok(needed_tasks)
     }
 
-    /// Recursively collect dependencies for a task
+    /// Recursively collect dependencies for a task. The stack holds the tasks whose
+    /// dependencies are being collected, so reaching one of them again means a cycle
     fn collect_dependencies_recursive(
         config: &TaskRegistry,
+        graph_dependencies: &HashMap<String, Vec<String>>,
         task_id: &String,
         needed_tasks: &mut HashSet<String>,
-        visited: &mut HashSet<String>,
-    ) {
-        // Avoid cycles
-        if visited.contains(task_id) {
-            return;
+        stack: &mut Vec<String>,
+    ) -> Result<(), DependencyGraphError> {
+        if let Some(position) = stack.iter().position(|id| id == task_id) {
+            let mut path = stack[position..].to_vec();
+            path.push(task_id.clone());
+            return CycleDetectedSnafu { path }.fail();
         }
-        visited.insert(task_id.clone());
 
-        // Add this task to the needed set
-        needed_tasks.insert(task_id.clone());
+        // Tasks already collected had all their dependencies collected too
+        if !needed_tasks.insert(task_id.clone()) {
+            return Ok(());
+        }
 
         // If the task exists in config, recursively collect its dependencies
-        if let Some(task) = config.get_task_by_id(task_id) {
-            for dep_id in task.dependencies() {
-                Self::collect_dependencies_recursive(config, dep_id, needed_tasks, visited);
+        stack.push(task_id.clone());
+        if config.get_task_by_id(task_id).is_some() {
+            for dep_id in graph_dependencies.get(task_id).into_iter().flatten() {
+                // The shortest cycle, usually a copied task whose `dependsOn` wasn't updated
+                if dep_id == task_id {
+                    return SelfDependencySnafu {
+                        task: task_id.clone(),
+                    }
+                    .fail();
+                }
+                if config.get_task_by_id(dep_id).is_none() {
+                    return MissingDependencySnafu {
+                        task: task_id.clone(),
+                        missing: dep_id.clone(),
+                    }
+                    .fail();
+                }
+                Self::collect_dependencies_recursive(
+                    config,
+                    graph_dependencies,
+                    dep_id,
+                    needed_tasks,
+                    stack,
+                )?;
             }
         } else {
             error!(
@@ -103,5 +310,276 @@ impl DependencyGraph {
                 location!()
             );
         }
+        stack.pop();
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum DependencyGraphError {
+    #[snafu(display("Tasks depend on each other in a cycle: {}", path.join(" -> ")))]
+    CycleDetected {
+        /// Tasks forming the cycle, starting and ending with the same task
+        path: Vec<String>,
+    },
+    #[snafu(display("Task '{}' depends on '{}', which does not exist", task, missing))]
+    MissingDependency { task: String, missing: String },
+    #[snafu(display("Task '{}' depends on itself", task))]
+    SelfDependency { task: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn registry() -> TaskRegistry {
+        r#"
+tasks:
+  lint:
+    command: cargo clippy
+  test:
+    command: cargo test
+  build:
+    command: cargo build
+    dependsOn: [lint, test]
+"#
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_dependency_counts_of_full_graph() {
+        let graph = DependencyGraph::from_config(&registry(), &"build".to_string()).unwrap();
+
+        let counts = graph.dependency_counts();
+
+        assert_eq!(counts["build"], 2);
+        assert_eq!(counts["lint"], 0);
+        assert_eq!(counts["test"], 0);
+    }
+
+    #[test]
+    fn test_dependency_counts_ignore_dependencies_outside_of_graph() {
+        // A subset run, in which the dependencies of the target were left out
+        let graph = DependencyGraph {
+            target: "build".to_string(),
+            task_parents: HashMap::from([("build".to_string(), Vec::new())]),
+            task_dependencies: HashMap::from([("build".to_string(), Vec::new())]),
+        };
+
+        let counts = graph.dependency_counts();
+
+        assert_eq!(counts, HashMap::from([("build".to_string(), 0)]));
+    }
+
+    #[test]
+    fn test_dependencies_and_dependents_of_task() {
+        let graph = DependencyGraph::from_config(&registry(), &"build".to_string()).unwrap();
+
+        let mut dependencies = graph.dependencies_of("build").unwrap().to_vec();
+        dependencies.sort();
+
+        assert_eq!(dependencies, vec!["lint", "test"]);
+        assert_eq!(graph.dependents_of("lint").unwrap(), ["build"]);
+        assert!(graph.dependents_of("build").unwrap().is_empty());
+        assert!(graph.dependencies_of("unknown").is_none());
+    }
+
+    #[test]
+    fn test_topo_order_puts_dependencies_first() {
+        let graph = DependencyGraph::from_config(&registry(), &"build".to_string()).unwrap();
+
+        assert_eq!(
+            graph.topo_order(),
+            Some(vec![
+                "lint".to_string(),
+                "test".to_string(),
+                "build".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_topo_order_of_cycle_is_none() {
+        let graph = DependencyGraph {
+            target: "a".to_string(),
+            task_parents: HashMap::from([
+                ("a".to_string(), vec!["b".to_string()]),
+                ("b".to_string(), vec!["a".to_string()]),
+            ]),
+            task_dependencies: HashMap::from([
+                ("a".to_string(), vec!["b".to_string()]),
+                ("b".to_string(), vec!["a".to_string()]),
+            ]),
+        };
+
+        assert_eq!(graph.topo_order(), None);
+    }
+
+    #[test]
+    fn test_two_task_cycle_is_detected() {
+        let config: TaskRegistry = r#"
+tasks:
+  a:
+    command: echo a
+    dependsOn: [b]
+  b:
+    command: echo b
+    dependsOn: [a]
+"#
+        .try_into()
+        .unwrap();
+
+        let result = DependencyGraph::from_config(&config, &"a".to_string());
+
+        assert!(matches!(
+            result,
+            Err(DependencyGraphError::CycleDetected { path }) if path == ["a", "b", "a"]
+        ));
+    }
+
+    #[rstest]
+    #[case("dependsOn: [lint, build]")]
+    #[case("after: [build]")]
+    fn test_self_dependency_is_rejected(#[case] dependencies: &str) {
+        let config: TaskRegistry = format!(
+            "tasks:\n  lint:\n    command: cargo clippy\n  build:\n    command: cargo build\n    {}",
+            dependencies
+        )
+        .as_str()
+        .try_into()
+        .unwrap();
+
+        for result in [
+            DependencyGraph::from_config(&config, &"build".to_string()),
+            DependencyGraph::with_all_tasks(&config, &"lint".to_string()),
+        ] {
+            assert!(matches!(
+                result,
+                Err(DependencyGraphError::SelfDependency { task }) if task == "build"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_three_task_cycle_is_detected_below_the_target() {
+        let config: TaskRegistry = r#"
+tasks:
+  release:
+    command: echo release
+    dependsOn: [a]
+  a:
+    command: echo a
+    dependsOn: [b]
+  b:
+    command: echo b
+    dependsOn: [c]
+  c:
+    command: echo c
+    dependsOn: [a]
+"#
+        .try_into()
+        .unwrap();
+
+        let result = DependencyGraph::from_config(&config, &"release".to_string());
+
+        assert!(matches!(
+            result,
+            Err(DependencyGraphError::CycleDetected { path }) if path == ["a", "b", "c", "a"]
+        ));
+    }
+
+    #[test]
+    fn test_shared_dependency_is_not_a_cycle() {
+        let config: TaskRegistry = r#"
+tasks:
+  lint:
+    command: cargo clippy
+  test:
+    command: cargo test
+    dependsOn: [lint]
+  build:
+    command: cargo build
+    dependsOn: [lint, test]
+"#
+        .try_into()
+        .unwrap();
+
+        assert!(DependencyGraph::from_config(&config, &"build".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_hooks_run_around_the_task() {
+        let config: TaskRegistry = r#"
+tasks:
+  fetch:
+    command: git fetch
+  build:
+    command: cargo build
+    before: [fetch]
+    after: [notify]
+  notify:
+    command: echo done
+"#
+        .try_into()
+        .unwrap();
+
+        let graph = DependencyGraph::from_config(&config, &"build".to_string()).unwrap();
+
+        assert_eq!(
+            graph.topo_order(),
+            Some(vec![
+                "fetch".to_string(),
+                "build".to_string(),
+                "notify".to_string()
+            ])
+        );
+        assert_eq!(graph.dependencies_of("notify").unwrap(), ["build"]);
+    }
+
+    #[test]
+    fn test_cycle_through_hooks_is_detected() {
+        let config: TaskRegistry = r#"
+tasks:
+  build:
+    command: cargo build
+    before: [notify]
+    after: [notify]
+  notify:
+    command: echo done
+"#
+        .try_into()
+        .unwrap();
+
+        let result = DependencyGraph::from_config(&config, &"build".to_string());
+
+        assert!(matches!(
+            result,
+            Err(DependencyGraphError::CycleDetected { path }) if path == ["build", "notify", "build"]
+        ));
+    }
+
+    #[test]
+    fn test_missing_dependency_names_the_referrer() {
+        let config: TaskRegistry = r#"
+tasks:
+  lint:
+    command: cargo clippy
+  build:
+    command: cargo build
+    dependsOn: [lint, tset]
+"#
+        .try_into()
+        .unwrap();
+
+        let result = DependencyGraph::from_config(&config, &"build".to_string());
+
+        assert!(matches!(
+            result,
+            Err(DependencyGraphError::MissingDependency { task, missing })
+                if task == "build" && missing == "tset"
+        ));
     }
 }
@@ -1,22 +1,82 @@
+use std::collections::{HashSet, VecDeque};
+use std::io::IsTerminal;
 use std::num::NonZeroUsize;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
 use std::thread::available_parallelism;
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, sync::Arc};
 
 use compio::dispatcher::{Dispatcher, DispatcherBuilder};
 use compio::runtime::spawn;
+use compio::time::sleep;
 use futures::StreamExt;
+use futures::future::{Either, select};
 use futures_channel::mpsc::{self, UnboundedSender};
 use snafu::{ResultExt, Snafu};
-use tracing::{debug, info};
+use tracing::{Instrument, Span, debug, field, info, info_span, warn};
 
 use crate::application::RuntimeConfig;
 use crate::config::task_registry::TaskRegistry;
-use crate::executor::DependencyGraph;
+use crate::executor::{
+    DependencyGraph, RunReport, SpeculativeFingerprints, TaskReport, TaskStatus,
+};
+use crate::ext::CancellationToken;
 use crate::file_dependencies::DependencyTracker;
-use crate::tasks::{Task, TaskError, TaskTrait};
+use crate::tasks::{
+    OutputOptions, StatusLine, Task, TaskContext, TaskError, TaskTrait, ensure_clean_git,
+};
 
 /// Default number of worker threads when unable to determine system parallelism
 const DEFAULT_WORKER_THREADS: usize = 1;
+/// How often the status line is redrawn to advance the spinner and elapsed times
+const STATUS_LINE_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Message sent to the result processing loop once a task has finished or was skipped
+struct TaskCompletion {
+    task_id: String,
+    duration: Duration,
+    result: Result<TaskStatus, TaskError>,
+}
+
+impl TaskCompletion {
+    fn into_report(self) -> TaskReport {
+        match self.result {
+            Ok(status) => TaskReport {
+                task_id: self.task_id,
+                status,
+                duration: self.duration,
+                exit_code: (status == TaskStatus::Executed).then_some(0),
+                error: None,
+                stderr_tail: None,
+            },
+            Err(TaskError::InterruptedError { .. }) => TaskReport {
+                task_id: self.task_id,
+                status: TaskStatus::Cancelled,
+                duration: self.duration,
+                exit_code: None,
+                error: None,
+                stderr_tail: None,
+            },
+            Err(error) => TaskReport {
+                task_id: error.task_id().to_string(),
+                status: TaskStatus::Failed,
+                duration: self.duration,
+                exit_code: error.exit_code(),
+                stderr_tail: error.stderr_tail().map(str::to_string),
+                error: Some(snafu::Report::from_error(error).to_string()),
+            },
+        }
+    }
+}
+
+/// Tasks dispatched for execution, and the ones waiting for a free slot under `--jobs`
+#[derive(Default)]
+struct JobSlots {
+    running: usize,
+    queued: VecDeque<(Task, Span)>,
+}
 
 pub struct Executor {
     dispatcher: Dispatcher,
@@ -24,6 +84,14 @@ pub struct Executor {
     config: Arc<TaskRegistry>,
     dependency_graph: Arc<DependencyGraph>,
     saved_dependencies: Arc<DependencyTracker>,
+    task_context: TaskContext,
+    /// Virtual artifacts produced by the tasks executed so far in this run
+    produced_artifacts: Mutex<HashSet<String>>,
+    /// Inputs of waiting tasks, fingerprinted while their dependencies run
+    speculative_fingerprints: SpeculativeFingerprints,
+    job_slots: Mutex<JobSlots>,
+    /// Number of tasks dispatched so far, each of which reports back exactly once
+    dispatched_tasks: AtomicUsize,
 }
 
 impl Executor {
@@ -34,9 +102,38 @@ impl Executor {
         app_config: Arc<RuntimeConfig>,
         saved_dependencies: Arc<DependencyTracker>,
     ) -> Result<Self, ExecutorCreationError> {
-        let workers_num = Self::determine_worker_count();
+        let workers_num = if app_config.serial {
+            NonZeroUsize::MIN
+        } else {
+            app_config.jobs.unwrap_or_else(Self::determine_worker_count)
+        };
         debug!("Using {} worker threads for task execution", workers_num);
 
+        let task_context = TaskContext {
+            cancellation: CancellationToken::new(),
+            output: OutputOptions {
+                batched: app_config.batch_output,
+                color_seed: app_config.seed,
+                colored: app_config.color.resolve(),
+                raw: app_config.raw_output,
+                without_prefix: !app_config.output_prefix.is_enabled(
+                    dependency_graph.get_task_parents_iter().count(),
+                    std::io::stdout().is_terminal(),
+                ),
+                max_lines: app_config.max_output_lines,
+                ..OutputOptions::default()
+            },
+            root: app_config.root.clone(),
+            command_wrapper: config.command_wrapper().to_vec(),
+            target: dependency_graph.target().to_string(),
+            ..TaskContext::default()
+        };
+
+        let speculative_fingerprints = SpeculativeFingerprints::new(
+            app_config.root.clone(),
+            task_context.cancellation.clone(),
+        );
+
         let dispatcher = DispatcherBuilder::new()
             .worker_threads(workers_num)
             .build()
@@ -48,9 +145,19 @@ impl Executor {
             dependency_graph,
             app_config,
             saved_dependencies,
+            task_context,
+            produced_artifacts: Mutex::new(HashSet::new()),
+            speculative_fingerprints,
+            job_slots: Mutex::default(),
+            dispatched_tasks: AtomicUsize::new(0),
         })
     }
 
+    /// Token stopping the tasks of the run, which end up cancelled in the report
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.task_context.cancellation
+    }
+
     /// Determines the optimal number of worker threads for task execution
     fn determine_worker_count() -> NonZeroUsize {
         available_parallelism()
@@ -61,32 +168,120 @@ impl Executor {
             .unwrap_or_else(|| NonZeroUsize::new(DEFAULT_WORKER_THREADS).unwrap())
     }
 
-    /// Main execution method that coordinates task execution based on dependencies
-    pub async fn execute(&self) -> Result<Vec<String>, ExecutionError> {
-        let mut dependency_counts = self.initialize_dependency_counts();
-        let (task_sender, mut task_receiver) = mpsc::unbounded::<Result<String, TaskError>>();
+    /// Main execution method that coordinates task execution based on dependencies.
+    /// Task failures are recorded in the returned report, stopping the run at the first one
+    /// unless `--keep-going` is set
+    pub async fn execute(&self) -> Result<RunReport, ExecutionError> {
+        let mut dependency_counts = self.dependency_graph.dependency_counts();
+        let (task_sender, mut task_receiver) = mpsc::unbounded::<TaskCompletion>();
 
-        // Dispatch all tasks that have no dependencies
-        self.dispatch_initial_tasks(&task_sender, &self.dependency_graph)
-            .await?;
+        if self.app_config.status_line && StatusLine::enable() {
+            spawn(async {
+                while StatusLine::refresh() {
+                    sleep(STATUS_LINE_REFRESH_INTERVAL).await;
+                }
+            })
+            .detach();
+        }
 
-        // Process task completion results until target is reached
-        self.process_task_results(&mut task_receiver, &mut dependency_counts, &task_sender)
-            .await
+        let result = async {
+            if self.app_config.serial {
+                return self.execute_serially().await;
+            }
+
+            // Dispatch all tasks that have no dependencies
+            self.dispatch_initial_tasks(&task_sender, &dependency_counts)
+                .await?;
+
+            // Process task completion results until target is reached
+            self.process_task_results(&mut task_receiver, &mut dependency_counts, task_sender)
+                .await
+        }
+        .instrument(info_span!("run", target = %self.dependency_graph.target()))
+        .await;
+
+        StatusLine::disable();
+        self.speculative_fingerprints.stop();
+        result
+    }
+
+    /// Runs the tasks one at a time in a fixed topological order, so runs are reproducible.
+    /// Stops at the first failing task, or with `--keep-going` skips the tasks depending on it
+    async fn execute_serially(&self) -> Result<RunReport, ExecutionError> {
+        let order = self
+            .dependency_graph
+            .topo_order()
+            .ok_or(ExecutionError::SerialOrderCycleError)?;
+        debug!("Running {} tasks serially: {:?}", order.len(), order);
+
+        let (task_sender, mut task_receiver) = mpsc::unbounded::<TaskCompletion>();
+        let mut report = RunReport::default();
+        // Failed tasks and the ones which can't run, because they depend on a failed task
+        let mut blocked_tasks = HashSet::new();
+
+        for task_id in order {
+            if self.task_context.cancellation.is_cancelled() {
+                warn!("Run cancelled, not running the remaining tasks");
+                break;
+            }
+            let Some(task) = self.config.get_task_by_id(&task_id).cloned() else {
+                continue;
+            };
+            if self
+                .dependency_graph
+                .dependencies_of(&task_id)
+                .unwrap_or_default()
+                .iter()
+                .any(|dependency| blocked_tasks.contains(dependency))
+            {
+                debug!(
+                    "Task '{}' depends on a failed task, not running it",
+                    task_id
+                );
+                blocked_tasks.insert(task_id);
+                continue;
+            }
+            self.dispatch_task(task_sender.clone(), task).await?;
+            let completion = task_receiver
+                .next()
+                .await
+                .ok_or(ExecutionError::ExecutionEndedPrematurely)?;
+
+            StatusLine::task_finished(&completion.task_id);
+            let task_report = completion.into_report();
+            let status = task_report.status;
+            report.push(task_report);
+            self.release_job_slot(status, &task_sender)?;
+
+            if status == TaskStatus::Executed {
+                self.record_produced_artifacts(&task_id);
+            }
+            if status == TaskStatus::Failed {
+                if !self.app_config.keep_going {
+                    warn!("Task '{}' failed, stopping execution", task_id);
+                    return Ok(report);
+                }
+                warn!("Task '{}' failed, continuing with the other tasks", task_id);
+                blocked_tasks.insert(task_id);
+            }
+        }
+
+        self.warn_about_blocked_tasks(&report);
+        Ok(report)
     }
 
     /// Dispatches all tasks that have no dependencies and are ready to execute immediately
     async fn dispatch_initial_tasks(
         &self,
-        task_sender: &UnboundedSender<Result<String, TaskError>>,
-        dependency_graph: &DependencyGraph,
+        task_sender: &UnboundedSender<TaskCompletion>,
+        dependency_counts: &HashMap<String, u32>,
     ) -> Result<(), ExecutionError> {
         debug!("Getting initial tasks with no dependencies");
 
-        let ready_tasks: Vec<Task> = dependency_graph
-            .get_task_parents_iter()
-            .filter_map(|(task_id, parents)| {
-                if parents.is_empty() {
+        let ready_tasks: Vec<Task> = dependency_counts
+            .iter()
+            .filter_map(|(task_id, count)| {
+                if *count == 0 {
                     self.config.get_task_by_id(task_id).cloned()
                 } else {
                     None
@@ -103,39 +298,86 @@ impl Executor {
         Ok(())
     }
 
-    /// Processes task completion results and manages dependency countdown
+    /// Processes task completion results and manages dependency countdown.
+    /// Once the run is cancelled, no further task is dispatched and the in-flight ones are stopped
     async fn process_task_results(
         &self,
-        task_receiver: &mut futures_channel::mpsc::UnboundedReceiver<Result<String, TaskError>>,
+        task_receiver: &mut futures_channel::mpsc::UnboundedReceiver<TaskCompletion>,
         dependency_counts: &mut HashMap<String, u32>,
-        task_sender: &UnboundedSender<Result<String, TaskError>>,
-    ) -> Result<Vec<String>, ExecutionError> {
+        task_sender: UnboundedSender<TaskCompletion>,
+    ) -> Result<RunReport, ExecutionError> {
         debug!("Starting result processing loop");
 
-        let mut task_ids: Vec<String> = Vec::new();
-
-        while let Some(result) = task_receiver.next().await {
-            match result {
-                Ok(task_id) => {
-                    debug!("Acknowledged task '{}' completion", task_id);
-                    task_ids.push(task_id.clone());
-
-                    // Check if we've reached the target task
-                    if task_id == self.app_config.target {
-                        info!(
-                            "Reached target task '{}'. Execution completed successfully.",
-                            task_id
-                        );
-                        return Ok(task_ids);
-                    }
-
-                    // Handle dependency management for completed task
-                    self.handle_task_completion(&task_id, dependency_counts, task_sender)
-                        .await?;
+        let mut report = RunReport::default();
+
+        loop {
+            let next_completion = {
+                let completion = task_receiver.next();
+                let cancelled = pin!(self.task_context.cancellation.cancelled());
+                match select(completion, cancelled).await {
+                    Either::Left((completion, _)) => Some(completion),
+                    Either::Right(_) => None,
                 }
-                Err(error) => {
-                    return Err(error).context(TaskExecutionSnafu);
+            };
+            let Some(completion) = next_completion else {
+                warn!("Run cancelled, stopping execution");
+                drop(task_sender);
+                self.cancel_in_flight_tasks(task_receiver, &mut report)
+                    .await;
+                return Ok(report);
+            };
+            let Some(completion) = completion else {
+                break;
+            };
+
+            StatusLine::task_finished(&completion.task_id);
+            let task_report = completion.into_report();
+            let task_id = task_report.task_id.clone();
+            let status = task_report.status;
+            report.push(task_report);
+
+            if status == TaskStatus::Executed {
+                self.record_produced_artifacts(&task_id);
+            }
+
+            if status == TaskStatus::Failed && !self.app_config.keep_going {
+                warn!("Task '{}' failed, stopping execution", task_id);
+                drop(task_sender);
+                self.cancel_in_flight_tasks(task_receiver, &mut report)
+                    .await;
+                return Ok(report);
+            }
+
+            debug!("Acknowledged task '{}' completion", task_id);
+            self.release_job_slot(status, &task_sender)?;
+
+            if status == TaskStatus::Failed {
+                // The tasks depending on the failed one are never dispatched,
+                // so the run ends once every dispatched task reported back
+                warn!("Task '{}' failed, continuing with the other tasks", task_id);
+                if self.all_dispatched_tasks_reported(&report) {
+                    self.warn_about_blocked_tasks(&report);
+                    return Ok(report);
                 }
+                continue;
+            }
+
+            // The target finishes last, unless `after` hooks have to run once it is done
+            if report.tasks().len() == self.dependency_graph.get_task_parents_iter().count() {
+                info!(
+                    "Finished all tasks of target '{}'. Execution completed successfully.",
+                    self.dependency_graph.target()
+                );
+                return Ok(report);
+            }
+
+            // Handle dependency management for completed task
+            self.handle_task_completion(&task_id, dependency_counts, &task_sender)
+                .await?;
+
+            if report.failed().next().is_some() && self.all_dispatched_tasks_reported(&report) {
+                self.warn_about_blocked_tasks(&report);
+                return Ok(report);
             }
         }
 
@@ -143,17 +385,94 @@ impl Executor {
         Err(ExecutionError::ExecutionEndedPrematurely)
     }
 
+    /// Returns true if no dispatched task is left to report back
+    fn all_dispatched_tasks_reported(&self, report: &RunReport) -> bool {
+        self.dispatched_tasks.load(Ordering::SeqCst) == report.tasks().len()
+    }
+
+    /// Tells how many tasks didn't run, because a task they depend on failed
+    fn warn_about_blocked_tasks(&self, report: &RunReport) {
+        let blocked_count = self
+            .dependency_graph
+            .get_task_parents_iter()
+            .count()
+            .saturating_sub(report.tasks().len());
+        if blocked_count > 0 {
+            warn!(
+                "{} task(s) were not run, because tasks they depend on failed",
+                blocked_count
+            );
+        }
+    }
+
+    /// Starts fingerprinting the inputs of the tasks waiting for the dispatched one
+    fn speculate_dependents(&self, task_id: &str) {
+        for dependent_id in self
+            .dependency_graph
+            .dependents_of(task_id)
+            .unwrap_or_default()
+        {
+            if let Some(dependent) = self.config.get_task_by_id(dependent_id) {
+                self.speculative_fingerprints
+                    .start(dependent, &self.saved_dependencies);
+            }
+        }
+    }
+
+    fn record_produced_artifacts(&self, task_id: &str) {
+        if let Some(task) = self.config.get_task_by_id(task_id) {
+            let mut produced_artifacts = self.lock_produced_artifacts();
+            produced_artifacts.extend(task.produces().iter().cloned());
+        }
+    }
+
+    /// Returns true if the task consumes a virtual artifact produced earlier in this run
+    fn consumes_produced_artifact(&self, task: &Task) -> bool {
+        let produced_artifacts = self.lock_produced_artifacts();
+        task.consumes()
+            .iter()
+            .any(|artifact| produced_artifacts.contains(artifact))
+    }
+
+    fn lock_produced_artifacts(&self) -> MutexGuard<'_, HashSet<String>> {
+        self.produced_artifacts
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Cancels all tasks which are still running and waits until they have stopped,
+    /// so no work continues in the background after the result is returned
+    async fn cancel_in_flight_tasks(
+        &self,
+        task_receiver: &mut futures_channel::mpsc::UnboundedReceiver<TaskCompletion>,
+        report: &mut RunReport,
+    ) {
+        debug!("Cancelling in-flight tasks");
+        self.task_context.cancellation.cancel();
+
+        // The channel closes once every in-flight task has reported back and dropped its sender
+        while let Some(completion) = task_receiver.next().await {
+            StatusLine::task_finished(&completion.task_id);
+            let task_report = completion.into_report();
+            debug!(
+                "In-flight task '{}' stopped with status {:?}",
+                task_report.task_id, task_report.status
+            );
+            report.push(task_report);
+        }
+    }
+
     /// Handles the completion of a task by updating dependency counts and dispatching newly ready tasks
     async fn handle_task_completion(
         &self,
         completed_task_id: &str,
         dependency_counts: &mut HashMap<String, u32>,
-        task_sender: &UnboundedSender<Result<String, TaskError>>,
+        task_sender: &UnboundedSender<TaskCompletion>,
     ) -> Result<(), ExecutionError> {
         let parent_tasks = self
             .dependency_graph
-            .get_parent_by_id(completed_task_id)
-            .cloned()
+            .dependents_of(completed_task_id)
+            .map(<[String]>::to_vec)
             .unwrap_or_default();
 
         for parent_id in parent_tasks {
@@ -181,70 +500,157 @@ impl Executor {
         Ok(())
     }
 
-    /// Initialize dependency counts for all tasks based on their declared dependencies
-    fn initialize_dependency_counts(&self) -> HashMap<String, u32> {
-        let mut counts = HashMap::new();
-
-        for task in self.config.get_tasks_iter() {
-            let dependency_count = task.dependencies().len() as u32;
-            counts.insert(task.id(), dependency_count);
-            debug!("Task '{}' has {} dependencies", task.id(), dependency_count);
-        }
-
-        debug!("Initialized dependency counts for {} tasks", counts.len());
-        counts
-    }
-
     /// Dispatch a task to the executor and forward the result to the task receiver
     async fn dispatch_task(
         &self,
-        task_sender: UnboundedSender<Result<String, TaskError>>,
+        task_sender: UnboundedSender<TaskCompletion>,
         task: Task,
     ) -> Result<(), ExecutionError> {
         let task_id = task.id().clone();
+        self.dispatched_tasks.fetch_add(1, Ordering::SeqCst);
+        // Child of the run span, covering both the up to date check and the execution
+        let span = info_span!(
+            "task",
+            task.id = %task_id,
+            task.kind = task.kind(),
+            duration_ms = field::Empty
+        );
 
-        if self
-            .saved_dependencies
-            .is_task_up_to_date(&task, &self.app_config.root)
-            .await
+        if self.app_config.dry_run {
+            println!("{}: {}", task_id, task.command());
+            Self::send_completion(&task_sender, task_id, Ok(TaskStatus::Planned));
+            return Ok(());
+        }
+
+        let speculative = self.speculative_fingerprints.take(&task_id);
+        if !self.app_config.force
+            && !self.consumes_produced_artifact(&task)
+            && self
+                .saved_dependencies
+                .is_task_up_to_date_reusing(&task, &self.app_config.root, speculative.as_ref())
+                .instrument(span.clone())
+                .await
         {
+            let _entered = span.enter();
+            span.record("duration_ms", 0);
             info!("Task '{}' is up to date, skipping execution", task_id);
-            let task_id_for_err = task_id.clone();
-            if let Err(send_err) = task_sender.unbounded_send(Ok(task_id)) {
-                debug!(
-                    "Failed to send task result for '{}': {}",
-                    task_id_for_err, send_err
-                );
-            }
+            Self::send_completion(&task_sender, task_id, Ok(TaskStatus::SkippedUpToDate));
             return Ok(());
         }
         debug!("Task '{}' is not up to date, executing", task_id);
 
+        if !self.take_job_slot() {
+            debug!("Task '{}' waits for a free job slot", task_id);
+            self.lock_job_slots().queued.push_back((task, span));
+            return Ok(());
+        }
+        self.run_task(task_sender, task, span)
+    }
+
+    /// Takes a slot for a task about to run, unless `--jobs` tasks are running already
+    fn take_job_slot(&self) -> bool {
+        let mut job_slots = self.lock_job_slots();
+        if let Some(jobs) = self.app_config.jobs
+            && job_slots.running >= jobs.get()
+        {
+            return false;
+        }
+        job_slots.running += 1;
+        true
+    }
+
+    /// Frees the slot of a finished task, handing it to the longest waiting task if there is one.
+    /// Tasks which were only checked, but never dispatched, didn't take a slot
+    fn release_job_slot(
+        &self,
+        status: TaskStatus,
+        task_sender: &UnboundedSender<TaskCompletion>,
+    ) -> Result<(), ExecutionError> {
+        if matches!(status, TaskStatus::SkippedUpToDate | TaskStatus::Planned) {
+            return Ok(());
+        }
+
+        let mut job_slots = self.lock_job_slots();
+        match job_slots.queued.pop_front() {
+            Some((task, span)) => {
+                drop(job_slots);
+                self.run_task(task_sender.clone(), task, span)
+            }
+            None => {
+                job_slots.running = job_slots.running.saturating_sub(1);
+                Ok(())
+            }
+        }
+    }
+
+    fn lock_job_slots(&self) -> MutexGuard<'_, JobSlots> {
+        self.job_slots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Dispatches the task to a worker, which runs it and forwards the result to the task receiver
+    fn run_task(
+        &self,
+        task_sender: UnboundedSender<TaskCompletion>,
+        task: Task,
+        span: Span,
+    ) -> Result<(), ExecutionError> {
+        let task_id = task.id();
+        let context = self.task_context.clone();
+        let root = self.app_config.root.clone();
         let receiver = self
             .dispatcher
-            .dispatch(move || async move { task.run().await })
+            .dispatch(move || {
+                async move {
+                    let start = Instant::now();
+                    let result = if task.requires_clean_git() {
+                        match ensure_clean_git(&task.id(), &root).await {
+                            Ok(()) => task.run(&context).await,
+                            Err(e) => Err(e),
+                        }
+                    } else {
+                        task.run(&context).await
+                    };
+                    let duration = start.elapsed();
+                    Span::current().record("duration_ms", duration.as_millis() as u64);
+                    (result, duration)
+                }
+                .instrument(span)
+            })
             .map_err(|e| ExecutionError::TaskDispatchError {
                 task_id: task_id.clone(),
                 error: e.to_string(),
             })?;
 
         info!("Dispatched task '{}'", task_id);
+        StatusLine::task_started(&task_id);
+        self.speculate_dependents(&task_id);
 
         // Forward the result to the task receiver with better error handling
-        let task_id_for_spawn = task_id.clone();
         spawn(async move {
-            let result = match receiver.await {
-                Ok(inner) => inner,
+            let (result, duration) = match receiver.await {
+                Ok((inner, duration)) => (inner.map(|_| TaskStatus::Executed), duration),
                 Err(e) => {
-                    debug!("Task '{}' was canceled: {}", task_id_for_spawn, e);
-                    Err(TaskError::CanceledError { source: e })
+                    debug!("Task '{}' was canceled: {}", task_id, e);
+                    let error = TaskError::CanceledError {
+                        task_id: task_id.clone(),
+                        source: e,
+                    };
+                    (Err(error), Duration::ZERO)
                 }
             };
 
-            if let Err(send_err) = task_sender.unbounded_send(result) {
+            let task_id_for_err = task_id.clone();
+            let completion = TaskCompletion {
+                task_id,
+                duration,
+                result,
+            };
+            if let Err(send_err) = task_sender.unbounded_send(completion) {
                 debug!(
                     "Failed to send task result for '{}': {}",
-                    task_id_for_spawn, send_err
+                    task_id_for_err, send_err
                 );
             }
         })
@@ -252,6 +658,26 @@ impl Executor {
 
         Ok(())
     }
+
+    /// Reports a task, which finished without being dispatched, to the result processing loop
+    fn send_completion(
+        task_sender: &UnboundedSender<TaskCompletion>,
+        task_id: String,
+        result: Result<TaskStatus, TaskError>,
+    ) {
+        let task_id_for_err = task_id.clone();
+        let completion = TaskCompletion {
+            task_id,
+            duration: Duration::ZERO,
+            result,
+        };
+        if let Err(send_err) = task_sender.unbounded_send(completion) {
+            debug!(
+                "Failed to send task result for '{}': {}",
+                task_id_for_err, send_err
+            );
+        }
+    }
 }
 
 #[derive(Debug, Snafu)]
@@ -264,8 +690,381 @@ pub enum ExecutorCreationError {
 pub enum ExecutionError {
     #[snafu(display("Failed to dispatch task '{}': {}", task_id, error))]
     TaskDispatchError { task_id: String, error: String },
-    #[snafu(display("Got a task execution error"))]
-    TaskExecutionError { source: TaskError },
     #[snafu(display("Execution loop ended before reaching target task"))]
     ExecutionEndedPrematurely,
+    #[snafu(display("The tasks can't be run serially, because their dependencies form a cycle"))]
+    SerialOrderCycleError,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use rstest::rstest;
+
+    use crate::cli::Cli;
+    #[cfg(target_family = "unix")]
+    use crate::tasks::process_stops_within;
+
+    #[compio::test]
+    async fn test_dry_run_plans_diamond_in_dependency_order() {
+        let config: TaskRegistry = r#"
+tasks:
+  fetch:
+    command: git fetch
+  lint:
+    command: cargo clippy
+    dependsOn: [fetch]
+  test:
+    command: cargo test
+    dependsOn: [fetch]
+  build:
+    command: cargo build
+    dependsOn: [lint, test]
+"#
+        .try_into()
+        .unwrap();
+        let graph = DependencyGraph::from_config(&config, &"build".to_string()).unwrap();
+        let app_config =
+            RuntimeConfig::from(Cli::try_parse_from(["tessy", "build", "-n"]).unwrap());
+
+        let report = Executor::new(
+            Arc::new(config),
+            Arc::new(graph),
+            Arc::new(app_config),
+            Arc::new(DependencyTracker::default()),
+        )
+        .unwrap()
+        .execute()
+        .await
+        .unwrap();
+
+        let order: Vec<_> = report
+            .tasks()
+            .iter()
+            .map(|task| task.task_id.as_str())
+            .collect();
+        assert_eq!(order.len(), 4);
+        assert_eq!(order.first(), Some(&"fetch"));
+        assert_eq!(order.last(), Some(&"build"));
+        assert_eq!(report.planned().count(), 4);
+        assert!(report.is_success());
+    }
+
+    #[compio::test]
+    async fn test_repeated_dependency_doesnt_stall_the_run() {
+        let config: TaskRegistry = r#"
+tasks:
+  fetch:
+    command: git fetch
+  build:
+    command: cargo build
+    dependsOn: [fetch, fetch]
+"#
+        .try_into()
+        .unwrap();
+        let graph = DependencyGraph::from_config(&config, &"build".to_string()).unwrap();
+        assert_eq!(graph.dependency_counts()["build"], 1);
+        let app_config =
+            RuntimeConfig::from(Cli::try_parse_from(["tessy", "build", "-n"]).unwrap());
+
+        let report = Executor::new(
+            Arc::new(config),
+            Arc::new(graph),
+            Arc::new(app_config),
+            Arc::new(DependencyTracker::default()),
+        )
+        .unwrap()
+        .execute()
+        .await
+        .unwrap();
+
+        let order: Vec<_> = report
+            .tasks()
+            .iter()
+            .map(|task| task.task_id.as_str())
+            .collect();
+        assert_eq!(order, vec!["fetch", "build"]);
+    }
+
+    #[compio::test]
+    async fn test_run_continues_with_after_hooks_of_the_target() {
+        let config: TaskRegistry = r#"
+tasks:
+  fetch:
+    command: git fetch
+  build:
+    command: cargo build
+    before: [fetch]
+    after: [notify]
+  notify:
+    command: echo done
+"#
+        .try_into()
+        .unwrap();
+        let graph = DependencyGraph::from_config(&config, &"build".to_string()).unwrap();
+        let app_config =
+            RuntimeConfig::from(Cli::try_parse_from(["tessy", "build", "-n"]).unwrap());
+
+        let report = Executor::new(
+            Arc::new(config),
+            Arc::new(graph),
+            Arc::new(app_config),
+            Arc::new(DependencyTracker::default()),
+        )
+        .unwrap()
+        .execute()
+        .await
+        .unwrap();
+
+        let order: Vec<_> = report
+            .tasks()
+            .iter()
+            .map(|task| task.task_id.as_str())
+            .collect();
+        assert_eq!(order, vec!["fetch", "build", "notify"]);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_force_executes_up_to_date_task() {
+        let root = tempfile::TempDir::new().unwrap();
+        let source = root.path().join("main.c");
+        std::fs::write(&source, "int main() {}").unwrap();
+        let config: TaskRegistry = format!(
+            "tasks:\n  build:\n    command: 'true'\n    inputs: [\"{}\"]\n",
+            source.display()
+        )
+        .as_str()
+        .try_into()
+        .unwrap();
+        let config = Arc::new(config);
+        let graph = Arc::new(DependencyGraph::from_config(&config, &"build".to_string()).unwrap());
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(config.get_tasks_iter(), root.path())
+            .await;
+        let tracker = Arc::new(tracker);
+
+        let mut reports = Vec::new();
+        for args in [vec!["tessy", "build"], vec!["tessy", "build", "--force"]] {
+            let mut app_config = RuntimeConfig::from(Cli::try_parse_from(args).unwrap());
+            app_config.root = root.path().to_path_buf();
+            let report = Executor::new(
+                config.clone(),
+                graph.clone(),
+                Arc::new(app_config),
+                tracker.clone(),
+            )
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+            reports.push(report);
+        }
+
+        assert_eq!(reports[0].skipped().count(), 1);
+        assert_eq!(reports[1].executed().count(), 1);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_cancelling_the_run_kills_running_commands() {
+        let root = tempfile::TempDir::new().unwrap();
+        let pid_file = root.path().join("pid");
+        let config: TaskRegistry = format!(
+            r#"
+tasks:
+  slow:
+    command: sleep 30 & echo $! > '{}'; wait; true
+  after:
+    command: 'true'
+    dependsOn: [slow]
+"#,
+            pid_file.display()
+        )
+        .as_str()
+        .try_into()
+        .unwrap();
+        let graph = DependencyGraph::from_config(&config, &"after".to_string()).unwrap();
+        let mut app_config = RuntimeConfig::from(Cli::try_parse_from(["tessy", "after"]).unwrap());
+        app_config.root = root.path().to_path_buf();
+        let executor = Executor::new(
+            Arc::new(config),
+            Arc::new(graph),
+            Arc::new(app_config),
+            Arc::new(DependencyTracker::default()),
+        )
+        .unwrap();
+
+        let start = Instant::now();
+        let (report, _) = futures::future::join(executor.execute(), async {
+            while !std::fs::read_to_string(&pid_file).is_ok_and(|pid| !pid.is_empty()) {
+                sleep(Duration::from_millis(50)).await;
+            }
+            executor.cancellation().cancel();
+        })
+        .await;
+        let report = report.unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        let cancelled: Vec<_> = report
+            .cancelled()
+            .map(|task| task.task_id.as_str())
+            .collect();
+        assert_eq!(cancelled, vec!["slow"]);
+        assert_eq!(report.tasks().len(), 1);
+        // The shell's child is killed along with it
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        assert!(process_stops_within(
+            pid.trim().parse().unwrap(),
+            Duration::from_secs(2)
+        ));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_failure_kills_running_sibling_tasks() {
+        let root = tempfile::TempDir::new().unwrap();
+        let pid_file = root.path().join("pid");
+        // The slow task's shell waits for a child of its own, which has to be killed as well
+        let config: TaskRegistry = format!(
+            r#"
+tasks:
+  slow:
+    command: sleep 30 & echo $! > '{0}'; wait
+  broken:
+    command: while [ ! -s '{0}' ]; do sleep 0.05; done; exit 1
+  build:
+    command: 'true'
+    dependsOn: [slow, broken]
+"#,
+            pid_file.display()
+        )
+        .as_str()
+        .try_into()
+        .unwrap();
+        let graph = DependencyGraph::from_config(&config, &"build".to_string()).unwrap();
+        let mut app_config =
+            RuntimeConfig::from(Cli::try_parse_from(["tessy", "build", "--jobs", "2"]).unwrap());
+        app_config.root = root.path().to_path_buf();
+
+        let start = Instant::now();
+        let report = Executor::new(
+            Arc::new(config),
+            Arc::new(graph),
+            Arc::new(app_config),
+            Arc::new(DependencyTracker::default()),
+        )
+        .unwrap()
+        .execute()
+        .await
+        .unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        let failed: Vec<_> = report.failed().map(|task| task.task_id.as_str()).collect();
+        let cancelled: Vec<_> = report
+            .cancelled()
+            .map(|task| task.task_id.as_str())
+            .collect();
+        assert_eq!(failed, vec!["broken"]);
+        assert_eq!(cancelled, vec!["slow"]);
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        assert!(process_stops_within(
+            pid.trim().parse().unwrap(),
+            Duration::from_secs(2)
+        ));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_single_job_runs_tasks_one_at_a_time() {
+        let root = tempfile::TempDir::new().unwrap();
+        // Every task fails if another one is running at the same time
+        let command = format!(
+            "mkdir '{0}/running' && sleep 0.1 && rmdir '{0}/running'",
+            root.path().display()
+        );
+        let config: TaskRegistry = format!(
+            r#"
+tasks:
+  a:
+    command: {0}
+  b:
+    command: {0}
+  c:
+    command: {0}
+  all:
+    command: 'true'
+    dependsOn: [a, b, c]
+"#,
+            command
+        )
+        .as_str()
+        .try_into()
+        .unwrap();
+        let graph = DependencyGraph::from_config(&config, &"all".to_string()).unwrap();
+        let mut app_config =
+            RuntimeConfig::from(Cli::try_parse_from(["tessy", "all", "--jobs", "1"]).unwrap());
+        app_config.root = root.path().to_path_buf();
+
+        let report = Executor::new(
+            Arc::new(config),
+            Arc::new(graph),
+            Arc::new(app_config),
+            Arc::new(DependencyTracker::default()),
+        )
+        .unwrap()
+        .execute()
+        .await
+        .unwrap();
+
+        assert_eq!(report.executed().count(), 4);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[rstest]
+    #[case(&["tessy", "build", "-k"])]
+    #[case(&["tessy", "build", "--keep-going", "--serial"])]
+    #[compio::test]
+    async fn test_keep_going_runs_tasks_independent_of_the_failure(#[case] args: &[&str]) {
+        let config: TaskRegistry = r#"
+tasks:
+  broken:
+    command: exit 1
+  slow:
+    command: sleep 0.2
+  after_slow:
+    command: 'true'
+    dependsOn: [slow]
+  build:
+    command: 'true'
+    dependsOn: [broken, after_slow]
+"#
+        .try_into()
+        .unwrap();
+        let graph = DependencyGraph::from_config(&config, &"build".to_string()).unwrap();
+        let app_config = RuntimeConfig::from(Cli::try_parse_from(args).unwrap());
+
+        let report = Executor::new(
+            Arc::new(config),
+            Arc::new(graph),
+            Arc::new(app_config),
+            Arc::new(DependencyTracker::default()),
+        )
+        .unwrap()
+        .execute()
+        .await
+        .unwrap();
+
+        let failed: Vec<_> = report.failed().map(|task| task.task_id.as_str()).collect();
+        let mut executed: Vec<_> = report
+            .executed()
+            .map(|task| task.task_id.as_str())
+            .collect();
+        executed.sort();
+        assert_eq!(failed, vec!["broken"]);
+        assert_eq!(executed, vec!["after_slow", "slow"]);
+        assert_eq!(report.tasks().len(), 3);
+    }
 }
@@ -0,0 +1,137 @@
+use std::fmt::Write;
+
+use crate::executor::{RunReport, TaskReport, TaskStatus};
+
+impl RunReport {
+    /// Renders the report as JUnit XML, with every task as a test case of a single suite.
+    /// Up to date and cancelled tasks are marked as skipped
+    pub fn to_junit_xml(&self, suite_name: &str) -> String {
+        let total_seconds: f64 = self
+            .tasks()
+            .iter()
+            .map(|task| task.duration.as_secs_f64())
+            .sum();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            escape_xml(suite_name),
+            self.tasks().len(),
+            self.failed().count(),
+            self.skipped().count() + self.cancelled().count(),
+            total_seconds
+        );
+        for task in self.tasks() {
+            write_test_case(&mut xml, suite_name, task);
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+fn write_test_case(xml: &mut String, suite_name: &str, task: &TaskReport) {
+    let _ = write!(
+        xml,
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+        escape_xml(&task.task_id),
+        escape_xml(suite_name),
+        task.duration.as_secs_f64()
+    );
+
+    match task.status {
+        TaskStatus::Executed => xml.push_str("/>\n"),
+        TaskStatus::SkippedUpToDate => {
+            xml.push_str(">\n      <skipped message=\"up to date\"/>\n    </testcase>\n")
+        }
+        TaskStatus::Cancelled => {
+            xml.push_str(">\n      <skipped message=\"cancelled\"/>\n    </testcase>\n")
+        }
+        TaskStatus::Planned => {
+            xml.push_str(">\n      <skipped message=\"dry run\"/>\n    </testcase>\n")
+        }
+        TaskStatus::Failed => {
+            let exit_code = task
+                .exit_code
+                .map(|code| format!("exit code {}", code))
+                .unwrap_or_else(|| "error".to_string());
+            let _ = writeln!(
+                xml,
+                ">\n      <failure message=\"{}\" type=\"{}\">{}</failure>\n    </testcase>",
+                escape_xml(task.error.as_deref().unwrap_or_default()),
+                exit_code,
+                escape_xml(task.stderr_tail.as_deref().unwrap_or_default())
+            );
+        }
+    }
+}
+
+/// Escapes the text for use in XML content and attributes, dropping characters
+/// XML can't represent, like the escape sequences of colored output
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn task_report(task_id: &str, status: TaskStatus) -> TaskReport {
+        TaskReport {
+            task_id: task_id.to_string(),
+            status,
+            duration: Duration::from_millis(1500),
+            exit_code: None,
+            error: None,
+            stderr_tail: None,
+        }
+    }
+
+    #[test]
+    fn test_junit_report_maps_task_statuses() {
+        let mut report = RunReport::default();
+        report.push(task_report("lint", TaskStatus::Executed));
+        report.push(task_report("build", TaskStatus::SkippedUpToDate));
+        report.push(TaskReport {
+            exit_code: Some(2),
+            error: Some("Command 'cargo test' failed".to_string()),
+            stderr_tail: Some("assertion `left == right` failed".to_string()),
+            ..task_report("test", TaskStatus::Failed)
+        });
+
+        let xml = report.to_junit_xml("tessy");
+
+        assert!(xml.contains(
+            "<testsuite name=\"tessy\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"4.500\">"
+        ));
+        assert!(xml.contains("<testcase name=\"lint\" classname=\"tessy\" time=\"1.500\"/>"));
+        assert!(xml.contains("<skipped message=\"up to date\"/>"));
+        assert!(xml.contains(
+            "<failure message=\"Command &apos;cargo test&apos; failed\" type=\"exit code 2\">\
+             assertion `left == right` failed</failure>"
+        ));
+    }
+
+    #[test]
+    fn test_escape_xml_drops_control_characters() {
+        assert_eq!(
+            escape_xml("\u{1b}[31m<error>\u{1b}[0m & more"),
+            "[31m&lt;error&gt;[0m &amp; more"
+        );
+    }
+}
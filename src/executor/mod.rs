@@ -1,5 +1,10 @@
 mod dependency_graph;
 mod executor_impl;
+mod junit_report;
+mod run_report;
+mod speculative_fingerprints;
 
 pub use dependency_graph::*;
 pub use executor_impl::*;
+pub use run_report::*;
+pub use speculative_fingerprints::*;
@@ -0,0 +1,204 @@
+use std::time::Duration;
+
+/// Final state of a single task within a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// The task's command was run and finished successfully
+    Executed,
+    /// The task was skipped, because its inputs did not change since the last run
+    SkippedUpToDate,
+    /// The task was run, but did not finish successfully
+    Failed,
+    /// The task was stopped before finishing, because the run was aborted
+    Cancelled,
+    /// The task would have run, but the run was a dry run
+    Planned,
+}
+
+/// Outcome of a single task within a run
+#[derive(Debug, Clone)]
+pub struct TaskReport {
+    pub task_id: String,
+    pub status: TaskStatus,
+    pub duration: Duration,
+    /// Exit code of the task's command, if it was run and the code is known
+    pub exit_code: Option<i32>,
+    /// Human-readable reason of the failure, present only for failed tasks
+    pub error: Option<String>,
+    /// Last lines written to stderr by the command of a failed task
+    pub stderr_tail: Option<String>,
+}
+
+/// Structured outcome of a whole run, with the tasks stored in the order they finished
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    tasks: Vec<TaskReport>,
+    /// Sum of the last recorded durations of the tasks skipped as up to date
+    time_saved: Duration,
+}
+
+impl RunReport {
+    pub fn push(&mut self, task_report: TaskReport) {
+        self.tasks.push(task_report);
+    }
+
+    pub fn tasks(&self) -> &[TaskReport] {
+        &self.tasks
+    }
+
+    /// Estimated time the up to date tasks would have taken to execute
+    pub fn time_saved(&self) -> Duration {
+        self.time_saved
+    }
+
+    pub fn set_time_saved(&mut self, time_saved: Duration) {
+        self.time_saved = time_saved;
+    }
+
+    pub fn executed(&self) -> impl Iterator<Item = &TaskReport> {
+        self.with_status(TaskStatus::Executed)
+    }
+
+    pub fn skipped(&self) -> impl Iterator<Item = &TaskReport> {
+        self.with_status(TaskStatus::SkippedUpToDate)
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &TaskReport> {
+        self.with_status(TaskStatus::Failed)
+    }
+
+    pub fn cancelled(&self) -> impl Iterator<Item = &TaskReport> {
+        self.with_status(TaskStatus::Cancelled)
+    }
+
+    pub fn planned(&self) -> impl Iterator<Item = &TaskReport> {
+        self.with_status(TaskStatus::Planned)
+    }
+
+    /// Returns true if every task in the run was either executed or up to date
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none() && self.cancelled().next().is_none()
+    }
+
+    /// Exit code of the failed task's command, when it is the only task which failed
+    pub fn failure_exit_code(&self) -> Option<i32> {
+        let mut failed = self.failed();
+        match (failed.next(), failed.next()) {
+            (Some(task), None) => task.exit_code,
+            _ => None,
+        }
+    }
+
+    /// Summary of how many tasks the cache let the run skip and the time it saved,
+    /// none if no task was executed or found up to date, e.g. in a dry run
+    pub fn cache_summary(&self) -> Option<String> {
+        let skipped_count = self.skipped().count();
+        let cacheable_count = self.executed().count() + skipped_count;
+        if cacheable_count == 0 {
+            return None;
+        }
+        Some(format!(
+            "Cache: {} of {} tasks up to date ({:.0}%), saving an estimated {:.2?}",
+            skipped_count,
+            cacheable_count,
+            100.0 * skipped_count as f64 / cacheable_count as f64,
+            self.time_saved
+        ))
+    }
+
+    fn with_status(&self, status: TaskStatus) -> impl Iterator<Item = &TaskReport> {
+        self.tasks.iter().filter(move |task| task.status == status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task_report(task_id: &str, status: TaskStatus) -> TaskReport {
+        TaskReport {
+            task_id: task_id.to_string(),
+            status,
+            duration: Duration::from_millis(10),
+            exit_code: None,
+            error: None,
+            stderr_tail: None,
+        }
+    }
+
+    #[test]
+    fn test_empty_report_is_success() {
+        let report = RunReport::default();
+
+        assert!(report.is_success());
+        assert!(report.tasks().is_empty());
+    }
+
+    #[test]
+    fn test_report_groups_tasks_by_status() {
+        let mut report = RunReport::default();
+        report.push(task_report("a", TaskStatus::SkippedUpToDate));
+        report.push(task_report("b", TaskStatus::Executed));
+        report.push(task_report("c", TaskStatus::Failed));
+
+        assert_eq!(report.executed().count(), 1);
+        assert_eq!(report.skipped().count(), 1);
+        assert_eq!(report.failed().next().unwrap().task_id, "c");
+        assert!(!report.is_success());
+    }
+
+    #[test]
+    fn test_report_with_cancelled_task_is_not_success() {
+        let mut report = RunReport::default();
+        report.push(task_report("a", TaskStatus::Executed));
+        report.push(task_report("b", TaskStatus::Cancelled));
+
+        assert_eq!(report.cancelled().count(), 1);
+        assert!(report.failed().next().is_none());
+        assert!(!report.is_success());
+    }
+
+    #[test]
+    fn test_failure_exit_code_of_a_single_failed_task() {
+        let failed = |task_id: &str, exit_code| TaskReport {
+            exit_code,
+            ..task_report(task_id, TaskStatus::Failed)
+        };
+        let mut report = RunReport::default();
+        report.push(task_report("a", TaskStatus::Executed));
+        assert_eq!(report.failure_exit_code(), None);
+
+        report.push(failed("b", Some(2)));
+        assert_eq!(report.failure_exit_code(), Some(2));
+
+        report.push(failed("c", Some(3)));
+        assert_eq!(report.failure_exit_code(), None);
+    }
+
+    #[test]
+    fn test_cache_summary() {
+        let mut report = RunReport::default();
+        assert_eq!(report.cache_summary(), None);
+
+        report.push(task_report("a", TaskStatus::SkippedUpToDate));
+        report.push(task_report("b", TaskStatus::Executed));
+        report.push(task_report("c", TaskStatus::Executed));
+        report.push(task_report("d", TaskStatus::Failed));
+        report.set_time_saved(Duration::from_millis(1500));
+
+        assert_eq!(
+            report.cache_summary().unwrap(),
+            "Cache: 1 of 3 tasks up to date (33%), saving an estimated 1.50s"
+        );
+    }
+
+    #[test]
+    fn test_report_preserves_completion_order() {
+        let mut report = RunReport::default();
+        report.push(task_report("second", TaskStatus::Executed));
+        report.push(task_report("first", TaskStatus::Executed));
+
+        let ids: Vec<_> = report.tasks().iter().map(|t| t.task_id.as_str()).collect();
+        assert_eq!(ids, vec!["second", "first"]);
+    }
+}
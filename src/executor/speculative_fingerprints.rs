@@ -0,0 +1,130 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    pin::pin,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use compio::runtime::spawn;
+use futures::future::{Either, select};
+use tracing::{Instrument, debug};
+
+use crate::{
+    ext::CancellationToken,
+    file_dependencies::{DependencyTracker, FileFingerprint},
+    tasks::{Task, TaskTrait},
+};
+
+/// Number of tasks whose inputs are fingerprinted ahead of time at once. Kept low,
+/// so the speculative reads don't compete with the running tasks for I/O
+const MAX_CONCURRENT_SPECULATIONS: usize = 2;
+
+/// Fingerprints the inputs of tasks in the background while their dependencies run,
+/// so the up to date check of a task finds its content hashes already computed.
+/// The check still looks at every file, so a file changed in the meantime is noticed
+#[derive(Debug)]
+pub struct SpeculativeFingerprints {
+    root: PathBuf,
+    /// Cancelled when the run is torn down, stopping all speculations
+    cancellation: CancellationToken,
+    state: Arc<Mutex<SpeculationState>>,
+}
+
+#[derive(Debug, Default)]
+struct SpeculationState {
+    finished: HashMap<String, HashMap<PathBuf, FileFingerprint>>,
+    running: HashMap<String, CancellationToken>,
+}
+
+impl SpeculativeFingerprints {
+    pub fn new(root: PathBuf, cancellation: CancellationToken) -> Self {
+        Self {
+            root,
+            cancellation,
+            state: Arc::default(),
+        }
+    }
+
+    /// Starts fingerprinting the inputs of the task in the background, unless it was already
+    /// started or too many speculations are running
+    pub fn start(&self, task: &Task, saved_dependencies: &DependencyTracker) {
+        let task_id = task.id();
+        let token = {
+            let mut state = lock_state(&self.state);
+            if state.finished.contains_key(&task_id)
+                || state.running.contains_key(&task_id)
+                || state.running.len() >= MAX_CONCURRENT_SPECULATIONS
+            {
+                return;
+            }
+            let token = CancellationToken::new();
+            state.running.insert(task_id.clone(), token.clone());
+            token
+        };
+        debug!(
+            "Fingerprinting the inputs of task '{}' ahead of time",
+            task_id
+        );
+
+        let task = task.clone();
+        // Cloned, so the speculation doesn't keep the tracker shared after the run
+        let previous = saved_dependencies.saved_fingerprints(&task_id).cloned();
+        let root = self.root.clone();
+        let run_cancellation = self.cancellation.clone();
+        let state = self.state.clone();
+        spawn(
+            async move {
+                let fingerprinting = pin!(DependencyTracker::fingerprint_task_inputs(
+                    &task,
+                    &root,
+                    previous.as_ref()
+                ));
+                let task_cancelled = pin!(token.cancelled());
+                let run_cancelled = pin!(run_cancellation.cancelled());
+                let cancelled = select(task_cancelled, run_cancelled);
+                let result = select(fingerprinting, cancelled).await;
+
+                let mut state = lock_state(&state);
+                state.running.remove(&task_id);
+                match result {
+                    Either::Left((fingerprints, _)) => {
+                        state.finished.insert(task_id, fingerprints);
+                    }
+                    Either::Right(_) => {
+                        debug!(
+                            "Speculative fingerprinting of task '{}' was cancelled",
+                            task_id
+                        )
+                    }
+                }
+            }
+            .in_current_span(),
+        )
+        .detach();
+    }
+
+    /// Returns the fingerprints taken ahead of time for the task, if they are finished.
+    /// A speculation still running is cancelled, since the task is about to be checked anyway
+    pub fn take(&self, task_id: &str) -> Option<HashMap<PathBuf, FileFingerprint>> {
+        let mut state = lock_state(&self.state);
+        if let Some(token) = state.running.remove(task_id) {
+            token.cancel();
+        }
+        state.finished.remove(task_id)
+    }
+
+    /// Cancels all running speculations, once no more tasks will be checked
+    pub fn stop(&self) {
+        let mut state = lock_state(&self.state);
+        for (_, token) in state.running.drain() {
+            token.cancel();
+        }
+        state.finished.clear();
+    }
+}
+
+fn lock_state(state: &Mutex<SpeculationState>) -> MutexGuard<'_, SpeculationState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -1,5 +1,9 @@
 mod async_conversion;
 mod best_effort_path_ext;
+mod cancellation_token;
+mod path_expansion;
 
 pub use async_conversion::*;
 pub use best_effort_path_ext::*;
+pub use cancellation_token::*;
+pub use path_expansion::*;
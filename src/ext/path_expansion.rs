@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+/// Expands a leading `~` and `$NAME` or `${NAME}` environment variables in the path,
+/// the way a shell would. Variables which aren't set are left as they are
+pub fn expand_path(path: &Path) -> PathBuf {
+    let Some(text) = path.to_str() else {
+        return path.to_path_buf();
+    };
+    let lookup = |name: &str| std::env::var(name).ok();
+    PathBuf::from(expand_vars(&expand_tilde(text, lookup), lookup))
+}
+
+/// Replaces a leading `~` with the home directory
+fn expand_tilde(text: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let Some(rest) = text.strip_prefix('~') else {
+        return text.to_string();
+    };
+    if !rest.is_empty() && !rest.starts_with(['/', '\\']) {
+        // `~user` paths are not supported
+        return text.to_string();
+    }
+
+    match lookup("HOME").or_else(|| lookup("USERPROFILE")) {
+        Some(home) => format!("{}{}", home, rest),
+        None => {
+            warn!(
+                "Can't expand '~' in '{}', the home directory is unknown",
+                text
+            );
+            text.to_string()
+        }
+    }
+}
+
+fn expand_vars(text: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after_dollar = &rest[start + 1..];
+
+        let (name, remainder) = match after_dollar.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                // An unterminated `${` is kept as it is
+                None => ("", after_dollar),
+            },
+            None => {
+                let end = after_dollar
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after_dollar.len());
+                (&after_dollar[..end], &after_dollar[end..])
+            }
+        };
+        let reference = &rest[start..rest.len() - remainder.len()];
+
+        match (!name.is_empty()).then(|| lookup(name)).flatten() {
+            Some(value) => expanded.push_str(&value),
+            None => {
+                if !name.is_empty() {
+                    warn!(
+                        "Environment variable '{}' is not set, keeping it as is",
+                        name
+                    );
+                }
+                expanded.push_str(reference);
+            }
+        }
+        rest = remainder;
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/user".to_string()),
+            "REPO" => Some("/work/repo".to_string()),
+            _ => None,
+        }
+    }
+
+    #[rstest]
+    #[case("$REPO/tasks.yaml", "/work/repo/tasks.yaml")]
+    #[case("${REPO}_backup", "/work/repo_backup")]
+    #[case("$HOME/$REPO", "/home/user//work/repo")]
+    #[case("plain/path", "plain/path")]
+    #[case("$MISSING/dir", "$MISSING/dir")]
+    #[case("${MISSING}/dir", "${MISSING}/dir")]
+    #[case("cost$", "cost$")]
+    #[case("${unterminated", "${unterminated")]
+    fn test_expand_vars(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(expand_vars(text, lookup), expected);
+    }
+
+    #[rstest]
+    #[case("~", "/home/user")]
+    #[case("~/work", "/home/user/work")]
+    #[case("~other/work", "~other/work")]
+    #[case("dir/~", "dir/~")]
+    fn test_expand_tilde(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(expand_tilde(text, lookup), expected);
+    }
+}
@@ -1,23 +1,89 @@
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::io;
 use std::path::Path;
+use std::time::{Duration, SystemTime};
 use std::{collections::HashMap, path::PathBuf};
 
 use bincode::{Decode, Encode};
 use compio::fs;
+use compio::runtime::spawn_blocking;
+use futures::{StreamExt, future, stream};
+use metrohash::MetroHash64;
 use tracing::{debug, info, warn};
 
-use crate::ext::{AsyncTryFrom, BestEffortPathExt};
-use crate::file_dependencies::FileFingerprint;
-use crate::tasks::{Task, TaskTrait};
+use crate::ext::BestEffortPathExt;
+use crate::file_dependencies::{
+    FileFingerprint, OutOfDateReason, expand_glob, fingerprint_url, is_glob, is_url,
+};
+use crate::tasks::{Task, TaskInput, TaskTrait};
 
 const STANDARD_DEPENDENCY_FILE_PATH: &str = ".tessy/dependencies.bincode.zstd";
-
-fn get_standard_dependency_file_path(root: &Path) -> PathBuf {
+/// Suffix of the file the dependency tracker is written to before replacing the previous one
+const TEMPORARY_FILE_SUFFIX: &str = ".tmp";
+/// Suffix a dependency file which can't be read is renamed with, to keep it for debugging
+const CORRUPT_FILE_SUFFIX: &str = ".corrupt";
+/// zstd level the dependency file is compressed with, unless another one is configured
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+/// Highest zstd level, giving the smallest file at the highest cost
+const MAX_COMPRESSION_LEVEL: i32 = 22;
+/// Upper bound of files fingerprinted at once, so large trees don't exhaust file descriptors
+const MAX_CONCURRENT_FINGERPRINTS: usize = 64;
+
+pub fn get_standard_dependency_file_path(root: &Path) -> PathBuf {
     root.join(STANDARD_DEPENDENCY_FILE_PATH)
 }
 
+/// zstd level for the configured compression level, clamped to 0-22.
+/// Level 0 uses the fastest level zstd has, which stores the data almost as it is
+fn zstd_level(level: i32) -> i32 {
+    let clamped = level.clamp(0, MAX_COMPRESSION_LEVEL);
+    if clamped != level {
+        warn!(
+            "Cache compression level {} is out of range, using {} instead",
+            level, clamped
+        );
+    }
+    if clamped == 0 {
+        *zstd::compression_level_range().start()
+    } else {
+        clamped
+    }
+}
+
+/// Path of a file next to the given one, named after it with the suffix appended
+fn path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// File of an input waiting to be fingerprinted
+struct PendingFile<'a> {
+    input: &'a TaskInput,
+    path: PathBuf,
+    /// Files found by walking a directory are tracked as unreadable if fingerprinting fails
+    in_directory: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default, Encode, Decode)]
 pub struct DependencyTracker {
     dependencies: HashMap<String, HashMap<PathBuf, FileFingerprint>>,
+    /// Modification times of the directories of inputs declared with `directoryStamp`
+    directory_stamps: HashMap<String, HashMap<PathBuf, SystemTime>>,
+    /// Keys of successful runs of memoized tasks, combining the command and the input fingerprints,
+    /// so they are shared by all tasks running the same command on the same inputs
+    memoized_runs: HashSet<u64>,
+    /// Number of times the producers of every virtual artifact ran
+    artifact_generations: HashMap<String, u64>,
+    /// Generations of the virtual artifacts which every consuming task last saw
+    consumed_generations: HashMap<String, HashMap<String, u64>>,
+    /// How long every task took when it was last executed
+    task_durations: HashMap<String, Duration>,
+    /// Fingerprints of the declared outputs of every task, taken after it last ran
+    outputs: HashMap<String, HashMap<PathBuf, FileFingerprint>>,
+    /// Hashes of the commands every task ran with, so editing a command reruns its task
+    command_hashes: HashMap<String, u64>,
 }
 
 impl DependencyTracker {
@@ -43,16 +109,28 @@ impl DependencyTracker {
             }
         };
 
-        Self::read_from_bytes(&bytes).await
+        match Self::decode(&bytes) {
+            Some(tracker) => tracker,
+            None => {
+                Self::back_up_corrupt_file(path).await;
+                Self::default()
+            }
+        }
     }
 
     pub async fn read_from_bytes(bytes: &[u8]) -> Self {
+        Self::decode(bytes).unwrap_or_default()
+    }
+
+    /// Decompresses and deserializes the dependency tracker, or returns `None` if the bytes
+    /// can't be read
+    fn decode(bytes: &[u8]) -> Option<Self> {
         debug!("Reading dependency tracker from bytes");
         let decompressed_bytes = match zstd::decode_all(bytes) {
             Ok(decompressed) => decompressed,
             Err(e) => {
                 warn!("Failed to decompress dependency tracker: {}", e);
-                return Self::default();
+                return None;
             }
         };
 
@@ -64,7 +142,7 @@ impl DependencyTracker {
             Ok(result) => result.0,
             Err(e) => {
                 warn!("Failed to read dependency tracker: ({}), starting fresh", e);
-                return Self::default();
+                return None;
             }
         };
 
@@ -78,7 +156,30 @@ impl DependencyTracker {
                 .sum::<usize>()
         );
         debug!("Successfully read dependency tracker: {:?}", result);
-        result
+        Some(result)
+    }
+
+    /// Moves an unreadable dependency file aside rather than overwriting it with the fresh
+    /// tracker, so the reason of the reset can still be looked into
+    async fn back_up_corrupt_file(path: &Path) {
+        let backup_path = path_with_suffix(path, CORRUPT_FILE_SUFFIX);
+        match fs::rename(path, &backup_path).await {
+            Ok(_) => warn!(
+                "Dependency tracker is corrupt, all tasks will run again. It was kept as '{}'",
+                backup_path.best_effort_path_display()
+            ),
+            Err(e) => warn!(
+                "Dependency tracker is corrupt, all tasks will run again. It couldn't be kept: {}",
+                e
+            ),
+        }
+    }
+
+    /// Iterates over the tasks with saved dependencies and their file fingerprints
+    pub fn tracked_tasks(
+        &self,
+    ) -> impl Iterator<Item = (&String, &HashMap<PathBuf, FileFingerprint>)> {
+        self.dependencies.iter()
     }
 
     pub async fn add_tasks_dependencies(
@@ -87,39 +188,442 @@ impl DependencyTracker {
         root: &Path,
     ) {
         for task in tasks {
-            let deps = Self::get_dependencies_from_inputs(task.inputs(), root).await;
+            let previous = self.dependencies.get(&task.id());
+            let deps = Self::get_dependencies_from_inputs(
+                task.inputs(),
+                task.ignore_inputs(),
+                root,
+                previous,
+            )
+            .await;
+            if let Some(key) = Self::memoization_key(task, &deps) {
+                self.memoized_runs.insert(key);
+            }
             self.dependencies.insert(task.id(), deps);
+            self.command_hashes.insert(task.id(), task.command_hash());
+
+            let mut stamps = HashMap::new();
+            for input in task.inputs().iter().filter(|input| input.directory_stamp) {
+                let path = root.join(&input.path);
+                if !Self::collect_directory_stamps(&path, &mut stamps) {
+                    debug!(
+                        "Could not stamp the directories of input '{}'",
+                        path.best_effort_path_display()
+                    );
+                }
+            }
+            if stamps.is_empty() {
+                self.directory_stamps.remove(&task.id());
+            } else {
+                self.directory_stamps.insert(task.id(), stamps);
+            }
+
+            if task.outputs().is_empty() {
+                self.outputs.remove(&task.id());
+            } else {
+                let outputs =
+                    Self::fingerprint_outputs(task, root, self.outputs.get(&task.id())).await;
+                self.outputs.insert(task.id(), outputs);
+            }
         }
     }
 
     pub async fn is_task_up_to_date(&self, task: &Task, root: &Path) -> bool {
+        self.is_task_up_to_date_reusing(task, root, None).await
+    }
+
+    /// Checks if the task is up to date like [`DependencyTracker::is_task_up_to_date`], reusing
+    /// the content hashes of fingerprints taken ahead of time, for the files whose modification
+    /// time didn't change since
+    pub async fn is_task_up_to_date_reusing(
+        &self,
+        task: &Task,
+        root: &Path,
+        speculative: Option<&HashMap<PathBuf, FileFingerprint>>,
+    ) -> bool {
         let id = task.id();
         info!("Checking if task '{}' is up to date", id);
 
-        let saved_dependencies = match self.dependencies.get(&id) {
-            Some(deps) => deps,
-            None => {
-                info!(
-                    "No saved dependencies found for task '{}', marking as out of date",
-                    id
+        if !self.are_consumed_artifacts_unchanged(task) {
+            info!(
+                "A virtual artifact consumed by task '{}' was produced since it last ran",
+                id
+            );
+            return false;
+        }
+
+        if !self.is_command_unchanged(task) {
+            info!("The command of task '{}' changed since it last ran", id);
+            return false;
+        }
+
+        if !self.are_outputs_unchanged(task, root).await {
+            info!(
+                "An output of task '{}' was removed or changed since it last ran",
+                id
+            );
+            return false;
+        }
+
+        let saved_dependencies = self.dependencies.get(&id);
+        if saved_dependencies.is_none() && !task.memoize() {
+            info!(
+                "No saved dependencies found for task '{}', marking as out of date",
+                id
+            );
+            return false;
+        }
+
+        let (stamped_inputs, scanned_inputs): (Vec<TaskInput>, Vec<TaskInput>) = task
+            .inputs()
+            .iter()
+            .cloned()
+            .partition(|input| self.are_directories_unchanged(&id, input, root));
+
+        // Inputs with unchanged directories keep their saved fingerprints without being scanned
+        let mut new_dependencies = HashMap::new();
+        for input in &stamped_inputs {
+            debug!(
+                "Directories of input '{}' are unchanged, skipping its scan",
+                input.path
+            );
+            let path = root.join(&input.path);
+            new_dependencies.extend(
+                saved_dependencies
+                    .into_iter()
+                    .flatten()
+                    .filter(|(dep_path, _)| dep_path.starts_with(&path))
+                    .map(|(dep_path, fingerprint)| (dep_path.clone(), fingerprint.clone())),
+            );
+        }
+        // Speculative fingerprints are newer than the saved ones, so they take precedence
+        let merged_previous;
+        let previous = match speculative {
+            Some(speculative) => {
+                let mut previous = saved_dependencies.cloned().unwrap_or_default();
+                previous.extend(
+                    speculative
+                        .iter()
+                        .map(|(path, fingerprint)| (path.clone(), fingerprint.clone())),
                 );
-                return false;
+                merged_previous = previous;
+                Some(&merged_previous)
             }
+            None => saved_dependencies,
         };
+        new_dependencies.extend(
+            Self::get_dependencies_from_inputs(
+                &scanned_inputs,
+                task.ignore_inputs(),
+                root,
+                previous,
+            )
+            .await,
+        );
+
+        saved_dependencies == Some(&new_dependencies) || self.is_memoized(task, &new_dependencies)
+    }
+
+    /// Explains why the task is not up to date, by comparing a full scan of its inputs
+    /// with the saved fingerprints. Empty if the task is up to date
+    pub async fn out_of_date_reasons(&self, task: &Task, root: &Path) -> Vec<OutOfDateReason> {
+        if self.is_task_up_to_date(task, root).await {
+            return Vec::new();
+        }
+
+        let seen_generations = self.consumed_generations.get(&task.id());
+        let mut reasons = Vec::new();
+        if self.dependencies.contains_key(&task.id()) && !self.is_command_unchanged(task) {
+            reasons.push(OutOfDateReason::CommandChanged);
+        }
+        reasons.extend(
+            task.consumes()
+                .iter()
+                .filter(|artifact| {
+                    seen_generations.and_then(|generations| generations.get(*artifact))
+                        != Some(&self.artifact_generation(artifact))
+                })
+                .map(|artifact| OutOfDateReason::ArtifactProduced(artifact.clone())),
+        );
+        reasons.extend(
+            self.changed_outputs(task, root)
+                .await
+                .into_iter()
+                .map(OutOfDateReason::OutputChanged),
+        );
+
+        let Some(saved_dependencies) = self.dependencies.get(&task.id()) else {
+            reasons.push(OutOfDateReason::NoSavedDependencies);
+            return reasons;
+        };
+        let new_dependencies = Self::get_dependencies_from_inputs(
+            task.inputs(),
+            task.ignore_inputs(),
+            root,
+            Some(saved_dependencies),
+        )
+        .await;
+
+        let mut changes: Vec<_> = new_dependencies
+            .iter()
+            .filter_map(|(path, fingerprint)| match saved_dependencies.get(path) {
+                None => Some(OutOfDateReason::InputAdded(path.clone())),
+                Some(saved) if saved != fingerprint => {
+                    Some(OutOfDateReason::InputChanged(path.clone()))
+                }
+                Some(_) => None,
+            })
+            .chain(
+                saved_dependencies
+                    .keys()
+                    .filter(|path| !new_dependencies.contains_key(*path))
+                    .map(|path| OutOfDateReason::InputRemoved(path.clone())),
+            )
+            .collect();
+        changes.sort_by(|a, b| a.to_string().cmp(&b.to_string()));
+        reasons.extend(changes);
+        reasons
+    }
+
+    /// Saved fingerprints of the task's inputs, if it ran before
+    pub fn saved_fingerprints(&self, task_id: &str) -> Option<&HashMap<PathBuf, FileFingerprint>> {
+        self.dependencies.get(task_id)
+    }
+
+    /// Fingerprints all inputs of the task, reusing the given previous fingerprints where the
+    /// strategy allows it. Used to fingerprint a task ahead of its up to date check
+    pub async fn fingerprint_task_inputs(
+        task: &Task,
+        root: &Path,
+        previous: Option<&HashMap<PathBuf, FileFingerprint>>,
+    ) -> HashMap<PathBuf, FileFingerprint> {
+        Self::get_dependencies_from_inputs(task.inputs(), task.ignore_inputs(), root, previous)
+            .await
+    }
+
+    /// Forgets the saved state of the task, so it runs again even if nothing changed.
+    /// Returns true if the task had saved dependencies
+    pub fn forget(&mut self, task_id: &str) -> bool {
+        let dependencies = self.dependencies.remove(task_id);
+        let command_hash = self.command_hashes.remove(task_id);
+        // Otherwise a memoized task would still match its last successful run
+        if let (Some(dependencies), Some(command_hash)) = (&dependencies, command_hash) {
+            self.memoized_runs
+                .remove(&Self::run_key(command_hash, dependencies));
+        }
+        self.directory_stamps.remove(task_id);
+        self.outputs.remove(task_id);
+        dependencies.is_some()
+    }
+
+    /// Drops the saved state of every task whose id isn't valid anymore, like a task which was
+    /// renamed or deleted. Memoized runs are kept, as other tasks can share them.
+    /// Returns the number of tasks dropped
+    pub fn prune(&mut self, valid_ids: &HashSet<String>) -> usize {
+        let stale_ids: HashSet<String> = self
+            .dependencies
+            .keys()
+            .chain(self.directory_stamps.keys())
+            .chain(self.consumed_generations.keys())
+            .chain(self.task_durations.keys())
+            .chain(self.outputs.keys())
+            .chain(self.command_hashes.keys())
+            .filter(|task_id| !valid_ids.contains(*task_id))
+            .cloned()
+            .collect();
+        for task_id in &stale_ids {
+            self.dependencies.remove(task_id);
+            self.directory_stamps.remove(task_id);
+            self.consumed_generations.remove(task_id);
+            self.task_durations.remove(task_id);
+            self.outputs.remove(task_id);
+            self.command_hashes.remove(task_id);
+        }
+        stale_ids.len()
+    }
+
+    /// Remembers how long the task took to execute, to estimate the time saved by skipping it
+    pub fn record_task_duration(&mut self, task_id: &str, duration: Duration) {
+        self.task_durations.insert(task_id.to_string(), duration);
+    }
+
+    /// Duration of the last execution of the task, if it was recorded
+    pub fn last_task_duration(&self, task_id: &str) -> Option<Duration> {
+        self.task_durations.get(task_id).copied()
+    }
+
+    /// Records the virtual artifacts of a task which finished successfully. Running a task
+    /// changes the artifacts it produces, while a consuming task remembers which generation
+    /// of the artifacts it saw. Tasks have to be recorded in the order they finished
+    pub fn record_virtual_artifacts(&mut self, task: &Task, executed: bool) {
+        if executed {
+            for artifact in task.produces() {
+                *self
+                    .artifact_generations
+                    .entry(artifact.clone())
+                    .or_default() += 1;
+            }
+        }
+
+        if task.consumes().is_empty() {
+            self.consumed_generations.remove(&task.id());
+            return;
+        }
+        let generations = task
+            .consumes()
+            .iter()
+            .map(|artifact| (artifact.clone(), self.artifact_generation(artifact)))
+            .collect();
+        self.consumed_generations.insert(task.id(), generations);
+    }
+
+    fn artifact_generation(&self, artifact: &str) -> u64 {
+        self.artifact_generations
+            .get(artifact)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns true if none of the virtual artifacts consumed by the task were produced
+    /// since it last ran
+    fn are_consumed_artifacts_unchanged(&self, task: &Task) -> bool {
+        let seen_generations = self.consumed_generations.get(&task.id());
+        task.consumes().iter().all(|artifact| {
+            seen_generations.and_then(|generations| generations.get(artifact))
+                == Some(&self.artifact_generation(artifact))
+        })
+    }
+
+    /// Returns true if the task last ran with the same command. Tasks which never ran are left
+    /// to the other checks, since a memoized run may still match them
+    fn is_command_unchanged(&self, task: &Task) -> bool {
+        self.command_hashes
+            .get(&task.id())
+            .is_none_or(|command_hash| *command_hash == task.command_hash())
+    }
+
+    /// Returns true if the declared outputs of the task still match the fingerprints taken
+    /// after it last ran. A task with outputs, which were never fingerprinted, is not up to date
+    async fn are_outputs_unchanged(&self, task: &Task, root: &Path) -> bool {
+        task.outputs().is_empty()
+            || (self.outputs.contains_key(&task.id())
+                && self.changed_outputs(task, root).await.is_empty())
+    }
+
+    /// Paths of the task's outputs, which were removed, changed or added since it last ran
+    async fn changed_outputs(&self, task: &Task, root: &Path) -> Vec<PathBuf> {
+        let Some(saved_outputs) = self.outputs.get(&task.id()) else {
+            return Vec::new();
+        };
+        let current_outputs = Self::fingerprint_outputs(task, root, Some(saved_outputs)).await;
+
+        let mut changed: Vec<_> = saved_outputs
+            .iter()
+            .filter(|(path, fingerprint)| current_outputs.get(*path) != Some(*fingerprint))
+            .map(|(path, _)| path.clone())
+            .chain(
+                current_outputs
+                    .keys()
+                    .filter(|path| !saved_outputs.contains_key(*path))
+                    .cloned(),
+            )
+            .collect();
+        changed.sort();
+        changed
+    }
+
+    /// Fingerprints the files of the task's declared outputs, like inputs with the default strategy
+    async fn fingerprint_outputs(
+        task: &Task,
+        root: &Path,
+        previous: Option<&HashMap<PathBuf, FileFingerprint>>,
+    ) -> HashMap<PathBuf, FileFingerprint> {
+        let outputs: Vec<_> = task.outputs().iter().map(TaskInput::new).collect();
+        Self::get_dependencies_from_inputs(&outputs, &[], root, previous).await
+    }
+
+    /// Returns true if a memoized run of the same command on the same inputs succeeded before,
+    /// possibly under a different task id
+    fn is_memoized(&self, task: &Task, dependencies: &HashMap<PathBuf, FileFingerprint>) -> bool {
+        let memoized = Self::memoization_key(task, dependencies)
+            .is_some_and(|key| self.memoized_runs.contains(&key));
+        if memoized {
+            info!("Task '{}' matches a memoized successful run", task.id());
+        }
+        memoized
+    }
+
+    /// Content-addressed key of a run of a memoized task with the given input fingerprints
+    fn memoization_key(
+        task: &Task,
+        dependencies: &HashMap<PathBuf, FileFingerprint>,
+    ) -> Option<u64> {
+        if !task.memoize() {
+            return None;
+        }
+        Some(Self::run_key(task.command_hash(), dependencies))
+    }
+
+    fn run_key(command_hash: u64, dependencies: &HashMap<PathBuf, FileFingerprint>) -> u64 {
+        let mut dependencies: Vec<_> = dependencies.iter().collect();
+        dependencies.sort_by(|(path, _), (other_path, _)| path.cmp(other_path));
+
+        let mut hasher = MetroHash64::new();
+        command_hash.hash(&mut hasher);
+        dependencies.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns true if the input is declared with `directoryStamp` and neither its directory
+    /// nor any of its subdirectories were modified since its stamps were saved
+    fn are_directories_unchanged(&self, id: &str, input: &TaskInput, root: &Path) -> bool {
+        if !input.directory_stamp {
+            return false;
+        }
+        let Some(saved_stamps) = self.directory_stamps.get(id) else {
+            return false;
+        };
+
+        let path = root.join(&input.path);
+        let mut current_stamps = HashMap::new();
+        if !Self::collect_directory_stamps(&path, &mut current_stamps) {
+            return false;
+        }
 
-        let inputs = task.inputs();
-        let new_dependencies = Self::get_dependencies_from_inputs(inputs, root).await;
+        let saved_stamps: HashMap<PathBuf, SystemTime> = saved_stamps
+            .iter()
+            .filter(|(dir_path, _)| dir_path.starts_with(&path))
+            .map(|(dir_path, mtime)| (dir_path.clone(), *mtime))
+            .collect();
+        !saved_stamps.is_empty() && saved_stamps == current_stamps
+    }
+
+    /// Records the modification times of the directory and all its subdirectories.
+    /// Returns false if any of them could not be read
+    fn collect_directory_stamps(path: &Path, stamps: &mut HashMap<PathBuf, SystemTime>) -> bool {
+        let Ok(mtime) = path.metadata().and_then(|metadata| metadata.modified()) else {
+            return false;
+        };
+        let Ok(entries) = std::fs::read_dir(path) else {
+            return false;
+        };
+        stamps.insert(path.to_path_buf(), mtime);
 
-        saved_dependencies == &new_dependencies
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|entry_path| entry_path.is_dir())
+            .all(|entry_path| Self::collect_directory_stamps(&entry_path, stamps))
     }
 
-    /// Saves the dependency tracker to the standard file path
-    pub async fn write(&self, root: &Path) {
+    /// Saves the dependency tracker to the standard file path, compressed with the given level
+    pub async fn write(&self, root: &Path, compression_level: i32) {
         let dep_file_path = get_standard_dependency_file_path(root);
-        self.write_into_path(&dep_file_path).await;
+        self.write_into_path(&dep_file_path, compression_level)
+            .await;
     }
 
-    pub async fn write_into_path(&self, path: &Path) {
+    pub async fn write_into_path(&self, path: &Path, compression_level: i32) {
         info!(
             "Writing dependency tracker with {} tasks to {}",
             self.dependencies.len(),
@@ -140,68 +644,120 @@ impl DependencyTracker {
         };
 
         // Compress the data
-        let compressed_bytes = match zstd::encode_all(&encoded_bytes[..], 3) {
-            Ok(compressed) => {
-                debug!(
-                    "Compressed dependency tracker: {} bytes -> {} bytes ({:.1}% reduction)",
-                    encoded_bytes.len(),
-                    compressed.len(),
-                    100.0 * (1.0 - compressed.len() as f64 / encoded_bytes.len() as f64)
-                );
-                compressed
-            }
+        let compressed_bytes =
+            match zstd::encode_all(&encoded_bytes[..], zstd_level(compression_level)) {
+                Ok(compressed) => {
+                    debug!(
+                        "Compressed dependency tracker: {} bytes -> {} bytes ({:.1}% reduction)",
+                        encoded_bytes.len(),
+                        compressed.len(),
+                        100.0 * (1.0 - compressed.len() as f64 / encoded_bytes.len() as f64)
+                    );
+                    compressed
+                }
+                Err(e) => {
+                    warn!("Failed to compress dependency tracker: {}", e);
+                    return;
+                }
+            };
+
+        // The file is written next to the previous one and renamed over it, so a write which is
+        // interrupted never leaves a truncated file behind. The rename replaces the previous
+        // file on Windows as well
+        let temporary_path = path_with_suffix(path, TEMPORARY_FILE_SUFFIX);
+        let write_result = fs::write(&temporary_path, compressed_bytes).await;
+        if let Err(e) = write_result.0 {
+            warn!("Failed to write dependency tracker file: {}", e);
+            return;
+        }
+        match fs::rename(&temporary_path, path).await {
+            Ok(_) => info!("Successfully saved dependency tracker"),
             Err(e) => {
-                warn!("Failed to compress dependency tracker: {}", e);
-                return;
+                warn!("Failed to replace dependency tracker file: {}", e);
+                let _ = fs::remove_file(&temporary_path).await;
             }
-        };
-
-        let write_result = fs::write(path, compressed_bytes).await;
-        match write_result.0 {
-            Ok(_) => info!("Successfully saved dependency tracker"),
-            Err(e) => warn!("Failed to write dependency tracker file: {}", e),
         }
     }
 
+    /// Fingerprints the files of all inputs, leaving out the ignored paths. The previous
+    /// fingerprints let unchanged files skip being read again, where the strategy allows it
     async fn get_dependencies_from_inputs(
-        inputs: &[String],
+        inputs: &[TaskInput],
+        ignored: &[String],
         root: &Path,
+        previous: Option<&HashMap<PathBuf, FileFingerprint>>,
     ) -> HashMap<PathBuf, FileFingerprint> {
         let mut all_dependencies = HashMap::new();
+        let mut pending_files = Vec::new();
 
         for input in inputs {
-            let path = root.join(input);
-            if let Some(deps) = Self::get_dependencies_from_input(input, &path).await {
-                for (dep_path, fingerprint) in deps {
-                    all_dependencies.insert(dep_path, fingerprint);
-                }
-            } else {
-                info!("No dependencies found for input '{}'", input);
+            if is_url(&input.path) {
+                // Remote inputs are kept under their URL, which no file path can collide with
+                all_dependencies.insert(
+                    PathBuf::from(&input.path),
+                    fingerprint_url(&input.path).await,
+                );
+                continue;
+            }
+            if is_glob(&input.path) {
+                // Every matched file is tracked, so a file starting to match changes the inputs
+                pending_files.extend(expand_glob(root, &input.path).into_iter().map(|path| {
+                    PendingFile {
+                        input,
+                        path,
+                        in_directory: false,
+                    }
+                }));
+                continue;
+            }
+            let path = root.join(&input.path);
+            if !Self::collect_input_files(input, &path, &mut pending_files, &mut all_dependencies)
+                .await
+            {
+                info!("No dependencies found for input '{}'", input.path);
             }
         }
+        all_dependencies.extend(Self::fingerprint_files(pending_files, previous).await);
+
+        let ignored_paths: Vec<PathBuf> = ignored.iter().map(|path| root.join(path)).collect();
+        all_dependencies.retain(|dep_path: &PathBuf, _| {
+            let is_ignored = ignored_paths
+                .iter()
+                .any(|ignored_path| dep_path.starts_with(ignored_path));
+            if is_ignored {
+                debug!(
+                    "Ignoring input file '{}'",
+                    dep_path.best_effort_path_display()
+                );
+            }
+            !is_ignored
+        });
         all_dependencies
     }
 
-    async fn get_dependencies_from_input(
-        input: &str,
+    /// Collects the files of the input to fingerprint, walking it if it is a directory.
+    /// Returns false if the path doesn't exist or can't be read
+    async fn collect_input_files<'a>(
+        input: &'a TaskInput,
         path: &Path,
-    ) -> Option<Vec<(PathBuf, FileFingerprint)>> {
+        pending_files: &mut Vec<PendingFile<'a>>,
+        unreadable_entries: &mut HashMap<PathBuf, FileFingerprint>,
+    ) -> bool {
         debug!("Analyzing path: '{}'", path.best_effort_path_display());
 
         if !path.exists() {
             debug!("Path '{}' does not exist", path.best_effort_path_display());
-            return None;
+            return false;
         }
 
         if path.is_file() {
             debug!("Processing file: '{}'", path.best_effort_path_display());
-            return FileFingerprint::async_try_from(path)
-                .await
-                .ok()
-                .map(|fingerprint| {
-                    debug!("Created fingerprint for file: '{}'", input);
-                    vec![(path.to_path_buf(), fingerprint)]
-                });
+            pending_files.push(PendingFile {
+                input,
+                path: path.to_path_buf(),
+                in_directory: false,
+            });
+            return true;
         }
 
         if path.is_dir() {
@@ -209,22 +765,27 @@ impl DependencyTracker {
                 "Processing directory: '{}'",
                 path.best_effort_path_display()
             );
-            return Self::get_dependencies_from_directory(path).await;
+            return Self::collect_directory_files(input, path, pending_files, unreadable_entries)
+                .await;
         }
 
         warn!(
             "Input path '{}' is neither file nor directory",
             path.best_effort_path_display()
         );
-        None
+        false
     }
 
-    async fn get_dependencies_from_directory(
+    /// Collects the files below the directory. Returns false if the directory can't be read
+    async fn collect_directory_files<'a>(
+        input: &'a TaskInput,
         path: &Path,
-    ) -> Option<Vec<(PathBuf, FileFingerprint)>> {
+        pending_files: &mut Vec<PendingFile<'a>>,
+        unreadable_entries: &mut HashMap<PathBuf, FileFingerprint>,
+    ) -> bool {
         debug!("Scanning directory: '{}'", path.best_effort_path_display());
 
-        let entries = match std::fs::read_dir(path) {
+        let entries = match Self::read_directory(path).await {
             Ok(entries) => entries,
             Err(e) => {
                 warn!(
@@ -232,52 +793,148 @@ impl DependencyTracker {
                     path.best_effort_path_display(),
                     e
                 );
-                return None;
+                return false;
             }
         };
 
-        let mut all_dependencies = Vec::new();
         let mut file_count = 0;
         let mut dir_count = 0;
 
-        for entry in entries.filter_map(|entry| entry.ok()) {
-            let entry_path = entry.path();
+        // Entries which can't be read are tracked as unreadable instead of being left out,
+        // so they make the task out of date rather than look like removed files
+        for entry in entries {
+            let entry_path = match entry {
+                Ok(entry_path) => entry_path,
+                Err(e) => {
+                    warn!(
+                        "Failed to read an entry of directory '{}': {}",
+                        path.best_effort_path_display(),
+                        e
+                    );
+                    unreadable_entries.insert(path.to_path_buf(), Self::unreadable());
+                    continue;
+                }
+            };
 
-            if entry_path.is_file() {
+            // Symlinks are followed, like inputs naming them directly
+            let Ok(metadata) = fs::metadata(&entry_path).await else {
+                continue;
+            };
+            if metadata.is_file() {
                 file_count += 1;
-                if let Ok(fingerprint) =
-                    Box::pin(FileFingerprint::async_try_from(&entry_path)).await
-                {
-                    all_dependencies.push((entry_path, fingerprint));
-                }
-            } else if entry_path.is_dir() {
+                pending_files.push(PendingFile {
+                    input,
+                    path: entry_path,
+                    in_directory: true,
+                });
+            } else if metadata.is_dir() {
                 dir_count += 1;
-                if let Some(dir_deps) =
-                    Box::pin(Self::get_dependencies_from_directory(&entry_path)).await
+                if !Box::pin(Self::collect_directory_files(
+                    input,
+                    &entry_path,
+                    pending_files,
+                    unreadable_entries,
+                ))
+                .await
                 {
-                    all_dependencies.extend(dir_deps);
+                    unreadable_entries.insert(entry_path, Self::unreadable());
                 }
             }
         }
 
         debug!(
-            "Directory '{}' scan complete: {} files, {} subdirs, {} total dependencies",
+            "Directory '{}' scan complete: {} files, {} subdirs",
             path.best_effort_path_display(),
             file_count,
-            dir_count,
-            all_dependencies.len()
+            dir_count
         );
 
-        Some(all_dependencies)
+        true
+    }
+
+    /// Lists the entries of the directory. Reading a directory blocks, so it happens on a
+    /// blocking thread rather than holding up the other tasks of the runtime
+    async fn read_directory(path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+        let path = path.to_path_buf();
+        spawn_blocking(move || {
+            std::fs::read_dir(path).map(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect()
+            })
+        })
+        .await
+        .unwrap_or_else(|_| Err(io::Error::other("reading the directory panicked")))
+    }
+
+    /// Fingerprints the files concurrently, at most [`MAX_CONCURRENT_FINGERPRINTS`] at a time.
+    /// The results keep the order of the files, so they don't depend on which finished first
+    async fn fingerprint_files(
+        pending_files: Vec<PendingFile<'_>>,
+        previous: Option<&HashMap<PathBuf, FileFingerprint>>,
+    ) -> Vec<(PathBuf, FileFingerprint)> {
+        stream::iter(pending_files)
+            .map(|pending| async move {
+                let previous_fingerprint =
+                    previous.and_then(|previous| previous.get(&pending.path));
+                match Self::fingerprint_file(pending.input, &pending.path, previous_fingerprint)
+                    .await
+                {
+                    Some(fingerprint) => Some((pending.path, fingerprint)),
+                    None => {
+                        warn!(
+                            "Failed to fingerprint file '{}'",
+                            pending.path.best_effort_path_display()
+                        );
+                        // Unlike files named by an input, the files of a directory stay tracked
+                        pending
+                            .in_directory
+                            .then(|| (pending.path, Self::unreadable()))
+                    }
+                }
+            })
+            .buffered(MAX_CONCURRENT_FINGERPRINTS)
+            .filter_map(future::ready)
+            .collect()
+            .await
+    }
+
+    /// Fingerprint of a directory entry which could not be read, never equal to a saved one
+    fn unreadable() -> FileFingerprint {
+        FileFingerprint::Unreachable {
+            checked_at: SystemTime::now(),
+        }
+    }
+
+    /// Fingerprints a single file of the input, including its permissions if the input asks for it
+    async fn fingerprint_file(
+        input: &TaskInput,
+        path: &Path,
+        previous: Option<&FileFingerprint>,
+    ) -> Option<FileFingerprint> {
+        let strategy = input.strategy.unwrap_or_default();
+        let line_endings = input.line_endings.unwrap_or_default();
+        let fingerprint =
+            FileFingerprint::from_text_path_reusing(path, strategy, line_endings, previous)
+                .await
+                .ok()?;
+        if input.permissions {
+            fingerprint.with_permissions(path).ok()
+        } else {
+            Some(fingerprint)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::task_registry::TaskRegistry;
+    use crate::file_dependencies::FingerprintStrategy;
     use crate::tasks::ExecuteTask;
     use hashlink::LinkedHashMap;
-    use saphyr::{Scalar, Yaml};
+    use rstest::rstest;
+    use saphyr::{LoadableYamlNode, Scalar, Yaml};
     use std::borrow::Cow;
     use std::io::Write;
     use tempfile::{NamedTempFile, TempDir};
@@ -336,7 +993,9 @@ mod tests {
         let file_path = temp_dir.path().join("test_dependencies.bincode.zstd");
 
         let original_tracker = DependencyTracker::default();
-        original_tracker.write_into_path(&file_path).await;
+        original_tracker
+            .write_into_path(&file_path, DEFAULT_COMPRESSION_LEVEL)
+            .await;
 
         let loaded_tracker = DependencyTracker::read_from_path(&file_path).await;
         assert_eq!(original_tracker, loaded_tracker);
@@ -364,7 +1023,9 @@ mod tests {
         original_tracker
             .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
             .await;
-        original_tracker.write_into_path(&file_path).await;
+        original_tracker
+            .write_into_path(&file_path, DEFAULT_COMPRESSION_LEVEL)
+            .await;
 
         let loaded_tracker = DependencyTracker::read_from_path(&file_path).await;
         assert_eq!(original_tracker, loaded_tracker);
@@ -372,6 +1033,48 @@ mod tests {
         assert!(loaded_tracker.dependencies.contains_key("test_task"));
     }
 
+    #[rstest]
+    #[case(0)]
+    #[case(1)]
+    #[case(19)]
+    #[case(-5)]
+    #[case(40)]
+    #[compio::test]
+    async fn test_write_and_read_at_compression_level(#[case] level: i32) {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let file_path = temp_dir.path().join("test_dependencies.bincode.zstd");
+        let mut original_tracker = DependencyTracker::default();
+        original_tracker.record_task_duration("build", Duration::from_secs(3));
+
+        original_tracker.write_into_path(&file_path, level).await;
+
+        let loaded_tracker = DependencyTracker::read_from_path(&file_path).await;
+        assert_eq!(original_tracker, loaded_tracker);
+    }
+
+    #[compio::test]
+    async fn test_interrupted_write_keeps_the_previous_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let file_path = temp_dir.path().join("test_dependencies.bincode.zstd");
+        let mut original_tracker = DependencyTracker::default();
+        original_tracker.record_task_duration("build", Duration::from_secs(3));
+        original_tracker
+            .write_into_path(&file_path, DEFAULT_COMPRESSION_LEVEL)
+            .await;
+        assert!(!path_with_suffix(&file_path, TEMPORARY_FILE_SUFFIX).exists());
+
+        // A write killed halfway leaves a truncated temporary file
+        let complete_bytes = std::fs::read(&file_path).unwrap();
+        std::fs::write(
+            path_with_suffix(&file_path, TEMPORARY_FILE_SUFFIX),
+            &complete_bytes[..complete_bytes.len() / 2],
+        )
+        .unwrap();
+
+        let loaded_tracker = DependencyTracker::read_from_path(&file_path).await;
+        assert_eq!(original_tracker, loaded_tracker);
+    }
+
     #[compio::test]
     async fn test_add_tasks_dependencies_with_files() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
@@ -550,6 +1253,23 @@ mod tests {
         assert!(tracker.dependencies.is_empty());
     }
 
+    #[compio::test]
+    async fn test_corrupt_file_is_backed_up() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let file_path = temp_dir.path().join("dependencies.bincode.zstd");
+        let corrupted_data = b"this is not valid compressed bincode data";
+        std::fs::write(&file_path, corrupted_data).unwrap();
+
+        let tracker = DependencyTracker::read_from_path(&file_path).await;
+
+        assert_eq!(tracker, DependencyTracker::default());
+        assert!(!file_path.exists());
+        assert_eq!(
+            std::fs::read(temp_dir.path().join("dependencies.bincode.zstd.corrupt")).unwrap(),
+            corrupted_data
+        );
+    }
+
     #[compio::test]
     async fn test_read_from_invalid_compression() {
         // Create valid bincode but with invalid compression
@@ -599,6 +1319,35 @@ mod tests {
         assert!(task_deps.contains_key(&file3_path));
     }
 
+    #[compio::test]
+    async fn test_relative_inputs_resolve_against_the_root_not_the_working_directory() {
+        let root = TempDir::new().expect("Failed to create temp directory");
+        std::fs::create_dir_all(root.path().join("assets")).expect("Failed to create directory");
+        let input_path = root.path().join("assets").join("relative_input.txt");
+        std::fs::write(&input_path, "content").expect("Failed to write file");
+        // The tests run in the crate directory, which doesn't contain the input
+        assert!(!Path::new("assets/relative_input.txt").exists());
+
+        let mut tracker = DependencyTracker::default();
+        let task = create_test_task(
+            "relative_task",
+            vec!["assets/relative_input.txt".to_string()],
+            vec![],
+        );
+
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), root.path())
+            .await;
+
+        assert_eq!(
+            tracker.dependencies["relative_task"]
+                .keys()
+                .collect::<Vec<_>>(),
+            vec![&input_path]
+        );
+        assert!(tracker.is_task_up_to_date(&task, root.path()).await);
+    }
+
     #[compio::test]
     async fn test_multiple_tasks_same_dependencies() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
@@ -653,4 +1402,654 @@ mod tests {
         // Empty task should be up to date
         assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
     }
+
+    #[compio::test]
+    async fn test_input_strategy_overrides_default() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        std::fs::write(temp_dir.path().join("hashed.txt"), "content")
+            .expect("Failed to write file");
+        std::fs::write(temp_dir.path().join("stamped.txt"), "content")
+            .expect("Failed to write file");
+
+        let task_yaml = r#"
+command: echo test
+inputs:
+  - path: hashed.txt
+    strategy: hash
+  - stamped.txt
+"#;
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        let task = Task::Execute(
+            ExecuteTask::from_task_yaml("task", task_data[0].as_mapping().unwrap()).unwrap(),
+        );
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+
+        let task_deps = &tracker.dependencies["task"];
+        assert!(matches!(
+            task_deps[&temp_dir.path().join("hashed.txt")],
+            FileFingerprint::Hash(_)
+        ));
+        assert!(matches!(
+            task_deps[&temp_dir.path().join("stamped.txt")],
+            FileFingerprint::MetaStamp { .. }
+        ));
+    }
+
+    #[compio::test]
+    async fn test_touched_file_with_same_content_is_up_to_date_with_mtime_and_hash() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let test_file_path = temp_dir.path().join("script.sh");
+        std::fs::write(&test_file_path, "echo hello").expect("Failed to write file");
+
+        let task_yaml = r#"
+command: echo test
+inputs:
+  - path: script.sh
+    strategy: mtime+hash
+"#;
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        let task = Task::Execute(
+            ExecuteTask::from_task_yaml("task", task_data[0].as_mapping().unwrap()).unwrap(),
+        );
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+
+        let touched_mtime = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(&test_file_path)
+            .and_then(|file| file.set_modified(touched_mtime))
+            .expect("Failed to touch the file");
+        assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+
+        std::fs::write(&test_file_path, "echo changed").expect("Failed to write file");
+        assert!(!tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+    }
+
+    #[cfg(unix)]
+    #[compio::test]
+    async fn test_permission_change_invalidates_task_tracking_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let test_file_path = temp_dir.path().join("script.sh");
+        std::fs::write(&test_file_path, "echo hello").expect("Failed to write file");
+        std::fs::set_permissions(&test_file_path, std::fs::Permissions::from_mode(0o644))
+            .expect("Failed to set permissions");
+
+        let task_yaml = r#"
+command: echo test
+inputs:
+  - path: script.sh
+    permissions: true
+"#;
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        let task = Task::Execute(
+            ExecuteTask::from_task_yaml("task", task_data[0].as_mapping().unwrap()).unwrap(),
+        );
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+        assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+
+        std::fs::set_permissions(&test_file_path, std::fs::Permissions::from_mode(0o755))
+            .expect("Failed to set permissions");
+        assert!(!tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+    }
+
+    #[cfg(unix)]
+    #[compio::test]
+    async fn test_unchanged_directory_stamps_skip_scanning_the_directory() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let generated_dir = temp_dir.path().join("generated");
+        std::fs::create_dir_all(generated_dir.join("nested")).expect("Failed to create dirs");
+        let file_path = generated_dir.join("nested/file.txt");
+        std::fs::write(&file_path, "content").expect("Failed to write file");
+
+        let task_yaml = r#"
+command: echo test
+inputs:
+  - path: generated
+    directoryStamp: true
+"#;
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        let task = Task::Execute(
+            ExecuteTask::from_task_yaml("task", task_data[0].as_mapping().unwrap()).unwrap(),
+        );
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+        assert_eq!(tracker.dependencies["task"].len(), 1);
+        assert_eq!(tracker.directory_stamps["task"].len(), 2);
+
+        // Editing a file in place leaves the directory stamps untouched
+        std::fs::write(&file_path, "changed content").expect("Failed to write file");
+        assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+
+        // A modified subdirectory falls back to scanning the files
+        let touched_mtime = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        std::fs::File::open(generated_dir.join("nested"))
+            .and_then(|dir| dir.set_modified(touched_mtime))
+            .expect("Failed to touch the directory");
+        assert!(!tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_memoized_run_is_shared_by_tasks_with_the_same_command_and_inputs() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        std::fs::write(temp_dir.path().join("input.txt"), "content").expect("Failed to write");
+
+        let memoized_task = |name: &str, command: &str| {
+            let task_yaml = format!("command: {}\ninputs: [input.txt]\nmemoize: true", command);
+            let task_data = Yaml::load_from_str(&task_yaml).unwrap();
+            Task::Execute(
+                ExecuteTask::from_task_yaml(name, task_data[0].as_mapping().unwrap()).unwrap(),
+            )
+        };
+        let original = memoized_task("original", "cat input.txt");
+        let copy = memoized_task("copy", "cat input.txt");
+        let other_command = memoized_task("other", "wc input.txt");
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&original), temp_dir.path())
+            .await;
+
+        assert!(tracker.is_task_up_to_date(&copy, temp_dir.path()).await);
+        assert!(
+            !tracker
+                .is_task_up_to_date(&other_command, temp_dir.path())
+                .await
+        );
+
+        std::fs::write(temp_dir.path().join("input.txt"), "changed").expect("Failed to write");
+        assert!(!tracker.is_task_up_to_date(&copy, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_tasks_without_memoize_do_not_share_runs() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let input_path = temp_dir.path().join("input.txt");
+        std::fs::write(&input_path, "content").expect("Failed to write");
+        let input = input_path.to_string_lossy().to_string();
+
+        let original = create_test_task("original", vec![input.clone()], vec![]);
+        let copy = create_test_task("copy", vec![input], vec![]);
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&original), temp_dir.path())
+            .await;
+
+        assert!(tracker.memoized_runs.is_empty());
+        assert!(!tracker.is_task_up_to_date(&copy, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_running_producer_invalidates_consumers_of_virtual_artifact() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let task_yaml = r#"
+migrate:
+  command: ./migrate
+  produces: ["virtual:db-schema"]
+test:
+  command: ./test
+  consumes: ["virtual:db-schema"]
+"#;
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        let task = |name: &str| {
+            let yaml = &task_data[0][name];
+            Task::Execute(ExecuteTask::from_task_yaml(name, yaml.as_mapping().unwrap()).unwrap())
+        };
+        let (migrate, test) = (task("migrate"), task("test"));
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies([&migrate, &test].into_iter(), temp_dir.path())
+            .await;
+        tracker.record_virtual_artifacts(&migrate, true);
+        tracker.record_virtual_artifacts(&test, true);
+        assert!(tracker.is_task_up_to_date(&test, temp_dir.path()).await);
+
+        // The migration ran again, while the tests were skipped in an earlier part of the run
+        tracker.record_virtual_artifacts(&test, false);
+        tracker.record_virtual_artifacts(&migrate, true);
+        assert!(!tracker.is_task_up_to_date(&test, temp_dir.path()).await);
+        assert!(tracker.is_task_up_to_date(&migrate, temp_dir.path()).await);
+
+        tracker.record_virtual_artifacts(&test, true);
+        assert!(tracker.is_task_up_to_date(&test, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_ignored_inputs_are_not_tracked() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        std::fs::create_dir_all(temp_dir.path().join("build/.cache")).unwrap();
+        std::fs::write(temp_dir.path().join("build/main.o"), "object").unwrap();
+        std::fs::write(temp_dir.path().join("build/.cache/state"), "1").unwrap();
+        let task_yaml = r#"
+build:
+  command: make
+  inputs: ["build"]
+  ignoreInputs: ["build/.cache"]
+"#;
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        let task = Task::Execute(
+            ExecuteTask::from_task_yaml("build", task_data[0]["build"].as_mapping().unwrap())
+                .unwrap(),
+        );
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+
+        let deps = tracker.dependencies.get("build").unwrap();
+        assert_eq!(deps.len(), 1);
+        assert!(deps.contains_key(&temp_dir.path().join("build/main.o")));
+
+        // The task rewriting its own cache doesn't make it out of date
+        std::fs::write(temp_dir.path().join("build/.cache/state"), "2").unwrap();
+        assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+
+        std::fs::write(temp_dir.path().join("build/main.o"), "changed").unwrap();
+        assert!(!tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_task_durations_survive_writing_and_reading() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("dependencies.bincode.zstd");
+
+        let mut tracker = DependencyTracker::default();
+        tracker.record_task_duration("build", Duration::from_millis(1500));
+        tracker
+            .write_into_path(&path, DEFAULT_COMPRESSION_LEVEL)
+            .await;
+        let read_tracker = DependencyTracker::read_from_path(&path).await;
+
+        assert_eq!(
+            read_tracker.last_task_duration("build"),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(read_tracker.last_task_duration("test"), None);
+    }
+
+    #[compio::test]
+    async fn test_out_of_date_reasons_list_input_changes() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let kept = temp_dir.path().join("kept.txt");
+        let changed = temp_dir.path().join("changed.txt");
+        let removed = temp_dir.path().join("removed.txt");
+        for path in [&kept, &changed, &removed] {
+            std::fs::write(path, "content").expect("Failed to write");
+        }
+        let task = create_test_task(
+            "task",
+            vec![temp_dir.path().to_string_lossy().to_string()],
+            vec![],
+        );
+
+        let mut tracker = DependencyTracker::default();
+        assert_eq!(
+            tracker.out_of_date_reasons(&task, temp_dir.path()).await,
+            vec![OutOfDateReason::NoSavedDependencies]
+        );
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+        assert!(
+            tracker
+                .out_of_date_reasons(&task, temp_dir.path())
+                .await
+                .is_empty()
+        );
+
+        std::fs::write(&changed, "changed content").expect("Failed to write");
+        std::fs::remove_file(&removed).expect("Failed to remove");
+        let added = temp_dir.path().join("added.txt");
+        std::fs::write(&added, "content").expect("Failed to write");
+
+        let reasons = tracker.out_of_date_reasons(&task, temp_dir.path()).await;
+
+        assert_eq!(reasons.len(), 3);
+        assert!(reasons.contains(&OutOfDateReason::InputAdded(added)));
+        assert!(reasons.contains(&OutOfDateReason::InputChanged(changed)));
+        assert!(reasons.contains(&OutOfDateReason::InputRemoved(removed)));
+    }
+
+    #[compio::test]
+    async fn test_speculative_hashes_are_reused_only_for_unchanged_files() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let input_path = temp_dir.path().join("input.txt");
+        std::fs::write(&input_path, "content").expect("Failed to write");
+        let task_yaml = format!(
+            "command: cat\ninputs: [{{ path: \"{}\", strategy: mtime+hash }}]",
+            input_path.display()
+        );
+        let task_data = Yaml::load_from_str(&task_yaml).unwrap();
+        let task = Task::Execute(
+            ExecuteTask::from_task_yaml("task", task_data[0].as_mapping().unwrap()).unwrap(),
+        );
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+        let speculative =
+            DependencyTracker::fingerprint_task_inputs(&task, temp_dir.path(), None).await;
+        assert!(
+            tracker
+                .is_task_up_to_date_reusing(&task, temp_dir.path(), Some(&speculative))
+                .await
+        );
+
+        // A change after the speculative fingerprinting is still noticed
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        std::fs::write(&input_path, "changed").expect("Failed to write");
+        assert!(
+            !tracker
+                .is_task_up_to_date_reusing(&task, temp_dir.path(), Some(&speculative))
+                .await
+        );
+    }
+
+    fn task_with_outputs() -> Task {
+        let task_yaml = r#"
+build:
+  command: make
+  inputs: ["main.c"]
+  outputs: ["dist"]
+"#;
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        Task::Execute(
+            ExecuteTask::from_task_yaml("build", task_data[0]["build"].as_mapping().unwrap())
+                .unwrap(),
+        )
+    }
+
+    #[rstest]
+    #[case::stable(None, true)]
+    #[case::output_deleted(Some(None), false)]
+    #[case::output_modified(Some(Some("tampered")), false)]
+    #[compio::test]
+    async fn test_outputs_must_match_to_be_up_to_date(
+        #[case] change: Option<Option<&str>>,
+        #[case] up_to_date: bool,
+    ) {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        std::fs::write(temp_dir.path().join("main.c"), "int main() {}").unwrap();
+        std::fs::create_dir(temp_dir.path().join("dist")).unwrap();
+        let binary = temp_dir.path().join("dist/app");
+        std::fs::write(&binary, "binary").unwrap();
+        let task = task_with_outputs();
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+        match change {
+            Some(Some(contents)) => std::fs::write(&binary, contents).unwrap(),
+            Some(None) => std::fs::remove_file(&binary).unwrap(),
+            None => {}
+        }
+
+        assert_eq!(
+            tracker.is_task_up_to_date(&task, temp_dir.path()).await,
+            up_to_date
+        );
+        if !up_to_date {
+            assert_eq!(
+                tracker.out_of_date_reasons(&task, temp_dir.path()).await,
+                vec![OutOfDateReason::OutputChanged(binary)]
+            );
+        }
+    }
+
+    #[compio::test]
+    async fn test_glob_input_tracks_matching_files() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        std::fs::create_dir_all(temp_dir.path().join("src/nested")).unwrap();
+        std::fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(temp_dir.path().join("src/README.md"), "docs").unwrap();
+        let task = create_test_task("build", vec!["src/**/*.rs".to_string()], vec![]);
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+
+        let deps = tracker.dependencies.get("build").unwrap();
+        assert_eq!(deps.len(), 1);
+        assert!(deps.contains_key(&temp_dir.path().join("src/main.rs")));
+
+        std::fs::write(temp_dir.path().join("src/README.md"), "more docs").unwrap();
+        assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+
+        std::fs::write(temp_dir.path().join("src/nested/lib.rs"), "").unwrap();
+        assert!(!tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_glob_input_without_matches_has_no_dependencies() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let task = create_test_task("build", vec!["src/**/*.rs".to_string()], vec![]);
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+
+        assert!(tracker.dependencies.get("build").unwrap().is_empty());
+        assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_files_added_to_or_removed_from_input_directory_invalidate_task() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let input_dir = temp_dir.path().join("assets");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("a.txt"), "a").unwrap();
+        std::fs::write(input_dir.join("b.txt"), "b").unwrap();
+        let task = create_test_task("bundle", vec!["assets".to_string()], vec![]);
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+        assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+
+        std::fs::write(input_dir.join("c.txt"), "c").unwrap();
+        assert!(!tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+
+        std::fs::remove_file(input_dir.join("c.txt")).unwrap();
+        assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+
+        std::fs::remove_file(input_dir.join("b.txt")).unwrap();
+        assert!(!tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_forced_hash_strategy_ignores_rewrites_with_same_content() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source = temp_dir.path().join("main.c");
+        std::fs::write(&source, "int main() {}").unwrap();
+        let mut task = create_test_task("build", vec!["main.c".to_string()], vec![]);
+        task.force_strategy(FingerprintStrategy::Hash);
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&task), temp_dir.path())
+            .await;
+        let file = std::fs::File::options().write(true).open(&source).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        assert!(tracker.is_task_up_to_date(&task, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_changing_only_the_command_reruns_the_task() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        std::fs::write(temp_dir.path().join("input.txt"), "content").expect("Failed to write");
+
+        let task_with_command = |command: &str| {
+            let task_yaml = format!("command: {}\ninputs: [input.txt]", command);
+            let task_data = Yaml::load_from_str(&task_yaml).unwrap();
+            Task::Execute(
+                ExecuteTask::from_task_yaml("task", task_data[0].as_mapping().unwrap()).unwrap(),
+            )
+        };
+        let original = task_with_command("cat input.txt");
+        let edited = task_with_command("wc input.txt");
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies(std::iter::once(&original), temp_dir.path())
+            .await;
+
+        assert!(tracker.is_task_up_to_date(&original, temp_dir.path()).await);
+        assert!(!tracker.is_task_up_to_date(&edited, temp_dir.path()).await);
+        assert_eq!(
+            tracker.out_of_date_reasons(&edited, temp_dir.path()).await,
+            vec![OutOfDateReason::CommandChanged]
+        );
+    }
+
+    #[compio::test]
+    async fn test_forget_reruns_only_the_forgotten_task() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        std::fs::write(temp_dir.path().join("input.txt"), "content").expect("Failed to write");
+        let task_named = |name: &str| {
+            let task_data =
+                Yaml::load_from_str("command: cat input.txt\ninputs: [input.txt]\nmemoize: true")
+                    .unwrap();
+            Task::Execute(
+                ExecuteTask::from_task_yaml(name, task_data[0].as_mapping().unwrap()).unwrap(),
+            )
+        };
+        let forgotten = task_named("forgotten");
+        let kept = task_named("kept");
+
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies([&forgotten, &kept].into_iter(), temp_dir.path())
+            .await;
+
+        assert!(tracker.forget("forgotten"));
+        assert!(!tracker.forget("forgotten"));
+        assert!(!tracker.forget("unknown"));
+        assert!(tracker.saved_fingerprints("forgotten").is_none());
+        assert!(tracker.saved_fingerprints("kept").is_some());
+        assert!(
+            !tracker
+                .is_task_up_to_date(&forgotten, temp_dir.path())
+                .await
+        );
+        assert!(tracker.is_task_up_to_date(&kept, temp_dir.path()).await);
+    }
+
+    #[compio::test]
+    async fn test_prune_drops_tasks_missing_from_the_registry() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        std::fs::write(temp_dir.path().join("input.txt"), "content").expect("Failed to write");
+        let registry = TaskRegistry::try_from(
+            "tasks:\n  build:\n    command: cat input.txt\n    inputs: [input.txt]",
+        )
+        .unwrap();
+        let build = registry.get_task_by_id("build").unwrap();
+        let defunct = create_test_task("defunct", vec!["input.txt".to_string()], vec![]);
+        let mut tracker = DependencyTracker::default();
+        tracker
+            .add_tasks_dependencies([build, &defunct].into_iter(), temp_dir.path())
+            .await;
+        tracker.record_task_duration("defunct", Duration::from_secs(1));
+
+        let valid_ids = registry.get_tasks_iter().map(|task| task.id()).collect();
+        assert_eq!(tracker.prune(&valid_ids), 1);
+        assert_eq!(tracker.prune(&valid_ids), 0);
+
+        assert!(tracker.saved_fingerprints("defunct").is_none());
+        assert!(tracker.last_task_duration("defunct").is_none());
+        assert!(tracker.saved_fingerprints("build").is_some());
+    }
+
+    #[compio::test]
+    async fn test_concurrent_fingerprints_match_sequential_ones() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut files = Vec::new();
+        for dir_index in 0..20 {
+            let dir = temp_dir.path().join(format!("dir{}", dir_index));
+            std::fs::create_dir(&dir).expect("Failed to create directory");
+            for file_index in 0..100 {
+                let path = dir.join(format!("file{}.txt", file_index));
+                std::fs::write(&path, format!("{} {}", dir_index, file_index))
+                    .expect("Failed to write");
+                files.push(path);
+            }
+        }
+        let input = TaskInput {
+            strategy: Some(FingerprintStrategy::Hash),
+            ..TaskInput::new(temp_dir.path().to_string_lossy())
+        };
+
+        let started = std::time::Instant::now();
+        let concurrent = DependencyTracker::get_dependencies_from_inputs(
+            std::slice::from_ref(&input),
+            &[],
+            temp_dir.path(),
+            None,
+        )
+        .await;
+        let concurrent_time = started.elapsed();
+
+        let started = std::time::Instant::now();
+        let mut sequential = HashMap::new();
+        for path in files {
+            let fingerprint = DependencyTracker::fingerprint_file(&input, &path, None)
+                .await
+                .unwrap();
+            sequential.insert(path, fingerprint);
+        }
+        let sequential_time = started.elapsed();
+
+        println!(
+            "Fingerprinted 2000 files in {:?} concurrently, {:?} sequentially",
+            concurrent_time, sequential_time
+        );
+        assert_eq!(concurrent, sequential);
+    }
+
+    #[compio::test]
+    async fn test_concurrent_directory_scans_complete() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        for dir_index in 0..10 {
+            let dir = temp_dir.path().join(format!("dir{}/nested", dir_index));
+            std::fs::create_dir_all(&dir).expect("Failed to create directory");
+            std::fs::write(dir.join("file.txt"), "content").expect("Failed to write");
+        }
+        let task = create_test_task(
+            "task",
+            vec![temp_dir.path().to_string_lossy().to_string()],
+            vec![],
+        );
+
+        let scans = (0..8)
+            .map(|_| DependencyTracker::fingerprint_task_inputs(&task, temp_dir.path(), None));
+        let results = futures::future::join_all(scans).await;
+
+        assert!(results.iter().all(|dependencies| dependencies.len() == 10));
+        assert!(results.windows(2).all(|pair| pair[0] == pair[1]));
+    }
 }
@@ -7,20 +7,169 @@ use bincode::{Decode, Encode};
 use compio::fs;
 use metrohash::MetroHash64;
 use snafu::{ResultExt, Snafu};
-use std::hash::Hasher;
+use std::hash::{Hash, Hasher};
 
 use crate::ext::{AsyncTryFrom, BestEffortPathExt};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode)]
 pub enum FileFingerprint {
-    ModifiedTime(SystemTime),
+    /// Cheap metadata-based stamp. Pairing the size with the modification time
+    /// catches content changes which restore an older modification time
+    MetaStamp { size: u64, mtime: SystemTime },
+    /// Content-based fingerprint, used when the metadata is not sufficient
     Hash(u64),
+    /// Content hash along with the modification time it was computed at.
+    /// The file is only read again when its modification time changes,
+    /// and only the content decides whether it is unchanged
+    StampedHash { mtime: SystemTime, hash: u64 },
+    /// Another fingerprint extended with the Unix permission bits of the file
+    WithPermissions {
+        mode: u32,
+        fingerprint: Box<FileFingerprint>,
+    },
+    /// Hash of the validator headers or the contents of a remote resource
+    Remote(u64),
+    /// Remote resource which could not be fetched, or a file found in an input directory
+    /// which could not be read. Differs from any other fingerprint taken at another time,
+    /// so the input is always treated as changed
+    Unreachable { checked_at: SystemTime },
 }
 
-impl AsyncTryFrom<&Path> for FileFingerprint {
-    type Error = Fingerprint;
+impl PartialEq for FileFingerprint {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                FileFingerprint::MetaStamp { size, mtime },
+                FileFingerprint::MetaStamp {
+                    size: other_size,
+                    mtime: other_mtime,
+                },
+            ) => size == other_size && mtime == other_mtime,
+            (FileFingerprint::Hash(hash), FileFingerprint::Hash(other_hash)) => hash == other_hash,
+            (
+                FileFingerprint::StampedHash { hash, .. },
+                FileFingerprint::StampedHash {
+                    hash: other_hash, ..
+                },
+            ) => hash == other_hash,
+            (
+                FileFingerprint::WithPermissions { mode, fingerprint },
+                FileFingerprint::WithPermissions {
+                    mode: other_mode,
+                    fingerprint: other_fingerprint,
+                },
+            ) => mode == other_mode && fingerprint == other_fingerprint,
+            (FileFingerprint::Remote(hash), FileFingerprint::Remote(other_hash)) => {
+                hash == other_hash
+            }
+            (
+                FileFingerprint::Unreachable { checked_at },
+                FileFingerprint::Unreachable {
+                    checked_at: other_checked_at,
+                },
+            ) => checked_at == other_checked_at,
+            _ => false,
+        }
+    }
+}
 
-    async fn async_try_from(path: &Path) -> Result<Self, Self::Error> {
+impl Eq for FileFingerprint {}
+
+impl Hash for FileFingerprint {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            FileFingerprint::MetaStamp { size, mtime } => {
+                size.hash(state);
+                mtime.hash(state);
+            }
+            // The modification time doesn't take part in equality, so it can't be hashed
+            FileFingerprint::Hash(hash)
+            | FileFingerprint::StampedHash { hash, .. }
+            | FileFingerprint::Remote(hash) => hash.hash(state),
+            FileFingerprint::WithPermissions { mode, fingerprint } => {
+                mode.hash(state);
+                fingerprint.hash(state);
+            }
+            FileFingerprint::Unreachable { checked_at } => checked_at.hash(state),
+        }
+    }
+}
+
+/// Selects how a file fingerprint is computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FingerprintStrategy {
+    /// Use the size and modification time, falling back to hashing when those are unavailable
+    #[default]
+    Metadata,
+    /// Always hash the file contents
+    Hash,
+    /// Hash the file contents, but only read them again when the modification time changes
+    MetadataAndHash,
+}
+
+impl FingerprintStrategy {
+    /// Parses the strategy name used in the task file
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mtime" => Some(FingerprintStrategy::Metadata),
+            "hash" => Some(FingerprintStrategy::Hash),
+            "mtime+hash" => Some(FingerprintStrategy::MetadataAndHash),
+            _ => None,
+        }
+    }
+}
+
+/// How the line endings of an input are treated when hashing its contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LineEndings {
+    /// Hash the contents as they are
+    #[default]
+    Keep,
+    /// Hash the contents with CRLF line endings converted to LF, so text files checked out
+    /// on Windows and Unix get the same hash
+    Normalize,
+}
+
+impl LineEndings {
+    /// Parses the `lineEndings` value used in the task file
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keep" => Some(LineEndings::Keep),
+            "normalize" => Some(LineEndings::Normalize),
+            _ => None,
+        }
+    }
+}
+
+impl FileFingerprint {
+    /// Fingerprints the file at the given path using the given strategy
+    pub async fn from_path(
+        path: &Path,
+        strategy: FingerprintStrategy,
+    ) -> Result<Self, Fingerprint> {
+        Self::from_path_reusing(path, strategy, None).await
+    }
+
+    /// Fingerprints the file at the given path, reusing the hash of the previous fingerprint
+    /// instead of reading the file, if its modification time didn't change since
+    pub async fn from_path_reusing(
+        path: &Path,
+        strategy: FingerprintStrategy,
+        previous: Option<&FileFingerprint>,
+    ) -> Result<Self, Fingerprint> {
+        Self::from_text_path_reusing(path, strategy, LineEndings::Keep, previous).await
+    }
+
+    /// Same as [`FileFingerprint::from_path_reusing`], with the line endings of the contents
+    /// treated as given when they are hashed
+    pub async fn from_text_path_reusing(
+        path: &Path,
+        strategy: FingerprintStrategy,
+        line_endings: LineEndings,
+        previous: Option<&FileFingerprint>,
+    ) -> Result<Self, Fingerprint> {
+        let previous = previous.map(FileFingerprint::without_permissions);
         let metadata = path.metadata().context(PathSnafu {
             path: path.to_path_buf(),
         })?;
@@ -31,21 +180,95 @@ impl AsyncTryFrom<&Path> for FileFingerprint {
             });
         }
 
-        // Try to use modified time first
-        if let Ok(modified_time) = metadata.modified() {
-            return Ok(FileFingerprint::ModifiedTime(modified_time));
+        // Try to use the metadata stamp first
+        if strategy == FingerprintStrategy::Metadata
+            && let Ok(mtime) = metadata.modified()
+        {
+            return Ok(FileFingerprint::MetaStamp {
+                size: metadata.len(),
+                mtime,
+            });
+        }
+
+        if strategy == FingerprintStrategy::MetadataAndHash
+            && let Ok(mtime) = metadata.modified()
+        {
+            if let Some(FileFingerprint::StampedHash {
+                mtime: previous_mtime,
+                hash,
+            }) = previous
+                && *previous_mtime == mtime
+            {
+                return Ok(FileFingerprint::StampedHash { mtime, hash: *hash });
+            }
+
+            let hash = Self::hash_contents(path, line_endings).await?;
+            return Ok(FileFingerprint::StampedHash { mtime, hash });
         }
 
-        // Fallback to hash if modified time is not available
+        // Hash the contents if requested or if modified time is not available
+        Ok(FileFingerprint::Hash(
+            Self::hash_contents(path, line_endings).await?,
+        ))
+    }
+
+    /// Extends the fingerprint with the permission bits of the file at the given path.
+    /// Permissions are only tracked on Unix, elsewhere the fingerprint is returned as is
+    pub fn with_permissions(self, path: &Path) -> Result<Self, Fingerprint> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let metadata = path.metadata().context(PathSnafu {
+                path: path.to_path_buf(),
+            })?;
+            Ok(FileFingerprint::WithPermissions {
+                mode: metadata.permissions().mode() & 0o7777,
+                fingerprint: Box::new(self),
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = path;
+            Ok(self)
+        }
+    }
+
+    /// Returns the fingerprint of the contents, without the permission bits
+    pub fn without_permissions(&self) -> &FileFingerprint {
+        match self {
+            FileFingerprint::WithPermissions { fingerprint, .. } => fingerprint.as_ref(),
+            fingerprint => fingerprint,
+        }
+    }
+
+    async fn hash_contents(path: &Path, line_endings: LineEndings) -> Result<u64, Fingerprint> {
         let bytes = fs::read(path).await.context(PathSnafu {
             path: path.to_path_buf(),
         })?;
 
         let mut hasher = MetroHash64::new();
-        hasher.write(&bytes);
-        let hash = hasher.finish();
+        match line_endings {
+            LineEndings::Keep => hasher.write(&bytes),
+            LineEndings::Normalize => {
+                // Skip the CR of every CRLF, hashing the remaining runs as they are
+                let mut rest = &bytes[..];
+                while let Some(position) = rest.windows(2).position(|pair| pair == b"\r\n") {
+                    hasher.write(&rest[..position]);
+                    rest = &rest[position + 1..];
+                }
+                hasher.write(rest);
+            }
+        }
+        Ok(hasher.finish())
+    }
+}
+
+impl AsyncTryFrom<&Path> for FileFingerprint {
+    type Error = Fingerprint;
 
-        Ok(FileFingerprint::Hash(hash))
+    async fn async_try_from(path: &Path) -> Result<Self, Self::Error> {
+        Self::from_path(path, FingerprintStrategy::default()).await
     }
 }
 #[derive(Debug, Snafu)]
@@ -77,12 +300,14 @@ mod tests {
 
         assert!(fingerprint.is_ok());
         match fingerprint.unwrap() {
-            FileFingerprint::ModifiedTime(_) => {
+            FileFingerprint::MetaStamp { size, .. } => {
                 // This is the expected case on most systems
+                assert_eq!(size, "test content\n".len() as u64);
             }
             FileFingerprint::Hash(_) => {
                 // This might happen on some systems where modified time is not available
             }
+            other => panic!("Unexpected fingerprint for the default strategy: {other:?}"),
         }
     }
 
@@ -166,8 +391,12 @@ mod tests {
                     "Files with identical content should have identical hashes"
                 );
             }
-            (FileFingerprint::ModifiedTime(_), FileFingerprint::ModifiedTime(_)) => {
-                // Modified times will likely be different, which is expected
+            (
+                FileFingerprint::MetaStamp { size: s1, .. },
+                FileFingerprint::MetaStamp { size: s2, .. },
+            ) => {
+                // Modified times will likely be different, but the sizes have to match
+                assert_eq!(s1, s2);
             }
             _ => {
                 // Mixed fingerprint types, which is possible but not the main test case
@@ -241,6 +470,162 @@ mod tests {
         assert_eq!(fingerprint1, fingerprint2);
     }
 
+    #[compio::test]
+    async fn test_file_fingerprint_detects_size_change_with_restored_mtime() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        std::fs::write(temp_file.path(), "short").expect("Failed to write to temp file");
+
+        let first_fingerprint = FileFingerprint::async_try_from(temp_file.path())
+            .await
+            .expect("Failed to create first fingerprint");
+        let original_mtime = temp_file
+            .path()
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .expect("Failed to read modification time");
+
+        std::fs::write(temp_file.path(), "much longer content")
+            .expect("Failed to write to temp file");
+        // Restore the old modification time, like an archive extraction would
+        temp_file
+            .as_file()
+            .set_modified(original_mtime)
+            .expect("Failed to restore modification time");
+
+        let second_fingerprint = FileFingerprint::async_try_from(temp_file.path())
+            .await
+            .expect("Failed to create second fingerprint");
+
+        assert_ne!(first_fingerprint, second_fingerprint);
+    }
+
+    #[compio::test]
+    async fn test_file_fingerprint_hash_strategy_hashes_contents() {
+        let mut temp_file1 = NamedTempFile::new().expect("Failed to create temp file 1");
+        let mut temp_file2 = NamedTempFile::new().expect("Failed to create temp file 2");
+        writeln!(temp_file1, "same content").expect("Failed to write to temp file 1");
+        writeln!(temp_file2, "same content").expect("Failed to write to temp file 2");
+
+        let fingerprint1 = FileFingerprint::from_path(temp_file1.path(), FingerprintStrategy::Hash)
+            .await
+            .expect("Failed to create fingerprint 1");
+        let fingerprint2 = FileFingerprint::from_path(temp_file2.path(), FingerprintStrategy::Hash)
+            .await
+            .expect("Failed to create fingerprint 2");
+
+        assert!(matches!(fingerprint1, FileFingerprint::Hash(_)));
+        assert_eq!(fingerprint1, fingerprint2);
+    }
+
+    #[compio::test]
+    async fn test_stamped_hash_ignores_touch_without_content_change() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        std::fs::write(temp_file.path(), "content").expect("Failed to write to temp file");
+        let strategy = FingerprintStrategy::MetadataAndHash;
+
+        let first_fingerprint = FileFingerprint::from_path(temp_file.path(), strategy)
+            .await
+            .expect("Failed to create first fingerprint");
+
+        temp_file
+            .as_file()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .expect("Failed to touch the file");
+        let touched_fingerprint = FileFingerprint::from_path_reusing(
+            temp_file.path(),
+            strategy,
+            Some(&first_fingerprint),
+        )
+        .await
+        .expect("Failed to create touched fingerprint");
+
+        std::fs::write(temp_file.path(), "changed").expect("Failed to write to temp file");
+        let changed_fingerprint = FileFingerprint::from_path_reusing(
+            temp_file.path(),
+            strategy,
+            Some(&first_fingerprint),
+        )
+        .await
+        .expect("Failed to create changed fingerprint");
+
+        assert!(matches!(
+            first_fingerprint,
+            FileFingerprint::StampedHash { .. }
+        ));
+        assert_eq!(first_fingerprint, touched_fingerprint);
+        assert_ne!(first_fingerprint, changed_fingerprint);
+    }
+
+    #[compio::test]
+    async fn test_stamped_hash_reuses_previous_hash_for_same_mtime() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        std::fs::write(temp_file.path(), "content").expect("Failed to write to temp file");
+        let mtime = temp_file
+            .path()
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .expect("Failed to read modification time");
+        // A hash that can't come from the contents proves the file wasn't read
+        let previous = FileFingerprint::StampedHash { mtime, hash: 42 };
+
+        let fingerprint = FileFingerprint::from_path_reusing(
+            temp_file.path(),
+            FingerprintStrategy::MetadataAndHash,
+            Some(&previous),
+        )
+        .await
+        .expect("Failed to create fingerprint");
+
+        assert!(matches!(
+            fingerprint,
+            FileFingerprint::StampedHash { hash: 42, .. }
+        ));
+    }
+
+    #[cfg(unix)]
+    #[compio::test]
+    async fn test_fingerprint_with_permissions_detects_chmod() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        std::fs::write(temp_file.path(), "#!/bin/sh").expect("Failed to write to temp file");
+        async fn fingerprint(path: &Path) -> FileFingerprint {
+            FileFingerprint::from_path(path, FingerprintStrategy::Metadata)
+                .await
+                .and_then(|fingerprint| fingerprint.with_permissions(path))
+                .expect("Failed to create fingerprint")
+        }
+
+        let before = fingerprint(temp_file.path()).await;
+        let mtime = temp_file
+            .path()
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .expect("Failed to read modification time");
+        std::fs::set_permissions(temp_file.path(), std::fs::Permissions::from_mode(0o755))
+            .expect("Failed to change permissions");
+        temp_file
+            .as_file()
+            .set_modified(mtime)
+            .expect("Failed to restore modification time");
+        let after = fingerprint(temp_file.path()).await;
+
+        assert_ne!(before, after);
+        assert_eq!(before.without_permissions(), after.without_permissions());
+    }
+
+    #[rstest]
+    #[case("mtime+hash", Some(FingerprintStrategy::MetadataAndHash))]
+    #[case("mtime", Some(FingerprintStrategy::Metadata))]
+    #[case("hash", Some(FingerprintStrategy::Hash))]
+    #[case("checksum", None)]
+    fn test_fingerprint_strategy_from_name(
+        #[case] name: &str,
+        #[case] expected: Option<FingerprintStrategy>,
+    ) {
+        assert_eq!(FingerprintStrategy::from_name(name), expected);
+    }
+
     #[test]
     fn test_fingerprint_error_display() {
         let nonexistent_path = PathBuf::from("/this/path/does/not/exist.txt");
@@ -264,4 +649,35 @@ mod tests {
         assert!(directory_error_msg.contains("contains a directory"));
         assert!(directory_error_msg.contains("/tmp"));
     }
+
+    #[rstest]
+    #[case(FingerprintStrategy::Hash)]
+    #[case(FingerprintStrategy::MetadataAndHash)]
+    #[compio::test]
+    async fn test_normalized_line_endings_hash_the_same(#[case] strategy: FingerprintStrategy) {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let unix_path = temp_dir.path().join("unix.txt");
+        let windows_path = temp_dir.path().join("windows.txt");
+        std::fs::write(&unix_path, "first\nsecond\n").expect("Failed to write");
+        std::fs::write(&windows_path, "first\r\nsecond\r\n").expect("Failed to write");
+        let fingerprint = async |path: &Path, line_endings: LineEndings| {
+            let fingerprint =
+                FileFingerprint::from_text_path_reusing(path, strategy, line_endings, None)
+                    .await
+                    .expect("Failed to create fingerprint");
+            match fingerprint {
+                FileFingerprint::Hash(hash) | FileFingerprint::StampedHash { hash, .. } => hash,
+                other => panic!("Expected a content hash, got {other:?}"),
+            }
+        };
+
+        assert_eq!(
+            fingerprint(&unix_path, LineEndings::Normalize).await,
+            fingerprint(&windows_path, LineEndings::Normalize).await
+        );
+        assert_ne!(
+            fingerprint(&unix_path, LineEndings::Keep).await,
+            fingerprint(&windows_path, LineEndings::Keep).await
+        );
+    }
 }
@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher};
+use tracing::{debug, warn};
+
+use crate::ext::BestEffortPathExt;
+
+/// Characters which make an input a glob pattern rather than a literal path
+const GLOB_METACHARACTERS: [char; 4] = ['*', '?', '[', '{'];
+
+/// Returns true if the input is a glob pattern, e.g. `src/**/*.rs`
+pub fn is_glob(input: &str) -> bool {
+    input.contains(GLOB_METACHARACTERS)
+}
+
+/// Finds the files below the root matching the pattern, sorted by path.
+/// An invalid pattern is reported and matches nothing, like a path which doesn't exist
+pub fn expand_glob(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let matcher = match GlobBuilder::new(pattern).literal_separator(true).build() {
+        Ok(glob) => glob.compile_matcher(),
+        Err(e) => {
+            warn!("Invalid glob pattern '{}': {}", pattern, e);
+            return Vec::new();
+        }
+    };
+
+    let mut matches = Vec::new();
+    collect_matches(root, &root.join(glob_base(pattern)), &matcher, &mut matches);
+    matches.sort();
+    debug!("Glob pattern '{}' matched {} files", pattern, matches.len());
+    matches
+}
+
+/// Directory before the first pattern component, the only one which has to be walked
+pub fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| !is_glob(&component.as_os_str().to_string_lossy()))
+        .collect()
+}
+
+fn collect_matches(root: &Path, dir: &Path, matcher: &GlobMatcher, matches: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        debug!(
+            "Could not read directory '{}' while expanding a glob",
+            dir.best_effort_path_display()
+        );
+        return;
+    };
+
+    for entry_path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if entry_path.is_dir() {
+            collect_matches(root, &entry_path, matcher, matches);
+        } else if entry_path
+            .strip_prefix(root)
+            .is_ok_and(|relative_path| matcher.is_match(relative_path))
+        {
+            matches.push(entry_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempfile::TempDir;
+
+    #[rstest]
+    #[case("src/**/*.rs", true)]
+    #[case("tests/fixture?.json", true)]
+    #[case("assets/{css,js}", true)]
+    #[case("src/main.rs", false)]
+    #[case("src/", false)]
+    fn test_is_glob(#[case] input: &str, #[case] expected: bool) {
+        assert_eq!(is_glob(input), expected);
+    }
+
+    #[test]
+    fn test_expand_glob_matches_nested_files() {
+        let root = TempDir::new().unwrap();
+        std::fs::create_dir_all(root.path().join("src/nested")).unwrap();
+        std::fs::write(root.path().join("src/main.rs"), "").unwrap();
+        std::fs::write(root.path().join("src/nested/lib.rs"), "").unwrap();
+        std::fs::write(root.path().join("src/nested/notes.md"), "").unwrap();
+        std::fs::write(root.path().join("build.rs"), "").unwrap();
+
+        assert_eq!(
+            expand_glob(root.path(), "src/**/*.rs"),
+            vec![
+                root.path().join("src/main.rs"),
+                root.path().join("src/nested/lib.rs")
+            ]
+        );
+        assert_eq!(
+            expand_glob(root.path(), "*.rs"),
+            vec![root.path().join("build.rs")]
+        );
+        assert!(expand_glob(root.path(), "missing/**/*.rs").is_empty());
+    }
+}
@@ -1,5 +1,11 @@
 mod dependency_tracker;
 mod file_fingerprint;
+mod input_glob;
+mod out_of_date_reason;
+mod url_fingerprint;
 
 pub use dependency_tracker::*;
 pub use file_fingerprint::*;
+pub use input_glob::*;
+pub use out_of_date_reason::*;
+pub use url_fingerprint::*;
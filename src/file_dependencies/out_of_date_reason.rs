@@ -0,0 +1,47 @@
+use std::{fmt, path::PathBuf};
+
+use crate::ext::BestEffortPathExt;
+
+/// Single reason why a task is not up to date
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutOfDateReason {
+    /// The task never finished successfully, so nothing was saved for it
+    NoSavedDependencies,
+    InputAdded(PathBuf),
+    InputRemoved(PathBuf),
+    InputChanged(PathBuf),
+    /// A producer of the virtual artifact ran since the task last ran
+    ArtifactProduced(String),
+    /// An output of the task was removed or changed since it created it
+    OutputChanged(PathBuf),
+    /// The command of the task was edited since it last ran
+    CommandChanged,
+}
+
+impl fmt::Display for OutOfDateReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutOfDateReason::NoSavedDependencies => write!(f, "it never ran successfully"),
+            OutOfDateReason::InputAdded(path) => {
+                write!(f, "{} was added", path.best_effort_path_display())
+            }
+            OutOfDateReason::InputRemoved(path) => {
+                write!(f, "{} was removed", path.best_effort_path_display())
+            }
+            OutOfDateReason::InputChanged(path) => {
+                write!(f, "{} changed", path.best_effort_path_display())
+            }
+            OutOfDateReason::ArtifactProduced(artifact) => {
+                write!(f, "{} was produced since it last ran", artifact)
+            }
+            OutOfDateReason::OutputChanged(path) => {
+                write!(
+                    f,
+                    "its output {} was removed or changed",
+                    path.best_effort_path_display()
+                )
+            }
+            OutOfDateReason::CommandChanged => write!(f, "its command changed"),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::hash::Hasher;
+use std::time::SystemTime;
+
+use metrohash::MetroHash64;
+use snafu::{ResultExt, Snafu};
+use tracing::{debug, warn};
+
+use crate::file_dependencies::FileFingerprint;
+
+/// Response headers identifying a version of a remote resource, in the order of preference
+const VALIDATOR_HEADERS: [&str; 2] = ["etag", "last-modified"];
+
+/// Returns true if the input refers to a remote resource rather than a path
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// Fingerprints a remote resource. An unreachable resource gets a fingerprint which
+/// differs from every other, so the task is treated as changed
+pub async fn fingerprint_url(url: &str) -> FileFingerprint {
+    match fetch_url_fingerprint(url).await {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => {
+            warn!("{}, treating it as changed", snafu::Report::from_error(e));
+            FileFingerprint::Unreachable {
+                checked_at: SystemTime::now(),
+            }
+        }
+    }
+}
+
+/// Hashes the validator headers of a HEAD request, falling back to hashing the whole
+/// resource when the server doesn't send any of them
+async fn fetch_url_fingerprint(url: &str) -> Result<FileFingerprint, UrlFingerprintError> {
+    let client = cyper::Client::new();
+    let response = client
+        .head(url)
+        .context(RequestSnafu { url })?
+        .send()
+        .await
+        .context(RequestSnafu { url })?;
+    ensure_success(url, response.status().as_u16())?;
+
+    let mut hasher = MetroHash64::new();
+    let mut has_validator = false;
+    for header in VALIDATOR_HEADERS {
+        if let Some(value) = response.headers().get(header) {
+            debug!(
+                "Using the '{}' header of '{}' as its fingerprint",
+                header, url
+            );
+            hasher.write(header.as_bytes());
+            hasher.write(value.as_bytes());
+            has_validator = true;
+        }
+    }
+    if has_validator {
+        return Ok(FileFingerprint::Remote(hasher.finish()));
+    }
+
+    debug!("'{}' has no validator headers, hashing its contents", url);
+    let response = client
+        .get(url)
+        .context(RequestSnafu { url })?
+        .send()
+        .await
+        .context(RequestSnafu { url })?;
+    ensure_success(url, response.status().as_u16())?;
+    let body = response.bytes().await.context(RequestSnafu { url })?;
+    hasher.write(&body);
+    Ok(FileFingerprint::Remote(hasher.finish()))
+}
+
+fn ensure_success(url: &str, status: u16) -> Result<(), UrlFingerprintError> {
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        StatusSnafu { url, status }.fail()
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum UrlFingerprintError {
+    #[snafu(display("Failed to request the remote input '{}'", url))]
+    RequestError { url: String, source: cyper::Error },
+    #[snafu(display("Remote input '{}' responded with status {}", url, status))]
+    StatusError { url: String, status: u16 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("https://example.com/schema.json", true)]
+    #[case("http://localhost:8080/api", true)]
+    #[case("src/main.rs", false)]
+    #[case("./https/file", false)]
+    fn test_is_url(#[case] input: &str, #[case] expected: bool) {
+        assert_eq!(is_url(input), expected);
+    }
+
+    #[compio::test]
+    async fn test_unreachable_url_is_treated_as_changed() {
+        let url = "http://127.0.0.1:9/schema.json";
+
+        let first = fingerprint_url(url).await;
+        let second = fingerprint_url(url).await;
+
+        assert!(matches!(first, FileFingerprint::Unreachable { .. }));
+        assert_ne!(first, second);
+    }
+}
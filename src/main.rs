@@ -1,11 +1,18 @@
 #![allow(clippy::enum_variant_names)]
 
+use std::{pin::pin, time::Duration};
+
 use clap::Parser as _;
-use tracing::debug;
+use compio::signal::ctrl_c;
+use futures::future::{Either, select};
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    application::{Application, ApplicationError},
-    cli::Cli,
+    application::{Application, ApplicationError, CacheReport, InputWatcher, data::GraphFormat},
+    cli::{CacheCommand, Cli, CliCommand},
+    config::rc_config::RcConfig,
+    executor::RunReport,
+    ext::{BestEffortPathExt, CancellationToken},
 };
 
 mod application;
@@ -16,20 +23,210 @@ mod ext;
 mod file_dependencies;
 mod tasks;
 
+/// Period without further changes to the inputs, after which a watched target runs again
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+/// Interval of polling the inputs, when watching them for changes isn't possible
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 #[compio::main]
-#[snafu::report]
-async fn main() -> Result<(), ApplicationError> {
+async fn main() {
+    if let Err(error) = run().await {
+        // Reported like `#[snafu::report]` does, but exiting with the status of the error
+        let exit_code = error.exit_code();
+        eprintln!("Error: {}", snafu::Report::from_error(error));
+        std::process::exit(exit_code);
+    }
+}
+
+async fn run() -> Result<(), ApplicationError> {
     let cli_args = Cli::parse();
+    let rc_config = RcConfig::read(&cli_args.rc_dir())
+        .await
+        .map_err(|source| ApplicationError::RcConfigError { source })?;
+    let cli_args = cli_args.with_rc_config(rc_config);
     setup_tracing(&cli_args);
     debug!("Parsed CLI arguments: {cli_args:?}");
 
-    Application::run(cli_args).await?;
+    if let Some(CliCommand::Cache {
+        action: CacheCommand::Show { json },
+    }) = cli_args.command
+    {
+        let report = CacheReport::read(&cli_args.rc_dir()).await;
+        if json {
+            println!("{}", report.to_json());
+        } else {
+            println!("{}", report);
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Cache {
+        action: CacheCommand::Forget { task },
+    }) = &cli_args.command
+    {
+        if Application::forget(cli_args.clone(), task).await? {
+            println!("Forgot the saved dependencies of task '{}'", task);
+        } else {
+            println!("Task '{}' has no saved dependencies", task);
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Clean) = cli_args.command {
+        match Application::clean(cli_args.clone()).await? {
+            Some(size) => println!("Deleted the dependency cache, freeing {} bytes", size),
+            None => println!("Nothing to clean, there is no dependency cache"),
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Init { force }) = cli_args.command {
+        let path = Application::init(cli_args.clone(), force).await?;
+        println!(
+            "Created the task file '{}'",
+            path.best_effort_path_display()
+        );
+        return Ok(());
+    }
+
+    if cli_args.lists_tasks() {
+        println!("{}", Application::list_tasks(cli_args.clone()).await?);
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Why { task }) = &cli_args.command {
+        let report = Application::why(cli_args.clone(), task).await?;
+        println!("{}", report);
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Graph { task, all }) = &cli_args.command {
+        let mut graph_args = cli_args.clone();
+        graph_args.target = task.clone().or(graph_args.target);
+        let dump = Application::dump_graph(graph_args, *all).await?;
+        println!("{}", dump.to_dot());
+        return Ok(());
+    }
+
+    if let Some(format) = cli_args.dump_graph {
+        let dump = Application::dump_graph(cli_args.clone(), cli_args.dump_graph_all).await?;
+        match format {
+            GraphFormat::Json => println!("{}", dump.to_json()),
+            GraphFormat::Dot => println!("{}", dump.to_dot()),
+        }
+        return Ok(());
+    }
+
+    if cli_args.watch {
+        return watch(cli_args).await;
+    }
+
+    if let Some(interval) = cli_args.watch_poll {
+        return poll(cli_args, Duration::from_millis(interval)).await;
+    }
+
+    let report = Application::run(cli_args).await?;
+    log_report(&report);
+
+    if !report.is_success() {
+        return Err(ApplicationError::from_report(&report));
+    }
 
     Ok(())
 }
 
+/// Runs the target whenever its inputs change, until Ctrl-C is pressed
+async fn watch(cli_args: Cli) -> Result<(), ApplicationError> {
+    loop {
+        let inputs = Application::watched_inputs(cli_args.clone()).await?;
+        let mut watcher = match InputWatcher::new(inputs) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                warn!(
+                    "Can't watch the inputs for changes, polling them instead: {}",
+                    e
+                );
+                return poll(cli_args, DEFAULT_POLL_INTERVAL).await;
+            }
+        };
+
+        // The watcher is started before the run, so changes made during it are noticed
+        let stop = CancellationToken::new();
+        let run = pin!(Application::run_until(cli_args.clone(), stop.clone()));
+        let changed = pin!(watcher.changed(WATCH_DEBOUNCE));
+        let interrupted = pin!(ctrl_c());
+        let woken = select(changed, interrupted);
+        let interrupted = match select(run, woken).await {
+            Either::Left((report, woken)) => {
+                log_report(&report?);
+                info!("Watching the inputs for changes");
+                matches!(woken.await, Either::Right(_))
+            }
+            Either::Right((woken, run)) => {
+                let interrupted = matches!(woken, Either::Right(_));
+                if interrupted {
+                    info!("Interrupted, stopping the run");
+                } else {
+                    info!("Inputs changed during the run, starting over");
+                }
+                stop.cancel();
+                log_report(&run.await?);
+                interrupted
+            }
+        };
+        if interrupted {
+            return Ok(());
+        }
+    }
+}
+
+/// Runs the target whenever polling finds its inputs changed.
+/// Ctrl-C stops a run in progress, and ends polling while waiting for changes
+async fn poll(cli_args: Cli, interval: Duration) -> Result<(), ApplicationError> {
+    loop {
+        let report = Application::run(cli_args.clone()).await?;
+        log_report(&report);
+
+        let changed = pin!(Application::wait_for_changes(cli_args.clone(), interval));
+        let interrupted = pin!(ctrl_c());
+        match select(changed, interrupted).await {
+            Either::Left((changed, _)) => changed?,
+            Either::Right(_) => return Ok(()),
+        }
+    }
+}
+
+fn log_report(report: &RunReport) {
+    info!(
+        "Finished: {} executed, {} up to date, {} failed, {} cancelled",
+        report.executed().count(),
+        report.skipped().count(),
+        report.failed().count(),
+        report.cancelled().count()
+    );
+    if report.planned().next().is_some() {
+        info!("Dry run: {} tasks would run", report.planned().count());
+    }
+    // Printed regardless of the log level, which hides info messages by default
+    if let Some(summary) = report.cache_summary() {
+        eprintln!("{}", summary);
+    }
+    for task in report.tasks() {
+        debug!(
+            "Task '{}': {:?} in {:.2?} (exit code: {:?})",
+            task.task_id, task.status, task.duration, task.exit_code
+        );
+    }
+    for task in report.failed() {
+        if let Some(error) = &task.error {
+            error!("Task '{}' failed: {}", task.task_id, error);
+        }
+    }
+}
+
 fn setup_tracing(cli_args: &Cli) {
-    if let Some(level) = cli_args.log_level.to_tracing_level() {
+    let log_level = cli_args.log_level.clone().unwrap_or_default();
+    if let Some(level) = log_level.to_tracing_level() {
         tracing_subscriber::fmt()
             .with_max_level(level)
             .without_time()
@@ -1,47 +1,204 @@
+use std::collections::HashSet;
+
 use hashlink::LinkedHashMap;
 use saphyr::{Scalar, Yaml};
+use tracing::warn;
 
-use crate::tasks::TaskTrait;
+use crate::file_dependencies::FingerprintStrategy;
+use crate::tasks::{TaskContext, TaskInput, TaskTrait};
 
 use super::TaskError;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BaseTask {
     name: String,
     dependencies: Vec<String>,
-    inputs: Vec<String>,
+    before: Vec<String>,
+    after: Vec<String>,
+    inputs: Vec<TaskInput>,
+    outputs: Vec<String>,
+    requires_clean_git: bool,
+    max_output_lines: Option<usize>,
+    memoize: bool,
+    sandbox: bool,
+    no_wrapper: bool,
+    produces: Vec<String>,
+    consumes: Vec<String>,
+    ignore_inputs: Vec<String>,
+    /// Environment variables set for the command, in the order they are declared
+    env: Vec<(String, String)>,
+    description: Option<String>,
 }
 
-impl TaskTrait for BaseTask {
-    fn from_task_yaml(task_name: &str, task_data: &LinkedHashMap<Yaml, Yaml>) -> Option<Self> {
-        let dependencies = task_data
-            .get(&Yaml::Value(Scalar::String("dependsOn".into())))
+impl BaseTask {
+    /// Returns a copy of the task with a different name
+    pub fn with_name(&self, name: String) -> Self {
+        Self {
+            name,
+            ..self.clone()
+        }
+    }
+
+    /// Fingerprints every input of the task with the strategy, overriding the declared ones
+    pub fn force_strategy(&mut self, strategy: FingerprintStrategy) {
+        for input in &mut self.inputs {
+            input.strategy = Some(strategy);
+        }
+    }
+
+    /// Replaces every dependency and hook with the task id it resolves to,
+    /// e.g. when it names an alias
+    pub fn resolve_dependencies(&mut self, resolve: impl Fn(&str) -> String) {
+        for tasks in [&mut self.dependencies, &mut self.before, &mut self.after] {
+            for task in tasks.iter_mut() {
+                *task = resolve(task);
+            }
+            // A task listed both by its id and by an alias is only waited for once
+            let mut seen = HashSet::new();
+            tasks.retain(|task| seen.insert(task.clone()));
+        }
+    }
+
+    /// Number of the last output lines of each stream to keep, overriding `--max-output-lines`
+    pub fn max_output_lines(&self) -> Option<usize> {
+        self.max_output_lines
+    }
+
+    /// Whether the task runs in a temporary directory containing only its declared inputs
+    pub fn sandbox(&self) -> bool {
+        self.sandbox
+    }
+
+    /// Whether the task's command runs as it is, without the `commandWrapper` of the task file
+    pub fn no_wrapper(&self) -> bool {
+        self.no_wrapper
+    }
+
+    /// Environment variables set for the command, including the ones of the task file
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    /// Reads a list of strings under the given key, skipping entries which aren't strings
+    fn string_list(task_data: &LinkedHashMap<Yaml, Yaml>, key: &str) -> Vec<String> {
+        task_data
+            .get(&Yaml::Value(Scalar::String(key.into())))
             .and_then(|v| v.as_sequence())
             .map(|seq| {
                 seq.iter()
                     .filter_map(|item| item.as_str().map(|s| s.to_string()))
                     .collect()
             })
-            .unwrap_or_default();
+            .unwrap_or_default()
+    }
+
+    /// Reads a list of tasks under the given key, dropping the ones listed more than once,
+    /// so every task in it is an edge of the dependency graph exactly once
+    fn task_list(task_name: &str, task_data: &LinkedHashMap<Yaml, Yaml>, key: &str) -> Vec<String> {
+        let mut tasks: Vec<String> = Vec::new();
+        for task in Self::string_list(task_data, key) {
+            if tasks.contains(&task) {
+                warn!(
+                    "Task '{}' lists '{}' more than once in '{}'",
+                    task_name, task, key
+                );
+            } else {
+                tasks.push(task);
+            }
+        }
+        tasks
+    }
+}
 
-        let inputs = task_data
+impl TaskTrait for BaseTask {
+    fn from_task_yaml(task_name: &str, task_data: &LinkedHashMap<Yaml, Yaml>) -> Option<Self> {
+        let dependencies = Self::task_list(task_name, task_data, "dependsOn");
+
+        let mut inputs: Vec<TaskInput> = task_data
             .get(&Yaml::Value(Scalar::String("inputs".into())))
             .and_then(|v| v.as_sequence())
-            .map(|seq| {
-                seq.iter()
-                    .filter_map(|item| item.as_str().map(|s| s.to_string()))
+            .map(|seq| seq.iter().filter_map(TaskInput::from_yaml).collect())
+            .unwrap_or_default();
+        // Inputs without their own `lineEndings` take the one of the task
+        if let Some(line_endings) = TaskInput::line_endings_from_yaml(task_data, task_name) {
+            for input in inputs
+                .iter_mut()
+                .filter(|input| input.line_endings.is_none())
+            {
+                input.line_endings = Some(line_endings);
+            }
+        }
+
+        let before = Self::task_list(task_name, task_data, "before");
+        let after = Self::task_list(task_name, task_data, "after");
+        let outputs = Self::string_list(task_data, "outputs");
+        let produces = Self::string_list(task_data, "produces");
+        let consumes = Self::string_list(task_data, "consumes");
+        let ignore_inputs = Self::string_list(task_data, "ignoreInputs");
+
+        let requires_clean_git = task_data
+            .get(&Yaml::Value(Scalar::String("requiresCleanGit".into())))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let max_output_lines = task_data
+            .get(&Yaml::Value(Scalar::String("maxOutputLines".into())))
+            .and_then(|v| v.as_integer())
+            .and_then(|lines| usize::try_from(lines).ok());
+
+        let memoize = task_data
+            .get(&Yaml::Value(Scalar::String("memoize".into())))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let sandbox = task_data
+            .get(&Yaml::Value(Scalar::String("sandbox".into())))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let no_wrapper = task_data
+            .get(&Yaml::Value(Scalar::String("noWrapper".into())))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let env = task_data
+            .get(&Yaml::Value(Scalar::String("env".into())))
+            .and_then(|v| v.as_mapping())
+            .map(|env| {
+                env.iter()
+                    .filter_map(|(name, value)| {
+                        Some((name.as_str()?.to_string(), value.as_str()?.to_string()))
+                    })
                     .collect()
             })
             .unwrap_or_default();
 
+        let description = task_data
+            .get(&Yaml::Value(Scalar::String("description".into())))
+            .and_then(|v| v.as_str())
+            .map(|description| description.to_string());
+
         Some(BaseTask {
             name: task_name.to_string(),
             dependencies,
+            before,
+            after,
             inputs,
+            outputs,
+            requires_clean_git,
+            max_output_lines,
+            memoize,
+            sandbox,
+            no_wrapper,
+            produces,
+            consumes,
+            ignore_inputs,
+            env,
+            description,
         })
     }
 
-    async fn run(&self) -> Result<String, TaskError> {
+    async fn run(&self, _context: &TaskContext) -> Result<String, TaskError> {
         Ok(self.id())
     }
 
@@ -53,18 +210,55 @@ impl TaskTrait for BaseTask {
         &self.dependencies
     }
 
-    fn inputs(&self) -> &Vec<String> {
+    fn before(&self) -> &Vec<String> {
+        &self.before
+    }
+
+    fn after(&self) -> &Vec<String> {
+        &self.after
+    }
+
+    fn inputs(&self) -> &Vec<TaskInput> {
         &self.inputs
     }
+
+    fn requires_clean_git(&self) -> bool {
+        self.requires_clean_git
+    }
+
+    fn memoize(&self) -> bool {
+        self.memoize
+    }
+
+    fn produces(&self) -> &Vec<String> {
+        &self.produces
+    }
+
+    fn consumes(&self) -> &Vec<String> {
+        &self.consumes
+    }
+
+    fn ignore_inputs(&self) -> &Vec<String> {
+        &self.ignore_inputs
+    }
+
+    fn outputs(&self) -> &Vec<String> {
+        &self.outputs
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::file_dependencies::{FingerprintStrategy, LineEndings};
     use hashlink::LinkedHashMap;
     use ordered_float::OrderedFloat;
     use rstest::rstest;
-    use saphyr::{Scalar, Yaml};
+    use saphyr::{LoadableYamlNode, Scalar, Yaml};
 
     #[test]
     fn test_base_task_from_task_yaml_with_dependencies() {
@@ -147,6 +341,47 @@ mod tests {
         assert_eq!(task.inputs, vec!["src/lib.rs", "Cargo.toml"]);
     }
 
+    #[test]
+    fn test_base_task_from_task_yaml_with_input_strategies() {
+        let mut hash_input = LinkedHashMap::new();
+        hash_input.insert(
+            Yaml::Value(Scalar::String("path".into())),
+            Yaml::Value(Scalar::String("src/".into())),
+        );
+        hash_input.insert(
+            Yaml::Value(Scalar::String("strategy".into())),
+            Yaml::Value(Scalar::String("hash".into())),
+        );
+        let mut missing_path_input = LinkedHashMap::new();
+        missing_path_input.insert(
+            Yaml::Value(Scalar::String("strategy".into())),
+            Yaml::Value(Scalar::String("mtime".into())),
+        );
+
+        let mut task_data = LinkedHashMap::new();
+        task_data.insert(
+            Yaml::Value(Scalar::String("inputs".into())),
+            Yaml::Sequence(vec![
+                Yaml::Value(Scalar::String("Cargo.toml".into())),
+                Yaml::Mapping(hash_input),
+                Yaml::Mapping(missing_path_input),
+            ]),
+        );
+
+        let task = BaseTask::from_task_yaml("test_task", &task_data).unwrap();
+
+        assert_eq!(
+            task.inputs,
+            vec![
+                TaskInput::new("Cargo.toml"),
+                TaskInput {
+                    strategy: Some(FingerprintStrategy::Hash),
+                    ..TaskInput::new("src/")
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_base_task_from_task_yaml_without_dependencies() {
         let task_name = "test_task";
@@ -269,7 +504,7 @@ mod tests {
         let task_data = LinkedHashMap::new();
         let base_task = BaseTask::from_task_yaml(task_name, &task_data).unwrap();
 
-        let result = base_task.run().await;
+        let result = base_task.run(&TaskContext::default()).await;
 
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "test_task");
@@ -403,4 +638,169 @@ mod tests {
         assert!(debug_output.contains("dep1"));
         assert!(debug_output.contains("input1.rs"));
     }
+
+    #[test]
+    fn test_base_task_color_is_reproducible_for_seed() {
+        let task = BaseTask::from_task_yaml("test_task", &LinkedHashMap::new()).unwrap();
+
+        // Without a seed, the colors stay the ones tessy always used
+        assert_eq!(
+            task.color(None),
+            colored::Color::TrueColor {
+                r: 186,
+                g: 204,
+                b: 167
+            }
+        );
+        assert_eq!(task.color(Some(7)), task.color(Some(7)));
+        assert_ne!(task.color(Some(7)), task.color(Some(8)));
+    }
+
+    #[test]
+    fn test_base_task_requires_clean_git() {
+        let mut task_data = LinkedHashMap::new();
+        task_data.insert(
+            Yaml::Value(Scalar::String("requiresCleanGit".into())),
+            Yaml::Value(Scalar::Boolean(true)),
+        );
+
+        let task = BaseTask::from_task_yaml("release", &task_data).unwrap();
+        let default_task = BaseTask::from_task_yaml("build", &LinkedHashMap::new()).unwrap();
+
+        assert!(task.requires_clean_git());
+        assert!(!default_task.requires_clean_git());
+    }
+
+    #[test]
+    fn test_base_task_max_output_lines() {
+        let mut task_data = LinkedHashMap::new();
+        task_data.insert(
+            Yaml::Value(Scalar::String("maxOutputLines".into())),
+            Yaml::Value(Scalar::Integer(100)),
+        );
+
+        let task = BaseTask::from_task_yaml("build", &task_data).unwrap();
+        let default_task = BaseTask::from_task_yaml("build", &LinkedHashMap::new()).unwrap();
+
+        assert_eq!(task.max_output_lines(), Some(100));
+        assert_eq!(default_task.max_output_lines(), None);
+    }
+
+    #[test]
+    fn test_base_task_sandbox_with_outputs() {
+        let mut task_data = LinkedHashMap::new();
+        task_data.insert(
+            Yaml::Value(Scalar::String("sandbox".into())),
+            Yaml::Value(Scalar::Boolean(true)),
+        );
+        task_data.insert(
+            Yaml::Value(Scalar::String("outputs".into())),
+            Yaml::Sequence(vec![Yaml::Value(Scalar::String("dist/".into()))]),
+        );
+
+        let task = BaseTask::from_task_yaml("build", &task_data).unwrap();
+        let default_task = BaseTask::from_task_yaml("build", &LinkedHashMap::new()).unwrap();
+
+        assert!(task.sandbox());
+        assert_eq!(task.outputs(), &vec!["dist/".to_string()]);
+        assert!(!default_task.sandbox());
+        assert!(default_task.outputs().is_empty());
+    }
+
+    #[test]
+    fn test_base_task_virtual_artifacts() {
+        let task_yaml = r#"
+produces: ["virtual:db-schema"]
+consumes: ["virtual:seed-data", "virtual:config"]
+"#;
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        let task = BaseTask::from_task_yaml("migrate", task_data[0].as_mapping().unwrap()).unwrap();
+
+        assert_eq!(task.produces(), &vec!["virtual:db-schema".to_string()]);
+        assert_eq!(
+            task.consumes(),
+            &vec![
+                "virtual:seed-data".to_string(),
+                "virtual:config".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_base_task_ignore_inputs() {
+        let task_data = Yaml::load_from_str(r#"ignoreInputs: ["build/.cache"]"#).unwrap();
+        let task = BaseTask::from_task_yaml("build", task_data[0].as_mapping().unwrap()).unwrap();
+        let default_task = BaseTask::from_task_yaml("build", &LinkedHashMap::new()).unwrap();
+
+        assert_eq!(task.ignore_inputs(), &vec!["build/.cache".to_string()]);
+        assert!(default_task.ignore_inputs().is_empty());
+    }
+
+    #[test]
+    fn test_base_task_no_wrapper() {
+        let task_data = Yaml::load_from_str("noWrapper: true").unwrap();
+        let task = BaseTask::from_task_yaml("fmt", task_data[0].as_mapping().unwrap()).unwrap();
+        let default_task = BaseTask::from_task_yaml("fmt", &LinkedHashMap::new()).unwrap();
+
+        assert!(task.no_wrapper());
+        assert!(!default_task.no_wrapper());
+    }
+
+    #[test]
+    fn test_base_task_hooks() {
+        let task_data = Yaml::load_from_str("before: [fetch]\nafter: [notify, clean]").unwrap();
+        let task = BaseTask::from_task_yaml("build", task_data[0].as_mapping().unwrap()).unwrap();
+
+        assert_eq!(task.before(), &vec!["fetch"]);
+        assert_eq!(task.after(), &vec!["notify", "clean"]);
+        assert!(task.dependencies().is_empty());
+    }
+
+    #[test]
+    fn test_base_task_drops_repeated_dependencies() {
+        let task_data = Yaml::load_from_str(
+            "dependsOn: [lint, test, lint]
+after: [notify, notify]",
+        )
+        .unwrap();
+        let mut task =
+            BaseTask::from_task_yaml("build", task_data[0].as_mapping().unwrap()).unwrap();
+
+        assert_eq!(task.dependencies(), &vec!["lint", "test"]);
+        assert_eq!(task.after(), &vec!["notify"]);
+
+        // Both resolve to the same task once aliases are replaced
+        task.resolve_dependencies(|name| name.replace("test", "lint"));
+        assert_eq!(task.dependencies(), &vec!["lint"]);
+    }
+
+    #[rstest]
+    #[case("description: Compiles the project", Some("Compiles the project"))]
+    #[case("dependsOn: [fetch]", None)]
+    fn test_base_task_description(#[case] task_yaml: &str, #[case] expected: Option<&str>) {
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        let task = BaseTask::from_task_yaml("build", task_data[0].as_mapping().unwrap()).unwrap();
+
+        assert_eq!(task.description(), expected);
+    }
+
+    #[test]
+    fn test_base_task_inputs_inherit_line_endings() {
+        let task_yaml = r#"
+lineEndings: normalize
+inputs:
+  - src/
+  - { path: assets/, lineEndings: keep }
+"#;
+        let task_data = Yaml::load_from_str(task_yaml).unwrap();
+        let task = BaseTask::from_task_yaml("build", task_data[0].as_mapping().unwrap()).unwrap();
+
+        assert_eq!(
+            task.inputs()
+                .iter()
+                .map(|input| input.line_endings)
+                .collect::<Vec<_>>(),
+            vec![Some(LineEndings::Normalize), Some(LineEndings::Keep)]
+        );
+    }
 }
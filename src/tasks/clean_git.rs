@@ -0,0 +1,100 @@
+use std::{path::Path, process::Stdio};
+
+use compio::process::Command;
+use tracing::debug;
+
+use super::TaskError;
+
+/// Largest number of changed paths listed in the error of a dirty working tree
+const MAX_LISTED_CHANGES: usize = 10;
+
+/// Fails if `git status --porcelain`, run in the given directory, reports any uncommitted changes
+pub async fn ensure_clean_git(task_id: &str, root: &Path) -> Result<(), TaskError> {
+    debug!(
+        "Checking that the git working tree is clean for task '{}'",
+        task_id
+    );
+
+    let mut cmd = Command::new("git");
+    cmd.args(["status", "--porcelain"]);
+    let _ = cmd.current_dir(root);
+    let _ = cmd.stdout(Stdio::piped());
+    let _ = cmd.stderr(Stdio::piped());
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|source| TaskError::GitStatusError {
+            task_id: task_id.to_string(),
+            source,
+        })?;
+
+    if !output.status.success() {
+        return Err(TaskError::GitStatusFailedError {
+            task_id: task_id.to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    match uncommitted_changes(&String::from_utf8_lossy(&output.stdout)) {
+        Some(changes) => Err(TaskError::DirtyWorkingTreeError {
+            task_id: task_id.to_string(),
+            changes,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Lists the changed paths from porcelain output, or returns `None` if there are none
+fn uncommitted_changes(porcelain: &str) -> Option<String> {
+    let changes: Vec<&str> = porcelain
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+
+    if changes.is_empty() {
+        return None;
+    }
+
+    let mut listed = changes
+        .iter()
+        .take(MAX_LISTED_CHANGES)
+        .map(|line| format!("  {}", line.trim_end()))
+        .collect::<Vec<_>>();
+    if changes.len() > MAX_LISTED_CHANGES {
+        listed.push(format!(
+            "  ... and {} more",
+            changes.len() - MAX_LISTED_CHANGES
+        ));
+    }
+
+    Some(listed.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_porcelain_output_has_no_changes() {
+        assert_eq!(uncommitted_changes(""), None);
+        assert_eq!(uncommitted_changes("\n"), None);
+    }
+
+    #[test]
+    fn test_porcelain_output_lists_changes() {
+        let changes = uncommitted_changes(" M src/main.rs\n?? notes.txt\n").unwrap();
+
+        assert_eq!(changes, "   M src/main.rs\n  ?? notes.txt");
+    }
+
+    #[test]
+    fn test_long_porcelain_output_is_truncated() {
+        let porcelain: String = (0..15).map(|i| format!("?? file{}\n", i)).collect();
+
+        let changes = uncommitted_changes(&porcelain).unwrap();
+
+        assert_eq!(changes.lines().count(), MAX_LISTED_CHANGES + 1);
+        assert!(changes.ends_with("... and 5 more"));
+    }
+}
@@ -0,0 +1,100 @@
+use std::{fmt::Debug, io, path::PathBuf, process::Stdio};
+
+use compio::process::{Child, Command};
+
+/// Program a task asks to run, before it is spawned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandInvocation {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Directory the command runs in, the current one if not set
+    pub current_dir: Option<PathBuf>,
+    /// Environment variables set for the command, on top of the inherited ones
+    pub env: Vec<(String, String)>,
+}
+
+/// Spawns the processes of tasks. Replacing the runner lets commands be wrapped,
+/// or recorded without being run
+pub trait CommandRunner: Debug + Send + Sync {
+    /// Spawns the invocation with its stdout and stderr piped
+    fn spawn(&self, invocation: &CommandInvocation) -> io::Result<Child>;
+}
+
+/// Runner spawning commands as they are
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessRunner;
+
+impl CommandRunner for ProcessRunner {
+    fn spawn(&self, invocation: &CommandInvocation) -> io::Result<Child> {
+        let mut cmd = Command::new(&invocation.program);
+        cmd.args(&invocation.args);
+        if let Some(current_dir) = &invocation.current_dir {
+            let _ = cmd.current_dir(current_dir);
+        }
+        for (name, value) in &invocation.env {
+            let _ = cmd.env(name, value);
+        }
+        let _ = cmd.stdout(Stdio::piped());
+        let _ = cmd.stderr(Stdio::piped());
+        // Leads its own process group, so killing the group stops everything the command started
+        #[cfg(target_family = "unix")]
+        let _ = cmd.process_group(0);
+        cmd.spawn()
+    }
+}
+
+/// Forcefully terminates the process along with the processes it started.
+/// On Unix the process group it leads is killed, falling back to the process alone
+/// for commands spawned by runners which don't give them a group of their own
+#[cfg(target_family = "unix")]
+pub async fn kill_process_tree(pid: u32) -> io::Result<()> {
+    let pid = libc::pid_t::try_from(pid).map_err(io::Error::other)?;
+    // A negative pid signals every process of the group
+    // SAFETY: `kill` only sends a signal and doesn't touch the memory of this process
+    if unsafe { libc::kill(-pid, libc::SIGKILL) } == 0 {
+        return Ok(());
+    }
+    // SAFETY: as above
+    if unsafe { libc::kill(pid, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Forcefully terminates the process along with the processes it started
+#[cfg(target_family = "windows")]
+pub async fn kill_process_tree(pid: u32) -> io::Result<()> {
+    let output = Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &pid.to_string()])
+        .output()
+        .await?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// Waits up to the timeout for the process to stop running, returning false if it still is.
+/// Processes which exited but weren't reaped by their parent yet count as stopped
+#[cfg(all(test, target_family = "unix"))]
+pub fn process_stops_within(pid: u32, timeout: std::time::Duration) -> bool {
+    let start = std::time::Instant::now();
+    loop {
+        let state = std::process::Command::new("ps")
+            .args(["-o", "stat=", "-p", &pid.to_string()])
+            .output()
+            .expect("Failed to run ps");
+        let state = String::from_utf8_lossy(&state.stdout);
+        if state.trim().is_empty() || state.trim_start().starts_with('Z') {
+            return true;
+        }
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+}
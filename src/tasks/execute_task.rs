@@ -1,76 +1,275 @@
-use compio::{io::compat::AsyncStream, process::Command, runtime::spawn};
-use futures::{AsyncBufReadExt, StreamExt, io::BufReader};
+use compio::{
+    io::compat::AsyncStream,
+    runtime::{JoinHandle, spawn},
+    time::{sleep, timeout},
+};
+use futures::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, StreamExt,
+    future::{Either, select},
+    io::BufReader,
+};
 use hashlink::LinkedHashMap;
 use saphyr::{Scalar, Yaml};
 use snafu::{ResultExt, Snafu};
-use std::{borrow::Cow, process::Stdio};
-use tracing::{debug, info};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    pin::pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{Instrument, debug, info, warn};
 
-use crate::tasks::task::print_from_task;
+use crate::{ext::BestEffortPathExt, file_dependencies::FingerprintStrategy};
 
-use super::{BaseTask, TaskError, TaskTrait};
+use super::{
+    BaseTask, CommandInvocation, OutputOptions, PtyChild, Sandbox, TaskContext, TaskError,
+    TaskInput, TaskLog, TaskOutput, TaskTrait, kill_process_tree,
+};
 
-#[derive(Debug, Clone)]
+/// Size of the buffer used to copy raw task output
+const RAW_CHUNK_SIZE: usize = 8 * 1024;
+/// Number of the last stderr lines kept to explain the failure of a task
+const STDERR_TAIL_LINES: usize = 20;
+/// Environment variable holding the id of the task running the command
+const TASK_ENV_VAR: &str = "TESSY_TASK";
+/// Environment variable holding the target of the run
+const TARGET_ENV_VAR: &str = "TESSY_TARGET";
+
+/// What a task runs: a command line, or a program and its arguments
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum TaskCommand {
+    /// Run through the shell, or split into words when `rawExec` is set
+    Shell(String),
+    /// Executed directly, each element being one argument, e.g. `[git, commit, -m, "a message"]`
+    Exec(Vec<String>),
+}
+
+impl std::fmt::Display for TaskCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskCommand::Shell(command) => write!(f, "{}", command),
+            TaskCommand::Exec(words) => write!(f, "{}", shell_words::join(words)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ExecuteTask {
     base_task: BaseTask,
-    command: String,
+    /// Commands run in order, stopping at the first which fails.
+    /// A task setting a single `command` holds just that one
+    commands: Vec<TaskCommand>,
+    /// Whether the command is split into words and executed directly, without a shell
+    raw_exec: bool,
+    /// Interpreter running the command instead of the os-specific shell, e.g. `bash` or `pwsh`
+    shell: Option<String>,
+    /// Whether the command runs attached to a pseudo-terminal, so tools color their output
+    pty: bool,
+    /// Directory the command runs in, relative to the root
+    working_dir: Option<PathBuf>,
+    /// Time after which the command is killed and the task fails
+    timeout: Option<Duration>,
+    /// How many times the command is run again after exiting unsuccessfully
+    retries: u32,
+    /// Pause between the attempts of a failing command
+    retry_delay: Option<Duration>,
+    /// File the combined output of the command is written to, relative to the root
+    log_file: Option<PathBuf>,
+    /// Whether the output of the command is not printed. A failure still shows the stderr tail
+    quiet: bool,
 }
 
 impl TaskTrait for ExecuteTask {
     fn from_task_yaml(task_name: &str, task_data: &LinkedHashMap<Yaml, Yaml>) -> Option<Self> {
         debug!("Parsing task '{}' of type 'execute'", task_name);
 
-        let command = task_data
-            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("command"))))?
-            .as_str()?
-            .to_string();
+        let commands = match task_data.get(&Yaml::Value(Scalar::String(Cow::Borrowed("commands"))))
+        {
+            Some(commands) => commands
+                .as_sequence()?
+                .iter()
+                .map(|command| platform_command(task_name, command))
+                .collect::<Option<Vec<_>>>()?,
+            None => vec![platform_command(
+                task_name,
+                task_data.get(&Yaml::Value(Scalar::String(Cow::Borrowed("command"))))?,
+            )?],
+        };
+
+        let raw_exec = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("rawExec"))))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let shell = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("shell"))))
+            .and_then(|v| v.as_str())
+            .map(|shell| shell.to_string());
+
+        let pty = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("pty"))))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let working_dir = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("workingDir"))))
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from);
+
+        let timeout = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("timeout"))))
+            .and_then(|v| v.as_integer())
+            .and_then(|seconds| u64::try_from(seconds).ok())
+            .map(Duration::from_secs);
+
+        let retries = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("retries"))))
+            .and_then(|v| v.as_integer())
+            .and_then(|retries| u32::try_from(retries).ok())
+            .unwrap_or(0);
+
+        let retry_delay = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("retryDelay"))))
+            .and_then(|v| v.as_integer())
+            .and_then(|seconds| u64::try_from(seconds).ok())
+            .map(Duration::from_secs);
+
+        let log_file = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("logFile"))))
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from);
+
+        let quiet = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("quiet"))))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
         let base_task = BaseTask::from_task_yaml(task_name, task_data)?;
 
-        Some(ExecuteTask { base_task, command })
+        Some(ExecuteTask {
+            base_task,
+            commands,
+            raw_exec,
+            shell,
+            pty,
+            working_dir,
+            timeout,
+            retries,
+            retry_delay,
+            log_file,
+            quiet,
+        })
     }
 
-    async fn run(&self) -> Result<String, TaskError> {
-        let mut cmd = self.create_command();
+    async fn run(&self, context: &TaskContext) -> Result<String, TaskError> {
+        let mut invocations = self
+            .commands
+            .iter()
+            .map(|command| self.invocation(command, &context.command_wrapper, &context.target))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| TaskError::ExecutionError {
+                task_id: self.id(),
+                source: err,
+            })?;
 
-        let mut handle = cmd
-            .spawn()
-            .context(SpawnSnafu {
-                command: self.command.clone(),
-                task_name: self.id(),
-            })
-            .map_err(|err| TaskError::ExecutionError { source: err })?;
+        let mut current_dir = None;
+        let sandbox = if self.base_task.sandbox() {
+            let sandbox = Sandbox::new(&context.root, self.inputs()).map_err(|source| {
+                TaskError::SandboxError {
+                    task_id: self.id(),
+                    source,
+                }
+            })?;
+            current_dir = Some(sandbox.path().to_path_buf());
+            Some(sandbox)
+        } else {
+            None
+        };
 
-        // Handle stdout
-        if let Some(stdout) = handle.stdout.take() {
-            self.spawn_stdout_handler(stdout, self.id());
+        if let Some(working_dir) = &self.working_dir {
+            // Inside a sandbox, the directory is looked up in its copy of the root
+            let base = current_dir.as_deref().unwrap_or(&context.root);
+            let working_dir = base.join(working_dir);
+            if !working_dir.is_dir() {
+                return Err(TaskError::ExecutionError {
+                    task_id: self.id(),
+                    source: ExecuteTaskError::MissingWorkingDir {
+                        task_name: self.id(),
+                        path: working_dir,
+                    },
+                });
+            }
+            current_dir = Some(working_dir);
         }
-
-        // Handle stderr
-        if let Some(stderr) = handle.stderr.take() {
-            self.spawn_stderr_handler(stderr, self.id());
+        for invocation in &mut invocations {
+            invocation.current_dir = current_dir.clone();
         }
 
-        let status = handle
-            .wait()
-            .await
-            .context(WaitSnafu {
-                command: self.command.clone(),
-                task_name: self.id(),
-            })
-            .map_err(|err| TaskError::ExecutionError { source: err })?;
+        let log = self.create_log(&context.root);
+        // The timeout covers the whole task, so it is counted from here rather than per command
+        let started = Instant::now();
+        let attempts = self.retries + 1;
+        let mut attempt = 1;
+        let (failure, stderr_tail) = loop {
+            // Output of retries is prefixed with the attempt, so the attempts can be told apart
+            let output_id = if attempt == 1 {
+                self.id()
+            } else {
+                format!("{} (attempt {}/{})", self.id(), attempt, attempts)
+            };
+            let run = Attempt {
+                output_id,
+                stderr_tail: StderrTail::default(),
+                log: log.clone(),
+                started,
+            };
+            let failure = self.run_commands(&invocations, context, &run).await?;
+            let Some((_, exit_code)) = failure.filter(|_| attempt < attempts) else {
+                break (failure, run.stderr_tail);
+            };
 
-        if status.success() {
-            info!("Task '{}' completed successfully", self.id());
-            Ok(self.id())
-        } else {
+            warn!(
+                "Task '{}' exited with code {} on attempt {} of {}, retrying",
+                self.id(),
+                exit_code,
+                attempt,
+                attempts
+            );
+            if let Some(delay) = self.retry_delay {
+                let delayed = pin!(sleep(delay));
+                let cancelled = pin!(context.cancellation.cancelled());
+                if let Either::Right(_) = select(delayed, cancelled).await {
+                    return Err(TaskError::InterruptedError { task_id: self.id() });
+                }
+            }
+            attempt += 1;
+        };
+
+        if let Some((index, exit_code)) = failure {
             Err(TaskError::ExecutionError {
+                task_id: self.id(),
                 source: ExecuteTaskError::UnsuccessfulExecution {
-                    command: self.command.clone(),
+                    command: self.commands[index].to_string(),
                     task_name: self.id(),
-                    status: status.code().unwrap_or(-1),
+                    status: exit_code,
+                    index: self.sequence_index(index),
+                    stderr_tail: stderr_tail.text(),
                 },
             })
+        } else {
+            if let Some(sandbox) = sandbox {
+                sandbox
+                    .copy_outputs(&context.root, self.outputs())
+                    .map_err(|source| TaskError::SandboxError {
+                        task_id: self.id(),
+                        source,
+                    })?;
+            }
+            info!("Task '{}' completed successfully", self.id());
+            Ok(self.id())
         }
     }
 
@@ -82,86 +281,608 @@ impl TaskTrait for ExecuteTask {
         self.base_task.dependencies()
     }
 
-    fn inputs(&self) -> &Vec<String> {
+    fn before(&self) -> &Vec<String> {
+        self.base_task.before()
+    }
+
+    fn after(&self) -> &Vec<String> {
+        self.base_task.after()
+    }
+
+    fn inputs(&self) -> &Vec<TaskInput> {
         self.base_task.inputs()
     }
+
+    fn requires_clean_git(&self) -> bool {
+        self.base_task.requires_clean_git()
+    }
+
+    fn memoize(&self) -> bool {
+        self.base_task.memoize()
+    }
+
+    fn produces(&self) -> &Vec<String> {
+        self.base_task.produces()
+    }
+
+    fn consumes(&self) -> &Vec<String> {
+        self.base_task.consumes()
+    }
+
+    fn ignore_inputs(&self) -> &Vec<String> {
+        self.base_task.ignore_inputs()
+    }
+
+    fn outputs(&self) -> &Vec<String> {
+        self.base_task.outputs()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.base_task.description()
+    }
 }
 
 impl ExecuteTask {
-    /// Returns the full command as a tuple of the command string and its arguments.
-    /// This should be os-specific.
-    fn full_command(&self) -> (&'static str, Vec<&str>) {
+    pub fn new(base_task: BaseTask, command: String) -> Self {
+        Self {
+            base_task,
+            commands: vec![TaskCommand::Shell(command)],
+            raw_exec: false,
+            shell: None,
+            pty: false,
+            working_dir: None,
+            timeout: None,
+            retries: 0,
+            retry_delay: None,
+            log_file: None,
+            quiet: false,
+        }
+    }
+
+    /// The command as written in the task file, with the arguments of an exec form
+    /// quoted the way a shell would need them. A sequence of commands is joined with `&&`
+    pub fn command(&self) -> String {
+        self.commands
+            .iter()
+            .map(|command| command.to_string())
+            .collect::<Vec<_>>()
+            .join(" && ")
+    }
+
+    /// Environment variables set for the command
+    pub fn env(&self) -> &[(String, String)] {
+        self.base_task.env()
+    }
+
+    /// Fingerprints every input of the task with the strategy, overriding the declared ones
+    pub fn force_strategy(&mut self, strategy: FingerprintStrategy) {
+        self.base_task.force_strategy(strategy);
+    }
+
+    /// Replaces every dependency with the task id it resolves to
+    pub fn resolve_dependencies(&mut self, resolve: impl Fn(&str) -> String) {
+        self.base_task.resolve_dependencies(resolve);
+    }
+
+    /// Hashes the parts of the task deciding what is run
+    pub fn hash_command(&self, state: &mut impl Hasher) {
+        self.commands.hash(state);
+        self.raw_exec.hash(state);
+        self.shell.hash(state);
+        self.working_dir.hash(state);
+        self.base_task.no_wrapper().hash(state);
+        self.base_task.env().hash(state);
+    }
+
+    /// Returns the full command as a tuple of the program and its arguments.
+    /// A command given as a sequence is executed directly. Unless `rawExec` is set,
+    /// any other is run through the task's shell, or the os-specific one if it doesn't set one.
+    fn full_command(
+        &self,
+        command: &TaskCommand,
+    ) -> Result<(String, Vec<String>), ExecuteTaskError> {
+        let command = match command {
+            TaskCommand::Exec(words) => return self.program_and_args(words.clone()),
+            TaskCommand::Shell(command) => command,
+        };
+        if self.raw_exec {
+            return self.split_command(command);
+        }
+
+        if let Some(shell) = &self.shell {
+            if shell.trim().is_empty() {
+                return EmptyShellSnafu {
+                    task_name: self.id(),
+                }
+                .fail();
+            }
+            return Ok((
+                shell.clone(),
+                vec![shell_command_flag(shell).to_string(), command.clone()],
+            ));
+        }
+
         #[cfg(target_family = "windows")]
         {
-            let args = vec!["/C", &self.command];
-            ("cmd", args)
+            Ok(("cmd".to_string(), vec!["/C".to_string(), command.clone()]))
         }
         #[cfg(target_family = "unix")]
         {
-            let args = vec!["-c", &self.command];
-            ("sh", args)
+            Ok(("sh".to_string(), vec!["-c".to_string(), command.clone()]))
         }
     }
 
-    /// Creates and configures the command with proper stdio settings
-    fn create_command(&self) -> Command {
-        let (command, args) = self.full_command();
-        let mut cmd = Command::new(command);
-        cmd.args(args);
-        let _ = cmd.stdout(Stdio::piped());
-        let _ = cmd.stderr(Stdio::piped());
-        cmd
+    /// Splits the command into the program and its arguments, following shell quoting rules
+    fn split_command(&self, command: &str) -> Result<(String, Vec<String>), ExecuteTaskError> {
+        let words = shell_words::split(command).context(CommandParseSnafu {
+            command,
+            task_name: self.id(),
+        })?;
+        self.program_and_args(words)
     }
 
-    /// Spawns a task to handle stdout stream
-    fn spawn_stdout_handler(&self, stdout: compio::process::ChildStdout, task_id: String) {
-        let stream = AsyncStream::new(stdout);
-        let color = self.color();
-        //TODO - return the handle to the spawned task and ensure proper shutdown
-        spawn(async move {
-            let reader = BufReader::new(stream);
-            let mut lines = reader.lines();
-
-            while let Some(line_result) = lines.next().await {
-                match line_result {
-                    Ok(line) => {
-                        if !line.trim().is_empty() {
-                            print_from_task(&task_id, color, line.trim());
-                        }
-                    }
-                    Err(e) => {
-                        debug!("Error reading stdout for task '{}': {}", task_id, e);
-                    }
+    /// Separates the program from its arguments, failing if there are no words
+    fn program_and_args(
+        &self,
+        mut words: Vec<String>,
+    ) -> Result<(String, Vec<String>), ExecuteTaskError> {
+        if words.is_empty() {
+            return EmptyCommandSnafu {
+                task_name: self.id(),
+            }
+            .fail();
+        }
+        let program = words.remove(0);
+        Ok((program, words))
+    }
+
+    /// Creates the invocation of the command, which the runner of the context spawns.
+    /// Unless the task sets `noWrapper`, the command becomes the last arguments of the wrapper
+    fn invocation(
+        &self,
+        command: &TaskCommand,
+        wrapper: &[String],
+        target: &str,
+    ) -> Result<CommandInvocation, ExecuteTaskError> {
+        let (program, args) = self.full_command(command)?;
+        let (program, args) = match wrapper.split_first() {
+            Some((wrapper_program, wrapper_args)) if !self.base_task.no_wrapper() => {
+                let args = wrapper_args
+                    .iter()
+                    .cloned()
+                    .chain(std::iter::once(program))
+                    .chain(args)
+                    .collect();
+                (wrapper_program.clone(), args)
+            }
+            _ => (program, args),
+        };
+        Ok(CommandInvocation {
+            program,
+            args,
+            current_dir: None,
+            env: self.command_env(target),
+        })
+    }
+
+    /// Environment of the commands: `TESSY_TASK` and `TESSY_TARGET` identifying the run,
+    /// followed by the variables of the task, which take precedence over them
+    fn command_env(&self, target: &str) -> Vec<(String, String)> {
+        let user_env = self.base_task.env();
+        [
+            (TASK_ENV_VAR, self.id()),
+            (TARGET_ENV_VAR, target.to_string()),
+        ]
+        .into_iter()
+        .filter(|(name, _)| !user_env.iter().any(|(user_name, _)| user_name == name))
+        .map(|(name, value)| (name.to_string(), value))
+        .chain(user_env.iter().cloned())
+        .collect()
+    }
+
+    /// Runs the commands in order, stopping at the first exiting unsuccessfully.
+    /// Returns the position of that command along with its exit code
+    async fn run_commands(
+        &self,
+        invocations: &[CommandInvocation],
+        context: &TaskContext,
+        run: &Attempt,
+    ) -> Result<Option<(usize, i32)>, TaskError> {
+        for (index, invocation) in invocations.iter().enumerate() {
+            match self.run_attempt(invocation, context, run).await? {
+                Ok(0) => {}
+                Ok(exit_code) => return Ok(Some((index, exit_code))),
+                Err(reason) => return Err(self.stopped_error(reason, index)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs the command once, in a pseudo-terminal if the task asks for one,
+    /// returning its exit code, or why it was stopped before it exited
+    async fn run_attempt(
+        &self,
+        invocation: &CommandInvocation,
+        context: &TaskContext,
+        run: &Attempt,
+    ) -> Result<Result<i32, StopReason>, TaskError> {
+        match self.pty.then(|| PtyChild::spawn(invocation)) {
+            Some(Ok((child, output))) => self.wait_in_pty(child, output, context, run).await,
+            Some(Err(e)) => {
+                warn!(
+                    "Failed to open a pseudo-terminal for task '{}', running it with piped output: {}",
+                    self.id(),
+                    e
+                );
+                self.wait_piped(invocation, context, run).await
+            }
+            None => self.wait_piped(invocation, context, run).await,
+        }
+    }
+
+    /// Spawns the invocation through the runner of the context with piped output,
+    /// returning its exit code, or why it was stopped before it exited
+    async fn wait_piped(
+        &self,
+        invocation: &CommandInvocation,
+        context: &TaskContext,
+        run: &Attempt,
+    ) -> Result<Result<i32, StopReason>, TaskError> {
+        let mut handle = context
+            .runner
+            .spawn(invocation)
+            .context(SpawnSnafu {
+                command: self.command(),
+                task_name: self.id(),
+            })
+            .map_err(|err| TaskError::ExecutionError {
+                task_id: self.id(),
+                source: err,
+            })?;
+
+        let mut output_handlers = Vec::with_capacity(2);
+        if let Some(stdout) = handle.stdout.take() {
+            output_handlers.push(self.spawn_stdout_handler(
+                stdout,
+                run.output_id.clone(),
+                &context.output,
+                run.log.clone(),
+            ));
+        }
+        if let Some(stderr) = handle.stderr.take() {
+            output_handlers.push(self.spawn_stderr_handler(
+                stderr,
+                run.output_id.clone(),
+                &context.output,
+                run.stderr_tail.clone(),
+                run.log.clone(),
+            ));
+        }
+
+        let pid = handle.id();
+        let wait = pin!(handle.wait());
+        let stop = pin!(self.stop_reason(context, run.started));
+        let status = match select(wait, stop).await {
+            Either::Left((status, _)) => status,
+            Either::Right((reason, wait)) => {
+                info!("Task '{}' {}, killing its process", self.id(), reason);
+                if let Err(e) = kill_process_tree(pid).await {
+                    warn!("Failed to kill the process of task '{}': {}", self.id(), e);
                 }
+                // Reap the killed child, so it doesn't linger as a zombie. With its whole
+                // process group gone, the pipes are closed and the output handlers finish
+                let _ = wait.await;
+                self.finish_output(output_handlers).await;
+                return Ok(Err(reason));
             }
+        }
+        .context(WaitSnafu {
+            command: self.command(),
+            task_name: self.id(),
         })
-        .detach();
+        .map_err(|err| TaskError::ExecutionError {
+            task_id: self.id(),
+            source: err,
+        })?;
+        self.finish_output(output_handlers).await;
+
+        Ok(Ok(if status.success() {
+            0
+        } else {
+            status.code().unwrap_or(-1)
+        }))
+    }
+
+    /// Waits for the command running in a pseudo-terminal, returning its exit code, or why
+    /// it was stopped before it exited. The terminal merges stdout and stderr, so all of its
+    /// lines count towards the stderr tail
+    async fn wait_in_pty(
+        &self,
+        mut child: PtyChild,
+        output: impl AsyncRead + Unpin + 'static,
+        context: &TaskContext,
+        run: &Attempt,
+    ) -> Result<Result<i32, StopReason>, TaskError> {
+        let options = &self.output_options(&context.output);
+        let task_output = TaskOutput::new(
+            run.output_id.clone(),
+            self.color(options.color_seed),
+            options,
+        )
+        .with_log(run.log.clone());
+        let output_handler = if options.raw {
+            spawn(forward_chunks(output, task_output, "pty").in_current_span())
+        } else {
+            spawn(
+                forward_lines(output, task_output, "pty", Some(run.stderr_tail.clone()))
+                    .in_current_span(),
+            )
+        };
+
+        // Scoped, so the child is no longer borrowed by the wait when it has to be killed
+        let finished = {
+            let wait = pin!(child.wait());
+            let stop = pin!(self.stop_reason(context, run.started));
+            match select(wait, stop).await {
+                Either::Left((exit_code, _)) => Ok(exit_code),
+                Either::Right((reason, _)) => Err(reason),
+            }
+        };
+        let exit_code = match finished {
+            Ok(exit_code) => exit_code,
+            Err(reason) => {
+                info!("Task '{}' {}, killing its process", self.id(), reason);
+                if let Err(e) = child.kill().await {
+                    warn!("Failed to kill the process of task '{}': {}", self.id(), e);
+                }
+                let _ = child.wait().await;
+                self.finish_output(vec![output_handler]).await;
+                return Ok(Err(reason));
+            }
+        };
+        let exit_code = exit_code
+            .context(WaitSnafu {
+                command: self.command(),
+                task_name: self.id(),
+            })
+            .map_err(|err| TaskError::ExecutionError {
+                task_id: self.id(),
+                source: err,
+            })?;
+        // The terminal is closed once the command exits, so its output reaches its end
+        self.finish_output(vec![output_handler]).await;
+
+        Ok(Ok(i32::try_from(exit_code).unwrap_or(-1)))
+    }
+
+    /// Resolves once the command has to be stopped early, because the run is being torn down
+    /// or the task's timeout, counted from when it started, has passed. Without a timeout,
+    /// only the former stops it
+    async fn stop_reason(&self, context: &TaskContext, started: Instant) -> StopReason {
+        let cancelled = pin!(context.cancellation.cancelled());
+        let Some(limit) = self.timeout else {
+            cancelled.await;
+            return StopReason::Interrupted;
+        };
+
+        let timed_out = pin!(sleep(limit.saturating_sub(started.elapsed())));
+        match select(cancelled, timed_out).await {
+            Either::Left(_) => StopReason::Interrupted,
+            Either::Right(_) => StopReason::TimedOut(limit),
+        }
+    }
+
+    /// Error of the command at the given position, stopped before it exited
+    fn stopped_error(&self, reason: StopReason, index: usize) -> TaskError {
+        match reason {
+            StopReason::Interrupted => TaskError::InterruptedError { task_id: self.id() },
+            StopReason::TimedOut(limit) => TaskError::ExecutionError {
+                task_id: self.id(),
+                source: ExecuteTaskError::Timeout {
+                    command: self.commands[index].to_string(),
+                    task_name: self.id(),
+                    seconds: limit.as_secs(),
+                    index: self.sequence_index(index),
+                },
+            },
+        }
     }
 
-    /// Spawns a task to handle stderr stream
-    fn spawn_stderr_handler(&self, stderr: compio::process::ChildStderr, task_id: String) {
+    /// Position of a command to point out in errors. Only a sequence of commands needs it
+    fn sequence_index(&self, index: usize) -> Option<usize> {
+        (self.commands.len() > 1).then_some(index)
+    }
+
+    /// Creates the log file of the task below the root, if it asks for one.
+    /// A log which can't be created is reported, and the output is only printed
+    fn create_log(&self, root: &Path) -> Option<TaskLog> {
+        let path = root.join(self.log_file.as_ref()?);
+        match TaskLog::create(&path) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                warn!(
+                    "Can't create log file '{}' of task '{}', only printing its output: {}",
+                    path.best_effort_path_display(),
+                    self.id(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Output options of the run, with the task's own `maxOutputLines` taking precedence.
+    /// A quiet task stays quiet, whatever the run asks for
+    fn output_options(&self, options: &OutputOptions) -> OutputOptions {
+        OutputOptions {
+            max_lines: self.base_task.max_output_lines().or(options.max_lines),
+            quiet: self.quiet || options.quiet,
+            ..options.clone()
+        }
+    }
+
+    /// Waits until the output handlers have forwarded everything the command wrote,
+    /// so no line of a finished task is lost or printed after it reported done
+    async fn finish_output(&self, output_handlers: Vec<JoinHandle<()>>) {
+        for output_handler in output_handlers {
+            if output_handler.await.is_err() {
+                warn!("Forwarding the output of task '{}' panicked", self.id());
+            }
+        }
+    }
+
+    /// Spawns a task to handle stdout stream, returning its handle
+    fn spawn_stdout_handler(
+        &self,
+        stdout: compio::process::ChildStdout,
+        task_id: String,
+        options: &OutputOptions,
+        log: Option<TaskLog>,
+    ) -> JoinHandle<()> {
+        let stream = AsyncStream::new(stdout);
+        let options = &self.output_options(options);
+        let output =
+            TaskOutput::new(task_id, self.color(options.color_seed), options).with_log(log);
+        if options.raw {
+            spawn(forward_chunks(stream, output, "stdout").in_current_span())
+        } else {
+            spawn(forward_lines(stream, output, "stdout", None).in_current_span())
+        }
+    }
+
+    /// Spawns a task to handle stderr stream, returning its handle
+    fn spawn_stderr_handler(
+        &self,
+        stderr: compio::process::ChildStderr,
+        task_id: String,
+        options: &OutputOptions,
+        tail: StderrTail,
+        log: Option<TaskLog>,
+    ) -> JoinHandle<()> {
         let stream = AsyncStream::new(stderr);
-        let color = self.color();
-        //TODO - return the handle to the spawned task and ensure proper shutdown
-        spawn(async move {
-            let reader = BufReader::new(stream);
-            let mut lines = reader.lines();
-
-            while let Some(line_result) = lines.next().await {
-                match line_result {
-                    Ok(line) => {
-                        if !line.trim().is_empty() {
-                            print_from_task(&task_id, color, line.trim());
-                        }
-                    }
-                    Err(e) => {
-                        debug!("Error reading stderr for task '{}': {}", task_id, e);
+        let options = &self.output_options(options);
+        let output =
+            TaskOutput::new(task_id, self.color(options.color_seed), options).with_log(log);
+        if options.raw {
+            spawn(forward_chunks(stream, output, "stderr").in_current_span())
+        } else {
+            spawn(forward_lines(stream, output, "stderr", Some(tail)).in_current_span())
+        }
+    }
+}
+
+/// State shared by the commands of one attempt at running the task
+struct Attempt {
+    /// Id prefixing the output lines of the attempt
+    output_id: String,
+    stderr_tail: StderrTail,
+    log: Option<TaskLog>,
+    /// When the task started, which its timeout is counted from
+    started: Instant,
+}
+
+/// Why a command was killed before it exited on its own
+#[derive(Debug, Clone, Copy)]
+enum StopReason {
+    Interrupted,
+    TimedOut(Duration),
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Interrupted => write!(f, "was interrupted"),
+            StopReason::TimedOut(limit) => write!(f, "timed out after {:?}", limit),
+        }
+    }
+}
+
+/// Last lines of a task's stderr, shared with the handler reading the stream
+#[derive(Debug, Clone, Default)]
+struct StderrTail(Arc<Mutex<VecDeque<String>>>);
+
+impl StderrTail {
+    fn push(&self, line: &str) {
+        let mut lines = self.lock();
+        if lines.len() == STDERR_TAIL_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_string());
+    }
+
+    fn text(&self) -> String {
+        self.lock().iter().cloned().collect::<Vec<_>>().join("\n")
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<String>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Reads the stream line by line and prints the non-empty lines through the task output.
+/// Pending batched lines are written out once their deadline passes, even if the stream is idle
+async fn forward_lines(
+    stream: impl AsyncRead + Unpin,
+    mut output: TaskOutput,
+    stream_name: &str,
+    tail: Option<StderrTail>,
+) {
+    let reader = BufReader::new(stream);
+    let mut lines = reader.lines();
+
+    loop {
+        let next_line = match output.flush_deadline() {
+            Some(deadline) => match timeout(deadline, lines.next()).await {
+                Ok(next_line) => next_line,
+                Err(_) => {
+                    output.flush();
+                    continue;
+                }
+            },
+            None => lines.next().await,
+        };
+
+        match next_line {
+            Some(Ok(line)) => {
+                if !line.trim().is_empty() {
+                    if let Some(tail) = &tail {
+                        tail.push(line.trim());
                     }
+                    output.push_line(line.trim());
                 }
             }
-        })
-        .detach();
+            Some(Err(e)) => {
+                debug!("Error reading {} of a task: {}", stream_name, e);
+            }
+            None => break,
+        }
+    }
+
+    output.finish();
+}
+
+/// Copies the stream in chunks through the task output, without splitting it into lines first
+async fn forward_chunks(
+    mut stream: impl AsyncRead + Unpin,
+    mut output: TaskOutput,
+    stream_name: &str,
+) {
+    let mut buffer = vec![0; RAW_CHUNK_SIZE];
+
+    loop {
+        match stream.read(&mut buffer).await {
+            Ok(0) => break,
+            Ok(read) => output.push_chunk(&buffer[..read]),
+            Err(e) => {
+                debug!("Error reading {} of a task: {}", stream_name, e);
+                break;
+            }
+        }
     }
+
+    output.flush();
 }
 
 #[derive(Debug, Snafu)]
@@ -179,8 +900,11 @@ pub enum ExecuteTaskError {
         source: std::io::Error,
     },
     #[snafu(display(
-        "Command '{}' for task '{}' failed with exit code {}",
+        "Command '{}'{} for task '{}' failed with exit code {}",
         command,
+        index
+            .map(|index| format!(" (command {} of the sequence)", index + 1))
+            .unwrap_or_default(),
         task_name,
         status
     ))]
@@ -188,5 +912,643 @@ pub enum ExecuteTaskError {
         command: String,
         task_name: String,
         status: i32,
+        /// Position of the failing command, counted from 0, when the task runs several
+        index: Option<usize>,
+        /// Last lines the command wrote to stderr
+        stderr_tail: String,
+    },
+    #[snafu(display(
+        "Command '{}'{} for task '{}' timed out after {} seconds",
+        command,
+        index
+            .map(|index| format!(" (command {} of the sequence)", index + 1))
+            .unwrap_or_default(),
+        task_name,
+        seconds
+    ))]
+    Timeout {
+        command: String,
+        task_name: String,
+        seconds: u64,
+        /// Position of the timed out command, counted from 0, when the task runs several
+        index: Option<usize>,
+    },
+    #[snafu(display("Failed to split command '{}' for task '{}'", command, task_name))]
+    CommandParseError {
+        command: String,
+        task_name: String,
+        source: shell_words::ParseError,
     },
+    #[snafu(display("Command for task '{}' is empty", task_name))]
+    EmptyCommand { task_name: String },
+    #[snafu(display("Shell for task '{}' is empty", task_name))]
+    EmptyShell { task_name: String },
+    #[snafu(display(
+        "Working directory '{}' of task '{}' does not exist",
+        path.display(),
+        task_name
+    ))]
+    MissingWorkingDir { task_name: String, path: PathBuf },
+}
+
+/// Platform keys of a command mapping, most specific first. A `default` entry
+/// applies on platforms without their own
+const PLATFORM_KEYS: &[&str] = if cfg!(target_os = "macos") {
+    &["macos", "unix", "default"]
+} else if cfg!(target_family = "windows") {
+    &["windows", "default"]
+} else if cfg!(target_family = "unix") {
+    &["unix", "default"]
+} else {
+    &["default"]
+};
+
+/// Selects the command of the current platform. The command is either used on every
+/// platform, or a mapping of `windows`, `unix`, `macos` and `default` to commands
+fn platform_command(task_name: &str, command: &Yaml) -> Option<TaskCommand> {
+    let Some(commands) = command.as_mapping() else {
+        return task_command(task_name, command);
+    };
+
+    let command = PLATFORM_KEYS
+        .iter()
+        .find_map(|key| commands.get(&Yaml::Value(Scalar::String(Cow::Borrowed(key)))));
+    if command.is_none() {
+        warn!(
+            "Task '{}' has no command for this platform, skipping it",
+            task_name
+        );
+    }
+    task_command(task_name, command?)
+}
+
+/// Reads a command line, or a sequence of the program and its arguments
+fn task_command(task_name: &str, command: &Yaml) -> Option<TaskCommand> {
+    if let Some(command) = command.as_str() {
+        return Some(TaskCommand::Shell(command.to_string()));
+    }
+
+    let words = command
+        .as_sequence()?
+        .iter()
+        .map(|word| match word {
+            Yaml::Value(Scalar::String(text)) => Some(text.to_string()),
+            Yaml::Value(Scalar::Integer(number)) => Some(number.to_string()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    if words.is_none() {
+        warn!(
+            "Task '{}' has a command argument which isn't a string, skipping it",
+            task_name
+        );
+    }
+    words.map(TaskCommand::Exec)
+}
+
+/// Flag making the shell run the command which follows it: `/C` for cmd-like shells,
+/// `-c` for POSIX shells and the others accepting it, like PowerShell
+fn shell_command_flag(shell: &str) -> &'static str {
+    let program = std::path::Path::new(shell)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase());
+    if program.as_deref() == Some("cmd") {
+        "/C"
+    } else {
+        "-c"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_family = "unix")]
+    use crate::tasks::process_stops_within;
+    use crate::tasks::{CommandRunner, OutputCapture};
+    use compio::process::Child;
+    use rstest::rstest;
+    use saphyr::LoadableYamlNode;
+
+    fn parse_task(yaml: &str) -> ExecuteTask {
+        let task_data = Yaml::load_from_str(yaml).unwrap();
+        ExecuteTask::from_task_yaml("task", task_data[0].as_mapping().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_scalar_command_is_used_on_every_platform() {
+        let task = parse_task("command: make all");
+
+        assert_eq!(task.command(), "make all");
+    }
+
+    #[test]
+    fn test_command_of_the_current_platform_is_selected() {
+        let task = parse_task(
+            "command:\n  windows: build.bat\n  unix: ./build.sh\n  macos: ./build-mac.sh",
+        );
+
+        let expected = if cfg!(target_os = "macos") {
+            "./build-mac.sh"
+        } else if cfg!(target_family = "windows") {
+            "build.bat"
+        } else {
+            "./build.sh"
+        };
+        assert_eq!(task.command(), expected);
+    }
+
+    #[test]
+    fn test_default_command_applies_to_platforms_without_their_own() {
+        let task = parse_task("command:\n  plan9: mk\n  default: make");
+
+        assert_eq!(task.command(), "make");
+    }
+
+    #[test]
+    fn test_task_without_command_for_the_current_platform_is_skipped() {
+        let task_data = Yaml::load_from_str("command:\n  plan9: mk").unwrap();
+
+        assert!(ExecuteTask::from_task_yaml("task", task_data[0].as_mapping().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_command_runs_through_shell_by_default() {
+        let task = parse_task("command: echo 'hello world'");
+
+        let (program, args) = task.full_command(&task.commands[0]).unwrap();
+
+        assert_ne!(program, "echo");
+        assert_eq!(args.last().unwrap(), "echo 'hello world'");
+    }
+
+    #[rstest]
+    #[case("bash", "-c")]
+    #[case("/usr/bin/zsh", "-c")]
+    #[case("pwsh", "-c")]
+    #[case("cmd.exe", "/C")]
+    fn test_command_runs_through_the_task_shell(#[case] shell: &str, #[case] flag: &str) {
+        let task = parse_task(&format!("command: echo $0\nshell: {}", shell));
+
+        let invocation = task.invocation(&task.commands[0], &[], "").unwrap();
+
+        assert_eq!(invocation.program, shell);
+        assert_eq!(invocation.args, vec![flag, "echo $0"]);
+    }
+
+    #[test]
+    fn test_empty_shell_is_rejected() {
+        let task = parse_task("command: echo\nshell: ' '");
+
+        assert!(matches!(
+            task.full_command(&task.commands[0]),
+            Err(ExecuteTaskError::EmptyShell { .. })
+        ));
+    }
+
+    #[test]
+    fn test_raw_exec_splits_command_into_words() {
+        let task = parse_task("command: echo 'hello world' \"a b\" c\nrawExec: true");
+
+        let (program, args) = task.full_command(&task.commands[0]).unwrap();
+
+        assert_eq!(program, "echo");
+        assert_eq!(args, vec!["hello world", "a b", "c"]);
+    }
+
+    #[test]
+    fn test_raw_exec_rejects_unbalanced_quotes() {
+        let task = parse_task("command: echo 'unterminated\nrawExec: true");
+
+        assert!(matches!(
+            task.full_command(&task.commands[0]),
+            Err(ExecuteTaskError::CommandParseError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_raw_exec_rejects_empty_command() {
+        let task = parse_task("command: '  '\nrawExec: true");
+
+        assert!(matches!(
+            task.full_command(&task.commands[0]),
+            Err(ExecuteTaskError::EmptyCommand { .. })
+        ));
+    }
+
+    #[test]
+    fn test_command_sequence_is_executed_without_shell() {
+        let task = parse_task("command: [git, commit, -m, 'hello world']\nshell: bash");
+
+        let (program, args) = task.full_command(&task.commands[0]).unwrap();
+
+        assert_eq!(program, "git");
+        assert_eq!(args, vec!["commit", "-m", "hello world"]);
+        assert_eq!(task.command(), "git commit -m 'hello world'");
+    }
+
+    #[test]
+    fn test_empty_command_sequence_is_rejected() {
+        let task = parse_task("command: []");
+
+        assert!(matches!(
+            task.full_command(&task.commands[0]),
+            Err(ExecuteTaskError::EmptyCommand { .. })
+        ));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_command_sequence_preserves_arguments_with_spaces() {
+        // Split into words, `test` would get five arguments and fail
+        let task = parse_task("command: [test, 'a b', '=', 'a b']");
+
+        assert!(task.run(&TaskContext::default()).await.is_ok());
+    }
+
+    /// Records the invocations instead of spawning them
+    #[derive(Debug, Default)]
+    struct RecordingRunner {
+        invocations: Mutex<Vec<CommandInvocation>>,
+    }
+
+    impl CommandRunner for RecordingRunner {
+        fn spawn(&self, invocation: &CommandInvocation) -> std::io::Result<Child> {
+            self.invocations.lock().unwrap().push(invocation.clone());
+            Err(std::io::Error::other("not spawned"))
+        }
+    }
+
+    #[compio::test]
+    async fn test_run_spawns_through_the_context_runner() {
+        let task = parse_task("command: make all\nrawExec: true");
+        let runner = Arc::new(RecordingRunner::default());
+        let context = TaskContext {
+            runner: runner.clone(),
+            ..TaskContext::default()
+        };
+
+        let result = task.run(&context).await;
+
+        assert!(matches!(
+            result,
+            Err(TaskError::ExecutionError {
+                source: ExecuteTaskError::SpawnError { .. },
+                ..
+            })
+        ));
+        assert_eq!(
+            *runner.invocations.lock().unwrap(),
+            vec![CommandInvocation {
+                program: "make".to_string(),
+                args: vec!["all".to_string()],
+                current_dir: None,
+                env: vec![
+                    ("TESSY_TASK".to_string(), "task".to_string()),
+                    ("TESSY_TARGET".to_string(), String::new()),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invocation_is_wrapped_unless_the_task_opts_out() {
+        let wrapper = vec!["docker".to_string(), "run".to_string(), "--rm".to_string()];
+        let task = parse_task("command: make all\nrawExec: true");
+        let unwrapped_task = parse_task("command: make all\nrawExec: true\nnoWrapper: true");
+
+        let invocation = task.invocation(&task.commands[0], &wrapper, "").unwrap();
+        let unwrapped_invocation = unwrapped_task
+            .invocation(&unwrapped_task.commands[0], &wrapper, "")
+            .unwrap();
+
+        assert_eq!(invocation.program, "docker");
+        assert_eq!(invocation.args, vec!["run", "--rm", "make", "all"]);
+        assert_eq!(unwrapped_invocation.program, "make");
+        assert_eq!(unwrapped_invocation.args, vec!["all"]);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_pty_task_reports_the_exit_code_of_its_command() {
+        let task = parse_task("command: test -t 1 && exit 3\npty: true");
+
+        let result = task.run(&TaskContext::default()).await;
+
+        assert!(matches!(
+            result,
+            Err(TaskError::ExecutionError {
+                source: ExecuteTaskError::UnsuccessfulExecution { status: 3, .. },
+                ..
+            })
+        ));
+    }
+
+    #[compio::test]
+    async fn test_working_dir_is_resolved_against_the_root() {
+        let root = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(root.path().join("frontend")).unwrap();
+        let task = parse_task("command: make all\nrawExec: true\nworkingDir: frontend");
+        let runner = Arc::new(RecordingRunner::default());
+        let context = TaskContext {
+            runner: runner.clone(),
+            root: root.path().to_path_buf(),
+            ..TaskContext::default()
+        };
+
+        let _ = task.run(&context).await;
+
+        assert_eq!(
+            runner.invocations.lock().unwrap()[0].current_dir,
+            Some(root.path().join("frontend"))
+        );
+    }
+
+    #[compio::test]
+    async fn test_missing_working_dir_fails_before_spawning() {
+        let root = tempfile::TempDir::new().unwrap();
+        let task = parse_task("command: make all\nworkingDir: missing");
+        let runner = Arc::new(RecordingRunner::default());
+        let context = TaskContext {
+            runner: runner.clone(),
+            root: root.path().to_path_buf(),
+            ..TaskContext::default()
+        };
+
+        let result = task.run(&context).await;
+
+        assert!(matches!(
+            result,
+            Err(TaskError::ExecutionError {
+                source: ExecuteTaskError::MissingWorkingDir { .. },
+                ..
+            })
+        ));
+        assert!(runner.invocations.lock().unwrap().is_empty());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_timeout_kills_the_command() {
+        let task = parse_task("command: sleep 5\nrawExec: true\ntimeout: 1");
+
+        let start = std::time::Instant::now();
+        let result = task.run(&TaskContext::default()).await;
+
+        assert!(start.elapsed() < Duration::from_secs(4));
+        assert!(matches!(
+            result,
+            Err(TaskError::ExecutionError {
+                source: ExecuteTaskError::Timeout { seconds: 1, .. },
+                ..
+            })
+        ));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_timeout_covers_the_whole_sequence_of_commands() {
+        let task =
+            parse_task("commands: [sleep 0.7, sleep 0.7, echo done]\nrawExec: true\ntimeout: 1");
+
+        let result = task.run(&TaskContext::default()).await;
+
+        let Err(TaskError::ExecutionError { source, .. }) = result else {
+            panic!("expected the task to time out, got {:?}", result);
+        };
+        assert!(matches!(
+            &source,
+            ExecuteTaskError::Timeout {
+                seconds: 1,
+                index: Some(1),
+                command,
+                ..
+            } if command == "sleep 0.7"
+        ));
+        assert!(source.to_string().contains("command 2 of the sequence"));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_timeout_kills_the_children_of_the_command() {
+        let root = tempfile::TempDir::new().unwrap();
+        let pid_file = root.path().join("pid");
+        let task = parse_task(&format!(
+            "command: sleep 30 & echo $! > '{}'; wait; true\ntimeout: 1",
+            pid_file.display()
+        ));
+
+        let result = task.run(&TaskContext::default()).await;
+
+        assert!(matches!(
+            result,
+            Err(TaskError::ExecutionError {
+                source: ExecuteTaskError::Timeout { seconds: 1, .. },
+                ..
+            })
+        ));
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        assert!(process_stops_within(
+            pid.trim().parse().unwrap(),
+            Duration::from_secs(2)
+        ));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_command_finishing_before_timeout_succeeds() {
+        let task = parse_task("command: 'true'\nrawExec: true\ntimeout: 5");
+
+        assert!(task.run(&TaskContext::default()).await.is_ok());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[rstest]
+    #[case("logs/build.log", true)]
+    #[case("blocker/build.log", false)]
+    #[compio::test]
+    async fn test_log_file_receives_the_output(#[case] log_file: &str, #[case] logged: bool) {
+        let root = tempfile::TempDir::new().unwrap();
+        // A file where the log's directory should be, so the log can't be created
+        std::fs::write(root.path().join("blocker"), "").unwrap();
+        let task = parse_task(&format!(
+            "command: echo out; echo err >&2\nlogFile: {}",
+            log_file
+        ));
+        let context = TaskContext {
+            root: root.path().to_path_buf(),
+            ..TaskContext::default()
+        };
+
+        let result = task.run(&context).await;
+
+        assert!(result.is_ok());
+        let log = std::fs::read_to_string(root.path().join(log_file));
+        assert_eq!(log.is_ok(), logged);
+        if let Ok(log) = log {
+            assert!(log.contains("out\n"), "{}", log);
+            assert!(log.contains("err\n"), "{}", log);
+        }
+    }
+
+    #[test]
+    fn test_commands_are_parsed_in_order() {
+        let task = parse_task("commands: ['cargo build', [git, tag, v1]]");
+
+        assert_eq!(task.command(), "cargo build && git tag v1");
+        assert_eq!(task.full_command(&task.commands[1]).unwrap().0, "git");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_all_commands_run_in_order() {
+        let root = tempfile::TempDir::new().unwrap();
+        let task = parse_task("commands: [echo first, echo second]\nlogFile: task.log");
+        let context = TaskContext {
+            root: root.path().to_path_buf(),
+            ..TaskContext::default()
+        };
+
+        assert!(task.run(&context).await.is_ok());
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("task.log")).unwrap(),
+            "first\nsecond\n"
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_command_sees_the_task_and_target() {
+        let root = tempfile::TempDir::new().unwrap();
+        let task = parse_task("command: echo $TESSY_TASK $TESSY_TARGET\nlogFile: task.log");
+        let context = TaskContext {
+            root: root.path().to_path_buf(),
+            target: "build".to_string(),
+            ..TaskContext::default()
+        };
+
+        assert!(task.run(&context).await.is_ok());
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("task.log")).unwrap(),
+            "task build\n"
+        );
+    }
+
+    #[test]
+    fn test_task_env_takes_precedence_over_tessy_variables() {
+        let task = parse_task("command: make\nenv:\n  TESSY_TARGET: custom");
+
+        let invocation = task.invocation(&task.commands[0], &[], "build").unwrap();
+
+        assert_eq!(
+            invocation.env,
+            vec![
+                ("TESSY_TASK".to_string(), "task".to_string()),
+                ("TESSY_TARGET".to_string(), "custom".to_string()),
+            ]
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_commands_stop_at_the_first_failure() {
+        let root = tempfile::TempDir::new().unwrap();
+        let task = parse_task("commands: [echo first, exit 4, echo third]\nlogFile: task.log");
+        let context = TaskContext {
+            root: root.path().to_path_buf(),
+            ..TaskContext::default()
+        };
+
+        let result = task.run(&context).await;
+
+        let Err(TaskError::ExecutionError { source, .. }) = result else {
+            panic!("expected the task to fail, got {:?}", result);
+        };
+        assert!(matches!(
+            &source,
+            ExecuteTaskError::UnsuccessfulExecution {
+                status: 4,
+                index: Some(1),
+                command,
+                ..
+            } if command == "exit 4"
+        ));
+        assert!(source.to_string().contains("command 2 of the sequence"));
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("task.log")).unwrap(),
+            "first\n"
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_quiet_task_succeeds_without_printing() {
+        let run_captured = async |yaml: &str| {
+            let capture = OutputCapture::default();
+            let context = TaskContext {
+                output: OutputOptions {
+                    capture: Some(capture.clone()),
+                    ..OutputOptions::default()
+                },
+                ..TaskContext::default()
+            };
+            assert!(parse_task(yaml).run(&context).await.is_ok());
+            capture.contents()
+        };
+
+        assert_eq!(run_captured("command: echo hello\nquiet: true").await, "");
+        assert!(run_captured("command: echo hello").await.contains("hello"));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_quiet_task_still_reports_stderr_on_failure() {
+        let task = parse_task("command: echo broken >&2; exit 1\nquiet: true");
+
+        let result = task.run(&TaskContext::default()).await;
+
+        assert!(matches!(
+            result,
+            Err(TaskError::ExecutionError {
+                source: ExecuteTaskError::UnsuccessfulExecution { stderr_tail, .. },
+                ..
+            }) if stderr_tail == "broken"
+        ));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[compio::test]
+    async fn test_stderr_of_a_fast_failing_command_is_complete() {
+        let task = parse_task("command: seq 1 500 >&2; exit 3");
+
+        let result = task.run(&TaskContext::default()).await;
+
+        let Err(TaskError::ExecutionError {
+            source: ExecuteTaskError::UnsuccessfulExecution { stderr_tail, .. },
+            ..
+        }) = result
+        else {
+            panic!("expected the command to fail, got {:?}", result);
+        };
+        assert!(stderr_tail.ends_with("500"), "{}", stderr_tail);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[rstest]
+    #[case(0, false)]
+    #[case(1, true)]
+    #[compio::test]
+    async fn test_failing_command_is_retried(#[case] retries: u32, #[case] succeeds: bool) {
+        let root = tempfile::TempDir::new().unwrap();
+        // Fails on the first attempt only, leaving a marker for the next one
+        let marker = root.path().join("attempted");
+        let task = parse_task(&format!(
+            "command: test -f '{0}' || {{ touch '{0}'; exit 1; }}\nretries: {1}",
+            marker.display(),
+            retries
+        ));
+
+        let result = task.run(&TaskContext::default()).await;
+
+        assert_eq!(result.is_ok(), succeeds);
+    }
 }
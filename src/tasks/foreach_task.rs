@@ -0,0 +1,239 @@
+use futures::future::join_all;
+use hashlink::LinkedHashMap;
+use saphyr::{Scalar, Yaml};
+use std::{
+    borrow::Cow,
+    hash::{Hash, Hasher},
+};
+use tracing::{Instrument, debug, info, info_span};
+
+use super::{BaseTask, ExecuteTask, TaskContext, TaskError, TaskInput, TaskTrait};
+use crate::file_dependencies::FingerprintStrategy;
+
+/// Placeholder in the command template, which is replaced with the item
+const ITEM_PLACEHOLDER: &str = "{item}";
+
+/// Runs a command template once per item, in parallel, under a single task id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ForeachTask {
+    base_task: BaseTask,
+    items: Vec<String>,
+    command: String,
+}
+
+impl TaskTrait for ForeachTask {
+    fn from_task_yaml(task_name: &str, task_data: &LinkedHashMap<Yaml, Yaml>) -> Option<Self> {
+        debug!("Parsing task '{}' of type 'foreach'", task_name);
+
+        let command = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("command"))))?
+            .as_str()?
+            .to_string();
+
+        let items = task_data
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("items"))))?
+            .as_sequence()?
+            .iter()
+            .filter_map(|item| item.as_str().map(|s| s.to_string()))
+            .collect();
+
+        let base_task = BaseTask::from_task_yaml(task_name, task_data)?;
+
+        Some(ForeachTask {
+            base_task,
+            items,
+            command,
+        })
+    }
+
+    async fn run(&self, context: &TaskContext) -> Result<String, TaskError> {
+        let item_tasks = self.item_tasks();
+        let results = join_all(item_tasks.iter().zip(&self.items).map(|(task, item)| {
+            task.run(context)
+                .instrument(info_span!("item", item = %item))
+        }))
+        .await;
+
+        let mut failed_items = Vec::new();
+        let mut interrupted = false;
+        for (item, result) in self.items.iter().zip(results) {
+            match result {
+                Ok(_) => {}
+                Err(TaskError::InterruptedError { .. }) => interrupted = true,
+                Err(_) => failed_items.push(item.clone()),
+            }
+        }
+
+        if interrupted {
+            return Err(TaskError::InterruptedError { task_id: self.id() });
+        }
+        if !failed_items.is_empty() {
+            return Err(TaskError::ForeachItemsFailedError {
+                task_id: self.id(),
+                total: self.items.len(),
+                failed_items,
+            });
+        }
+
+        info!(
+            "All {} items of task '{}' completed successfully",
+            self.items.len(),
+            self.id()
+        );
+        Ok(self.id())
+    }
+
+    fn id(&self) -> String {
+        self.base_task.id()
+    }
+
+    fn dependencies(&self) -> &Vec<String> {
+        self.base_task.dependencies()
+    }
+
+    fn before(&self) -> &Vec<String> {
+        self.base_task.before()
+    }
+
+    fn after(&self) -> &Vec<String> {
+        self.base_task.after()
+    }
+
+    fn inputs(&self) -> &Vec<TaskInput> {
+        self.base_task.inputs()
+    }
+
+    fn requires_clean_git(&self) -> bool {
+        self.base_task.requires_clean_git()
+    }
+
+    fn memoize(&self) -> bool {
+        self.base_task.memoize()
+    }
+
+    fn produces(&self) -> &Vec<String> {
+        self.base_task.produces()
+    }
+
+    fn consumes(&self) -> &Vec<String> {
+        self.base_task.consumes()
+    }
+
+    fn ignore_inputs(&self) -> &Vec<String> {
+        self.base_task.ignore_inputs()
+    }
+
+    fn outputs(&self) -> &Vec<String> {
+        self.base_task.outputs()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.base_task.description()
+    }
+}
+
+impl ForeachTask {
+    /// The command template as written in the task file, run once per item
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Environment variables set for the command of every item
+    pub fn env(&self) -> &[(String, String)] {
+        self.base_task.env()
+    }
+
+    /// Fingerprints every input of the task with the strategy, overriding the declared ones
+    pub fn force_strategy(&mut self, strategy: FingerprintStrategy) {
+        self.base_task.force_strategy(strategy);
+    }
+
+    /// Replaces every dependency with the task id it resolves to
+    pub fn resolve_dependencies(&mut self, resolve: impl Fn(&str) -> String) {
+        self.base_task.resolve_dependencies(resolve);
+    }
+
+    /// Hashes the parts of the task deciding what is run
+    pub fn hash_command(&self, state: &mut impl Hasher) {
+        self.command.hash(state);
+        self.items.hash(state);
+        self.base_task.no_wrapper().hash(state);
+        self.base_task.env().hash(state);
+    }
+
+    /// Creates one subtask per item, with the item substituted into the command and an id of `id[item]`
+    fn item_tasks(&self) -> Vec<ExecuteTask> {
+        self.items
+            .iter()
+            .map(|item| {
+                let base_task = self.base_task.with_name(format!("{}[{}]", self.id(), item));
+                ExecuteTask::new(base_task, self.command.replace(ITEM_PLACEHOLDER, item))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saphyr::LoadableYamlNode;
+
+    fn foreach_task(yaml: &str) -> Option<ForeachTask> {
+        let documents = Yaml::load_from_str(yaml).unwrap();
+        ForeachTask::from_task_yaml("lint", documents[0].as_mapping().unwrap())
+    }
+
+    #[test]
+    fn test_foreach_task_creates_a_subtask_per_item() {
+        let task = foreach_task(
+            r#"
+type: foreach
+items: [frontend, backend]
+command: "cargo clippy -p {item}"
+"#,
+        )
+        .unwrap();
+
+        let item_tasks = task.item_tasks();
+
+        assert_eq!(
+            item_tasks,
+            vec![
+                ExecuteTask::new(
+                    task.base_task.with_name("lint[frontend]".to_string()),
+                    "cargo clippy -p frontend".to_string()
+                ),
+                ExecuteTask::new(
+                    task.base_task.with_name("lint[backend]".to_string()),
+                    "cargo clippy -p backend".to_string()
+                ),
+            ]
+        );
+        assert_eq!(item_tasks[0].id(), "lint[frontend]");
+    }
+
+    #[test]
+    fn test_foreach_task_requires_items_and_command() {
+        assert!(foreach_task("command: echo {item}").is_none());
+        assert!(foreach_task("items: [a]").is_none());
+    }
+
+    #[compio::test]
+    async fn test_foreach_task_reports_failed_items() {
+        let task = foreach_task(
+            r#"
+items: ["0", "3", "0"]
+command: "exit {item}"
+"#,
+        )
+        .unwrap();
+
+        let result = task.run(&TaskContext::default()).await;
+
+        assert!(matches!(
+            result,
+            Err(TaskError::ForeachItemsFailedError { total: 3, ref failed_items, .. })
+                if failed_items == &vec!["3".to_string()]
+        ));
+    }
+}
@@ -1,7 +1,29 @@
 mod base_task;
+mod clean_git;
+mod command_runner;
 mod execute_task;
+mod foreach_task;
+mod pty;
+mod sandbox;
+mod status_line;
 mod task;
+mod task_context;
+mod task_input;
+mod task_output;
 
 pub use base_task::BaseTask;
+pub use clean_git::ensure_clean_git;
+#[cfg(all(test, target_family = "unix"))]
+pub use command_runner::process_stops_within;
+pub use command_runner::{CommandInvocation, CommandRunner, ProcessRunner, kill_process_tree};
 pub use execute_task::{ExecuteTask, ExecuteTaskError};
+pub use foreach_task::ForeachTask;
+pub use pty::PtyChild;
+pub use sandbox::Sandbox;
+pub use status_line::StatusLine;
 pub use task::{Task, TaskError, TaskTrait};
+pub use task_context::TaskContext;
+pub use task_input::TaskInput;
+#[cfg(test)]
+pub use task_output::OutputCapture;
+pub use task_output::{OutputOptions, TaskLog, TaskOutput};
@@ -0,0 +1,115 @@
+use std::{io, io::Read, thread};
+
+use futures::{AsyncRead, StreamExt, TryStreamExt};
+use futures_channel::{mpsc, oneshot};
+use portable_pty::{ChildKiller, CommandBuilder, PtySize, native_pty_system};
+use tracing::debug;
+
+use super::{CommandInvocation, kill_process_tree};
+
+/// Size of the terminal the command sees. Tools mostly use it to wrap their output
+const PTY_SIZE: PtySize = PtySize {
+    rows: 24,
+    cols: 120,
+    pixel_width: 0,
+    pixel_height: 0,
+};
+/// Size of the buffer used to read the output of the terminal
+const PTY_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Command running attached to a pseudo-terminal, so tools which only color their output
+/// for terminals still do. Stdout and stderr are merged into the terminal's output.
+/// The pty crate is blocking, so reading the output and waiting run on their own threads
+pub struct PtyChild {
+    /// Id of the command, which leads a session of its own on Unix
+    pid: Option<u32>,
+    killer: Box<dyn ChildKiller + Send + Sync>,
+    exit_code: oneshot::Receiver<io::Result<u32>>,
+}
+
+impl PtyChild {
+    /// Spawns the invocation in a new pseudo-terminal, returning the child and its output
+    pub fn spawn(
+        invocation: &CommandInvocation,
+    ) -> io::Result<(Self, impl AsyncRead + Unpin + 'static)> {
+        let pair = native_pty_system()
+            .openpty(PTY_SIZE)
+            .map_err(io::Error::other)?;
+
+        let mut command = CommandBuilder::new(&invocation.program);
+        command.args(&invocation.args);
+        for (name, value) in &invocation.env {
+            command.env(name, value);
+        }
+        match &invocation.current_dir {
+            Some(current_dir) => command.cwd(current_dir),
+            None => command.cwd(std::env::current_dir()?),
+        }
+
+        let mut child = pair
+            .slave
+            .spawn_command(command)
+            .map_err(io::Error::other)?;
+        // The output reaches its end only once no handle to the terminal is left in this process
+        drop(pair.slave);
+        let reader = pair.master.try_clone_reader().map_err(io::Error::other)?;
+        let pid = child.process_id();
+        let killer = child.clone_killer();
+
+        let (output_sender, output_receiver) = mpsc::unbounded();
+        thread::spawn(move || read_terminal(reader, output_sender));
+
+        let (exit_sender, exit_code) = oneshot::channel();
+        thread::spawn(move || {
+            let result = child.wait().map(|status| status.exit_code());
+            // Closes the terminal once the command exits, even if it left children holding it
+            drop(pair.master);
+            let _ = exit_sender.send(result);
+        });
+
+        let output = output_receiver.map(Ok::<_, io::Error>).into_async_read();
+        Ok((
+            Self {
+                pid,
+                killer,
+                exit_code,
+            },
+            output,
+        ))
+    }
+
+    /// Waits for the command to exit, returning its exit code
+    pub async fn wait(&mut self) -> io::Result<u32> {
+        (&mut self.exit_code)
+            .await
+            .unwrap_or_else(|_| Err(io::Error::other("the pty waiting thread stopped")))
+    }
+
+    /// Forcefully terminates the command along with the processes it started
+    pub async fn kill(&mut self) -> io::Result<()> {
+        match self.pid {
+            Some(pid) => kill_process_tree(pid).await,
+            None => self.killer.kill(),
+        }
+    }
+}
+
+/// Copies the output of the terminal into the channel until it is closed
+fn read_terminal(mut reader: Box<dyn Read + Send>, sender: mpsc::UnboundedSender<Vec<u8>>) {
+    let mut buffer = vec![0; PTY_CHUNK_SIZE];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => {
+                if sender.unbounded_send(buffer[..read].to_vec()).is_err() {
+                    break;
+                }
+            }
+            // Reading a terminal whose command has exited fails instead of returning EOF on Linux
+            Err(e) => {
+                debug!("Stopped reading the output of a pty: {}", e);
+                break;
+            }
+        }
+    }
+}
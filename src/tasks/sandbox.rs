@@ -0,0 +1,153 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use tempfile::TempDir;
+use tracing::{debug, warn};
+
+use crate::ext::BestEffortPathExt;
+
+use super::TaskInput;
+
+/// Fresh temporary directory, in which a task runs with only its declared inputs present.
+/// The directory is removed once the sandbox is dropped
+pub struct Sandbox {
+    dir: TempDir,
+}
+
+impl Sandbox {
+    /// Creates the sandbox and places the inputs in it, at the same paths relative to the root
+    pub fn new(root: &Path, inputs: &[TaskInput]) -> io::Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("tessy-sandbox-")
+            .tempdir()?;
+        debug!(
+            "Created sandbox '{}'",
+            dir.path().best_effort_path_display()
+        );
+
+        for input in inputs {
+            let Some(relative_path) = relative_to_root(root, &input.path) else {
+                warn!(
+                    "Input '{}' is outside of the root, so it isn't available in the sandbox",
+                    input.path
+                );
+                continue;
+            };
+            let source = root.join(&relative_path);
+            if !source.exists() {
+                debug!("Input '{}' does not exist, not placing it", input.path);
+                continue;
+            }
+            link_or_copy(&source, &dir.path().join(relative_path))?;
+        }
+
+        Ok(Self { dir })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Copies the outputs the task created in the sandbox back to the root
+    pub fn copy_outputs(&self, root: &Path, outputs: &[String]) -> io::Result<()> {
+        for output in outputs {
+            let Some(relative_path) = relative_to_root(root, output) else {
+                warn!("Output '{}' is outside of the root, not copying it", output);
+                continue;
+            };
+            let produced = self.path().join(&relative_path);
+            if !produced.exists() {
+                warn!(
+                    "Declared output '{}' was not created in the sandbox",
+                    output
+                );
+                continue;
+            }
+            copy_recursively(&produced, &root.join(relative_path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path relative to the root, or `None` if it points outside of it
+fn relative_to_root(root: &Path, path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    if path.is_relative() {
+        return Some(path.to_path_buf());
+    }
+    path.strip_prefix(root).ok().map(Path::to_path_buf)
+}
+
+/// Symlinks the source into the sandbox where possible, and copies it otherwise
+fn link_or_copy(source: &Path, destination: &Path) -> io::Result<()> {
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(std::path::absolute(source)?, destination)
+    }
+    #[cfg(not(unix))]
+    {
+        copy_recursively(source, destination)
+    }
+}
+
+/// Copies a file, or a directory with all its contents, replacing files at the destination
+fn copy_recursively(source: &Path, destination: &Path) -> io::Result<()> {
+    if source.is_dir() {
+        std::fs::create_dir_all(destination)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(source, destination).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_contains_only_declared_inputs() {
+        let root = TempDir::new().unwrap();
+        std::fs::create_dir_all(root.path().join("src")).unwrap();
+        std::fs::write(root.path().join("src/main.c"), "int main() {}").unwrap();
+        std::fs::write(root.path().join("secret.txt"), "undeclared").unwrap();
+
+        let sandbox = Sandbox::new(root.path(), &[TaskInput::new("src")]).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(sandbox.path().join("src/main.c")).unwrap(),
+            "int main() {}"
+        );
+        assert!(!sandbox.path().join("secret.txt").exists());
+    }
+
+    #[test]
+    fn test_sandbox_copies_outputs_back() {
+        let root = TempDir::new().unwrap();
+        let sandbox = Sandbox::new(root.path(), &[]).unwrap();
+        std::fs::create_dir_all(sandbox.path().join("dist/assets")).unwrap();
+        std::fs::write(sandbox.path().join("dist/assets/app.js"), "built").unwrap();
+
+        sandbox
+            .copy_outputs(root.path(), &["dist".to_string(), "missing".to_string()])
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("dist/assets/app.js")).unwrap(),
+            "built"
+        );
+        assert!(!root.path().join("missing").exists());
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    io::{IsTerminal, StdoutLock, Write},
+    sync::{Mutex, MutexGuard},
+    time::Instant,
+};
+
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+/// Width used for the status line, when the terminal doesn't report its own
+const DEFAULT_WIDTH: usize = 80;
+/// Clears the current terminal line and moves the cursor to its beginning
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+static STATE: Mutex<StatusLineState> = Mutex::new(StatusLineState::new());
+
+/// Live line at the bottom of the terminal, listing the currently running tasks.
+///
+/// All task output has to go through [`StatusLine::write_output`], so the line can be
+/// cleared before the output is printed and redrawn below it afterwards.
+/// The line is only ever drawn when stdout is a terminal.
+pub struct StatusLine;
+
+impl StatusLine {
+    /// Enables the status line if stdout is a terminal. Returns whether it got enabled
+    pub fn enable() -> bool {
+        let enabled = std::io::stdout().is_terminal();
+        lock_state().enabled = enabled;
+        enabled
+    }
+
+    /// Clears the line and stops drawing it
+    pub fn disable() {
+        let mut state = lock_state();
+        let mut stdout = std::io::stdout().lock();
+        state.clear(&mut stdout);
+        let _ = stdout.flush();
+        state.enabled = false;
+        state.running.clear();
+    }
+
+    pub fn task_started(task_id: &str) {
+        let mut state = lock_state();
+        state.running.push((task_id.to_string(), Instant::now()));
+        state.redraw();
+    }
+
+    pub fn task_finished(task_id: &str) {
+        let mut state = lock_state();
+        state.running.retain(|(id, _)| id != task_id);
+        state.redraw();
+    }
+
+    /// Advances the spinner and updates the elapsed times.
+    /// Returns false once the status line is disabled
+    pub fn refresh() -> bool {
+        let mut state = lock_state();
+        state.frame = state.frame.wrapping_add(1);
+        state.redraw();
+        state.enabled
+    }
+
+    /// Writes the text to stdout above the status line
+    pub fn write_output(output: impl AsRef<[u8]>) -> std::io::Result<()> {
+        let mut state = lock_state();
+        let mut stdout = std::io::stdout().lock();
+        state.clear(&mut stdout);
+        stdout.write_all(output.as_ref())?;
+        state.draw(&mut stdout);
+        stdout.flush()
+    }
+}
+
+fn lock_state() -> MutexGuard<'static, StatusLineState> {
+    STATE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+struct StatusLineState {
+    enabled: bool,
+    running: Vec<(String, Instant)>,
+    frame: usize,
+    drawn: bool,
+}
+
+impl StatusLineState {
+    const fn new() -> Self {
+        Self {
+            enabled: false,
+            running: Vec::new(),
+            frame: 0,
+            drawn: false,
+        }
+    }
+
+    fn redraw(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let mut stdout = std::io::stdout().lock();
+        self.clear(&mut stdout);
+        self.draw(&mut stdout);
+        let _ = stdout.flush();
+    }
+
+    fn clear(&mut self, stdout: &mut StdoutLock) {
+        if self.drawn {
+            let _ = stdout.write_all(CLEAR_LINE.as_bytes());
+            self.drawn = false;
+        }
+    }
+
+    fn draw(&mut self, stdout: &mut StdoutLock) {
+        if !self.enabled || self.running.is_empty() {
+            return;
+        }
+        let _ = stdout.write_all(self.render(terminal_width()).as_bytes());
+        self.drawn = true;
+    }
+
+    fn render(&self, width: usize) -> String {
+        let spinner = SPINNER_FRAMES[self.frame % SPINNER_FRAMES.len()];
+        let tasks = self
+            .running
+            .iter()
+            .map(|(id, started)| format!("{} ({:.1}s)", id, started.elapsed().as_secs_f64()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // Never wrap, as only the last terminal line can be cleared
+        format!("{} Running: {}", spinner, tasks)
+            .chars()
+            .take(width.saturating_sub(1))
+            .collect()
+    }
+}
+
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with_tasks(tasks: &[&str]) -> StatusLineState {
+        let mut state = StatusLineState::new();
+        state.enabled = true;
+        for task in tasks {
+            state.running.push((task.to_string(), Instant::now()));
+        }
+        state
+    }
+
+    #[test]
+    fn test_render_lists_running_tasks() {
+        let state = state_with_tasks(&["build", "test"]);
+
+        let line = state.render(DEFAULT_WIDTH);
+
+        assert!(line.starts_with(SPINNER_FRAMES[0]));
+        assert!(line.contains("Running: build (0.0s), test (0.0s)"));
+    }
+
+    #[test]
+    fn test_render_never_exceeds_terminal_width() {
+        let state = state_with_tasks(&["a_very_long_task_name"; 10]);
+
+        let line = state.render(40);
+
+        assert_eq!(line.chars().count(), 39);
+    }
+
+    #[test]
+    fn test_spinner_advances_with_frame() {
+        let mut state = state_with_tasks(&["build"]);
+        state.frame = 3;
+
+        assert!(state.render(DEFAULT_WIDTH).starts_with(SPINNER_FRAMES[3]));
+    }
+}
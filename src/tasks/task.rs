@@ -1,22 +1,32 @@
 use std::hash::{DefaultHasher, Hash, Hasher};
 
-use colored::{Color, Colorize};
+use colored::Color;
 use hashlink::LinkedHashMap;
+use metrohash::MetroHash64;
 use saphyr::{Scalar, Yaml};
 use snafu::Snafu;
 
-use crate::tasks::{ExecuteTask, ExecuteTaskError};
+use crate::file_dependencies::FingerprintStrategy;
+use crate::tasks::{ExecuteTask, ExecuteTaskError, ForeachTask, TaskContext, TaskInput};
 
-pub fn print_from_task(id: impl AsRef<str>, color: Color, message: impl AsRef<str>) {
+/// Formats a line of task output, prefixed with the task id, colored unless no color is given
+pub fn format_task_line(
+    id: impl AsRef<str>,
+    color: Option<Color>,
+    message: impl AsRef<str>,
+) -> String {
     let task_info = format!("[{}]", id.as_ref());
 
-    match supports_color::on_cached(supports_color::Stream::Stdout) {
-        Some(support) if support.has_16m => {
-            println!("{}: {}", task_info.color(color), message.as_ref());
-        }
-        _ => {
-            println!("{}: {}", task_info, message.as_ref());
-        }
+    match color {
+        // Written out directly, as `colored` would drop the color once `NO_COLOR` is set,
+        // even with `--color always`
+        Some(color) => format!(
+            "\x1b[{}m{}\x1b[0m: {}",
+            color.to_fg_str(),
+            task_info,
+            message.as_ref()
+        ),
+        None => format!("{}: {}", task_info, message.as_ref()),
     }
 }
 
@@ -25,14 +35,49 @@ pub trait TaskTrait {
     where
         Self: Sized;
     // Runs the task and returns its id on success
-    async fn run(&self) -> Result<String, TaskError>;
+    async fn run(&self, context: &TaskContext) -> Result<String, TaskError>;
     fn id(&self) -> String;
     fn dependencies(&self) -> &Vec<String>;
-    fn inputs(&self) -> &Vec<String>;
-    fn color(&self) -> Color {
-        let mut hasher = DefaultHasher::new();
-        self.id().hash(&mut hasher);
-        let hash = hasher.finish();
+    /// Tasks run right before this one, as if it depended on them
+    fn before(&self) -> &Vec<String>;
+    /// Tasks run right after this one, as if they depended on it
+    fn after(&self) -> &Vec<String>;
+    fn inputs(&self) -> &Vec<TaskInput>;
+    /// Whether the git working tree has to be clean before the task is run
+    fn requires_clean_git(&self) -> bool;
+    /// Whether a successful run can be reused by any task running the same command on the same inputs
+    fn memoize(&self) -> bool;
+    /// Virtual artifacts, which are changed whenever the task runs
+    fn produces(&self) -> &Vec<String>;
+    /// Virtual artifacts, whose producers running makes the task out of date
+    fn consumes(&self) -> &Vec<String>;
+    /// Paths excluded from the fingerprinted inputs, along with everything below them
+    fn ignore_inputs(&self) -> &Vec<String>;
+    /// Paths the task creates, relative to the root. The task is out of date when they were
+    /// removed or changed since it last ran
+    fn outputs(&self) -> &Vec<String>;
+    /// Text explaining what the task is for, shown when listing the tasks
+    fn description(&self) -> Option<&str> {
+        None
+    }
+    /// Color of the task's output prefix, derived from its id.
+    /// A seed changes the assignment while keeping it reproducible, across Rust releases
+    /// and platforms as well, since the bytes are hashed with MetroHash
+    fn color(&self, seed: Option<u64>) -> Color {
+        let hash = match seed {
+            Some(seed) => {
+                let mut hasher = MetroHash64::new();
+                hasher.write(&seed.to_le_bytes());
+                hasher.write(self.id().as_bytes());
+                hasher.finish()
+            }
+            // The default colors, kept as they always were
+            None => {
+                let mut hasher = DefaultHasher::new();
+                self.id().hash(&mut hasher);
+                hasher.finish()
+            }
+        };
         let red = (hash & 0xFF) as u8;
         let green = ((hash >> 8) & 0xFF) as u8;
         let blue = ((hash >> 16) & 0xFF) as u8;
@@ -44,9 +89,12 @@ pub trait TaskTrait {
     }
 }
 
-#[derive(Debug, Clone)]
+/// A task, compared and hashed by its whole definition, so tasks sharing an id but
+/// differing otherwise are never considered equal
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Task {
     Execute(ExecuteTask),
+    Foreach(ForeachTask),
 }
 
 impl TaskTrait for Task {
@@ -58,6 +106,7 @@ impl TaskTrait for Task {
             Some("execute") | None => {
                 ExecuteTask::from_task_yaml(task_name, task_data).map(Task::Execute)
             }
+            Some("foreach") => ForeachTask::from_task_yaml(task_name, task_data).map(Task::Foreach),
             _ => {
                 tracing::warn!(
                     "Unknown task type for task '{}': {:?}. Skipping.",
@@ -69,37 +118,237 @@ impl TaskTrait for Task {
         }
     }
 
-    async fn run(&self) -> Result<String, TaskError> {
+    async fn run(&self, context: &TaskContext) -> Result<String, TaskError> {
         match self {
-            Task::Execute(task) => task.run().await,
+            Task::Execute(task) => task.run(context).await,
+            Task::Foreach(task) => task.run(context).await,
         }
     }
 
     fn id(&self) -> String {
         match self {
             Task::Execute(task) => task.id(),
+            Task::Foreach(task) => task.id(),
         }
     }
 
     fn dependencies(&self) -> &Vec<String> {
         match self {
             Task::Execute(task) => task.dependencies(),
+            Task::Foreach(task) => task.dependencies(),
         }
     }
 
-    fn inputs(&self) -> &Vec<String> {
+    fn before(&self) -> &Vec<String> {
+        match self {
+            Task::Execute(task) => task.before(),
+            Task::Foreach(task) => task.before(),
+        }
+    }
+
+    fn after(&self) -> &Vec<String> {
+        match self {
+            Task::Execute(task) => task.after(),
+            Task::Foreach(task) => task.after(),
+        }
+    }
+
+    fn inputs(&self) -> &Vec<TaskInput> {
         match self {
             Task::Execute(task) => task.inputs(),
+            Task::Foreach(task) => task.inputs(),
+        }
+    }
+
+    fn requires_clean_git(&self) -> bool {
+        match self {
+            Task::Execute(task) => task.requires_clean_git(),
+            Task::Foreach(task) => task.requires_clean_git(),
+        }
+    }
+
+    fn memoize(&self) -> bool {
+        match self {
+            Task::Execute(task) => task.memoize(),
+            Task::Foreach(task) => task.memoize(),
+        }
+    }
+
+    fn produces(&self) -> &Vec<String> {
+        match self {
+            Task::Execute(task) => task.produces(),
+            Task::Foreach(task) => task.produces(),
+        }
+    }
+
+    fn consumes(&self) -> &Vec<String> {
+        match self {
+            Task::Execute(task) => task.consumes(),
+            Task::Foreach(task) => task.consumes(),
+        }
+    }
+
+    fn ignore_inputs(&self) -> &Vec<String> {
+        match self {
+            Task::Execute(task) => task.ignore_inputs(),
+            Task::Foreach(task) => task.ignore_inputs(),
+        }
+    }
+
+    fn outputs(&self) -> &Vec<String> {
+        match self {
+            Task::Execute(task) => task.outputs(),
+            Task::Foreach(task) => task.outputs(),
+        }
+    }
+
+    fn description(&self) -> Option<&str> {
+        match self {
+            Task::Execute(task) => task.description(),
+            Task::Foreach(task) => task.description(),
         }
     }
 }
 
+impl Task {
+    /// Name of the task type, as declared in the task file
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Task::Execute(_) => "execute",
+            Task::Foreach(_) => "foreach",
+        }
+    }
+
+    /// Fingerprints every input of the task with the strategy, overriding the declared ones
+    pub fn force_strategy(&mut self, strategy: FingerprintStrategy) {
+        match self {
+            Task::Execute(task) => task.force_strategy(strategy),
+            Task::Foreach(task) => task.force_strategy(strategy),
+        }
+    }
+
+    /// Replaces every dependency and hook with the task id it resolves to,
+    /// e.g. when it names an alias
+    pub fn resolve_dependencies(&mut self, resolve: impl Fn(&str) -> String) {
+        match self {
+            Task::Execute(task) => task.resolve_dependencies(resolve),
+            Task::Foreach(task) => task.resolve_dependencies(resolve),
+        }
+    }
+
+    /// The command the task runs, as written in the task file
+    pub fn command(&self) -> String {
+        match self {
+            Task::Execute(task) => task.command(),
+            Task::Foreach(task) => task.command().to_string(),
+        }
+    }
+
+    /// Environment variables set for the command of the task
+    pub fn env(&self) -> &[(String, String)] {
+        match self {
+            Task::Execute(task) => task.env(),
+            Task::Foreach(task) => task.env(),
+        }
+    }
+
+    /// Stable hash of what the task runs, leaving out its name and dependencies
+    pub fn command_hash(&self) -> u64 {
+        let mut hasher = MetroHash64::new();
+        self.kind().hash(&mut hasher);
+        match self {
+            Task::Execute(task) => task.hash_command(&mut hasher),
+            Task::Foreach(task) => task.hash_command(&mut hasher),
+        }
+        hasher.finish()
+    }
+}
+
+/// Failure of a single task. Every variant carries the id of the task it belongs to
 #[derive(Debug, Snafu)]
 pub enum TaskError {
-    #[snafu(display("Failed to execute task"))]
-    ExecutionError { source: ExecuteTaskError },
-    #[snafu(display("Task got cancelled"))]
+    #[snafu(display("Failed to execute task '{}'", task_id))]
+    ExecutionError {
+        task_id: String,
+        source: ExecuteTaskError,
+    },
+    #[snafu(display("Task '{}' got cancelled", task_id))]
     CanceledError {
+        task_id: String,
         source: futures_channel::oneshot::Canceled,
     },
+    #[snafu(display("Task '{}' was interrupted before it could finish", task_id))]
+    InterruptedError { task_id: String },
+    #[snafu(display(
+        "{} of {} items of task '{}' failed: {}",
+        failed_items.len(),
+        total,
+        task_id,
+        failed_items.join(", ")
+    ))]
+    ForeachItemsFailedError {
+        task_id: String,
+        total: usize,
+        failed_items: Vec<String>,
+    },
+    #[snafu(display("Failed to run git to check the working tree for task '{}'", task_id))]
+    GitStatusError {
+        task_id: String,
+        source: std::io::Error,
+    },
+    #[snafu(display(
+        "Checking the git working tree for task '{}' failed: {}",
+        task_id,
+        stderr
+    ))]
+    GitStatusFailedError { task_id: String, stderr: String },
+    #[snafu(display(
+        "Task '{}' requires a clean git working tree, but there are uncommitted changes:\n{}",
+        task_id,
+        changes
+    ))]
+    DirtyWorkingTreeError { task_id: String, changes: String },
+    #[snafu(display("Failed to prepare the sandbox of task '{}'", task_id))]
+    SandboxError {
+        task_id: String,
+        source: std::io::Error,
+    },
+}
+
+impl TaskError {
+    /// Returns the id of the task which failed
+    pub fn task_id(&self) -> &str {
+        match self {
+            TaskError::ExecutionError { task_id, .. }
+            | TaskError::CanceledError { task_id, .. }
+            | TaskError::InterruptedError { task_id }
+            | TaskError::ForeachItemsFailedError { task_id, .. }
+            | TaskError::GitStatusError { task_id, .. }
+            | TaskError::GitStatusFailedError { task_id, .. }
+            | TaskError::DirtyWorkingTreeError { task_id, .. }
+            | TaskError::SandboxError { task_id, .. } => task_id,
+        }
+    }
+
+    /// Returns the exit code of the task's command, if the failure was caused by it
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            TaskError::ExecutionError {
+                source: ExecuteTaskError::UnsuccessfulExecution { status, .. },
+                ..
+            } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Returns the last lines the task's command wrote to stderr, if it failed with any
+    pub fn stderr_tail(&self) -> Option<&str> {
+        match self {
+            TaskError::ExecutionError {
+                source: ExecuteTaskError::UnsuccessfulExecution { stderr_tail, .. },
+                ..
+            } if !stderr_tail.is_empty() => Some(stderr_tail),
+            _ => None,
+        }
+    }
 }
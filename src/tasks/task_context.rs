@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::ext::CancellationToken;
+use crate::tasks::{CommandRunner, OutputOptions, ProcessRunner};
+
+/// Run-wide state handed to a task while it executes
+#[derive(Debug, Clone)]
+pub struct TaskContext {
+    /// Cancelled when the run is being torn down and the task should stop early
+    pub cancellation: CancellationToken,
+    pub output: OutputOptions,
+    /// Root directory of the project, which task paths are relative to
+    pub root: PathBuf,
+    /// Spawns the commands of tasks
+    pub runner: Arc<dyn CommandRunner>,
+    /// Program and arguments wrapping the command of every task which doesn't opt out
+    pub command_wrapper: Vec<String>,
+    /// Task the run was started for, exposed to commands as `TESSY_TARGET`
+    pub target: String,
+}
+
+impl Default for TaskContext {
+    fn default() -> Self {
+        Self {
+            cancellation: CancellationToken::default(),
+            output: OutputOptions::default(),
+            root: PathBuf::default(),
+            runner: Arc::new(ProcessRunner),
+            command_wrapper: Vec::new(),
+            target: String::new(),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+
+use hashlink::LinkedHashMap;
+use saphyr::{Scalar, Yaml};
+use tracing::warn;
+
+use crate::file_dependencies::{FingerprintStrategy, LineEndings};
+
+/// A single entry of a task's `inputs`.
+///
+/// Declared either as a plain path, or as a mapping with a `path` and optional overrides,
+/// e.g. `{ path: "src/", strategy: hash }`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TaskInput {
+    pub path: String,
+    /// Overrides the default fingerprint strategy for this input
+    pub strategy: Option<FingerprintStrategy>,
+    /// Also track the Unix permission bits of the files, so e.g. `chmod +x` invalidates the task
+    pub permissions: bool,
+    /// Trust the saved fingerprints of a directory input while the modification times of the
+    /// directory and all its subdirectories stay the same.
+    ///
+    /// A directory's modification time only changes when entries are added, removed or renamed,
+    /// so files edited in place are not noticed. Some filesystems (e.g. FAT or network mounts)
+    /// update it lazily or with a coarse resolution. Only enable it for directories whose files
+    /// are replaced rather than edited, like generated or downloaded ones
+    pub directory_stamp: bool,
+    /// How line endings are treated when the contents are hashed, inherited from the task
+    /// or the task file when not set
+    pub line_endings: Option<LineEndings>,
+}
+
+impl TaskInput {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            strategy: None,
+            permissions: false,
+            directory_stamp: false,
+            line_endings: None,
+        }
+    }
+
+    pub fn from_yaml(item: &Yaml) -> Option<Self> {
+        if let Some(path) = item.as_str() {
+            return Some(Self::new(path));
+        }
+
+        let mapping = item.as_mapping()?;
+        let path = mapping
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("path"))))
+            .and_then(|v| v.as_str())?;
+
+        let strategy = match mapping
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("strategy"))))
+            .and_then(|v| v.as_str())
+        {
+            Some(name) => match FingerprintStrategy::from_name(name) {
+                Some(strategy) => Some(strategy),
+                None => {
+                    warn!(
+                        "Unknown fingerprint strategy '{}' for input '{}', using the default",
+                        name, path
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let line_endings = Self::line_endings_from_yaml(mapping, path);
+
+        let permissions = mapping
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("permissions"))))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let directory_stamp = mapping
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed(
+                "directoryStamp",
+            ))))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        Some(Self {
+            path: path.to_string(),
+            strategy,
+            permissions,
+            directory_stamp,
+            line_endings,
+        })
+    }
+
+    /// Reads the `lineEndings` value of an input, a task or the task file
+    pub fn line_endings_from_yaml(
+        mapping: &LinkedHashMap<Yaml, Yaml>,
+        context: &str,
+    ) -> Option<LineEndings> {
+        let name = mapping
+            .get(&Yaml::Value(Scalar::String(Cow::Borrowed("lineEndings"))))
+            .and_then(|v| v.as_str())?;
+        let line_endings = LineEndings::from_name(name);
+        if line_endings.is_none() {
+            warn!(
+                "Unknown line endings '{}' for '{}', keeping them as they are",
+                name, context
+            );
+        }
+        line_endings
+    }
+}
+
+/// A plain path equals an input declared without any overrides
+impl PartialEq<&str> for TaskInput {
+    fn eq(&self, other: &&str) -> bool {
+        self == &TaskInput::new(*other)
+    }
+}
+
+impl PartialEq<String> for TaskInput {
+    fn eq(&self, other: &String) -> bool {
+        self == &other.as_str()
+    }
+}
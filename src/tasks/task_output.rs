@@ -0,0 +1,470 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use colored::Color;
+use tracing::debug;
+
+use crate::{
+    ext::BestEffortPathExt,
+    tasks::{StatusLine, task::format_task_line},
+};
+
+/// Longest time lines are held back in a batch before being written out
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+/// Largest number of lines held back in a batch before being written out
+const BATCH_MAX_LINES: usize = 64;
+
+/// Options controlling how the output of tasks is printed
+#[derive(Debug, Clone, Default)]
+pub struct OutputOptions {
+    /// Collect lines and write them in batches, instead of locking stdout for every line
+    pub batched: bool,
+    /// Seed mixed into the task colors, so they can be reproduced
+    pub color_seed: Option<u64>,
+    /// Color the task ids, as resolved from the `--color` mode
+    pub colored: bool,
+    /// Copy the output in raw chunks, prefixing only at line starts, instead of reading it line by line
+    pub raw: bool,
+    /// Write the lines as they are, without the task id prefix
+    pub without_prefix: bool,
+    /// Keep only this many last lines of every stream and print them once the stream ends.
+    /// Ignored for raw output, which isn't split into lines
+    pub max_lines: Option<usize>,
+    /// Print nothing, while still writing the output to the log file of the task
+    pub quiet: bool,
+    /// Collect the printed output here instead of writing it to stdout
+    #[cfg(test)]
+    pub capture: Option<OutputCapture>,
+}
+
+/// Buffer collecting the printed output of tasks in tests, shared by the handlers of their streams
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct OutputCapture(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl OutputCapture {
+    /// Everything printed so far
+    pub fn contents(&self) -> String {
+        let bytes = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    fn write(&self, bytes: &[u8]) {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .extend_from_slice(bytes);
+    }
+}
+
+/// File receiving the combined output of a task, shared by the handlers of its streams
+#[derive(Debug, Clone)]
+pub struct TaskLog {
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+impl TaskLog {
+    /// Creates the log file along with its missing parent directories, replacing an earlier log
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Arc::new(Mutex::new(File::create(path)?)),
+        })
+    }
+
+    fn write(&self, bytes: &[u8]) {
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(e) = file.write_all(bytes) {
+            debug!(
+                "Failed to write to log file '{}': {}",
+                self.path.best_effort_path_display(),
+                e
+            );
+        }
+    }
+}
+
+/// Prints the output lines of a single task stream, either one by one or in batches
+pub struct TaskOutput {
+    task_id: String,
+    /// Color of the task id, none if the output isn't colored
+    color: Option<Color>,
+    prefixed: bool,
+    batched: bool,
+    batch: String,
+    batch_lines: usize,
+    batch_started: Instant,
+    /// Task prefix written at the start of every raw output line
+    raw_prefix: String,
+    raw_buffer: Vec<u8>,
+    raw_at_line_start: bool,
+    max_lines: Option<usize>,
+    quiet: bool,
+    /// Last lines of a stream with limited output, held back until it ends
+    tail: VecDeque<String>,
+    suppressed_lines: usize,
+    /// File every line is written to as well, without the task prefix
+    log: Option<TaskLog>,
+    #[cfg(test)]
+    capture: Option<OutputCapture>,
+}
+
+impl TaskOutput {
+    pub fn new(task_id: String, color: Color, options: &OutputOptions) -> Self {
+        let color = options.colored.then_some(color);
+        let raw_prefix = if options.raw && !options.without_prefix {
+            format_task_line(&task_id, color, "")
+        } else {
+            String::new()
+        };
+
+        Self {
+            task_id,
+            color,
+            prefixed: !options.without_prefix,
+            batched: options.batched,
+            batch: String::new(),
+            batch_lines: 0,
+            batch_started: Instant::now(),
+            raw_prefix,
+            raw_buffer: Vec::new(),
+            raw_at_line_start: true,
+            max_lines: options.max_lines,
+            quiet: options.quiet,
+            tail: VecDeque::new(),
+            suppressed_lines: 0,
+            log: None,
+            #[cfg(test)]
+            capture: options.capture.clone(),
+        }
+    }
+
+    /// Writes the output to the log file as well as printing it
+    pub fn with_log(mut self, log: Option<TaskLog>) -> Self {
+        self.log = log;
+        self
+    }
+
+    /// Prints the output above the status line
+    fn write(&self, output: impl AsRef<[u8]>) {
+        #[cfg(test)]
+        if let Some(capture) = &self.capture {
+            capture.write(output.as_ref());
+            return;
+        }
+        if let Err(e) = StatusLine::write_output(output) {
+            debug!("Failed to write output of task '{}': {}", self.task_id, e);
+        }
+    }
+
+    /// Writes a raw chunk of output, inserting the task prefix at the start of every line
+    pub fn push_chunk(&mut self, chunk: &[u8]) {
+        if let Some(log) = &self.log {
+            log.write(chunk);
+        }
+        if self.quiet {
+            return;
+        }
+        self.prefix_chunk(chunk);
+        self.write(&self.raw_buffer);
+    }
+
+    fn prefix_chunk(&mut self, chunk: &[u8]) {
+        self.raw_buffer.clear();
+        for line in chunk.split_inclusive(|&byte| byte == b'\n') {
+            if self.raw_at_line_start {
+                self.raw_buffer
+                    .extend_from_slice(self.raw_prefix.as_bytes());
+            }
+            self.raw_buffer.extend_from_slice(line);
+            self.raw_at_line_start = line.ends_with(b"\n");
+        }
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        if let Some(log) = &self.log {
+            log.write(format!("{}\n", line).as_bytes());
+        }
+        if self.quiet {
+            return;
+        }
+        let formatted = self.format_line(line);
+        if let Some(max_lines) = self.max_lines {
+            self.tail.push_back(formatted);
+            if self.tail.len() > max_lines {
+                self.tail.pop_front();
+                self.suppressed_lines += 1;
+            }
+            return;
+        }
+        if !self.batched {
+            self.write(&format!("{}\n", formatted));
+            return;
+        }
+
+        if self.batch_lines == 0 {
+            self.batch_started = Instant::now();
+        }
+        self.batch.push_str(&formatted);
+        self.batch.push('\n');
+        self.batch_lines += 1;
+
+        if self.batch_lines >= BATCH_MAX_LINES || self.batch_started.elapsed() >= BATCH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    fn format_line(&self, line: &str) -> String {
+        if self.prefixed {
+            format_task_line(&self.task_id, self.color, line)
+        } else {
+            line.to_string()
+        }
+    }
+
+    /// Writes out everything still held back, once the stream has ended.
+    /// The retained tail of limited output is preceded by the number of suppressed lines
+    pub fn finish(&mut self) {
+        self.flush();
+        if self.tail.is_empty() {
+            return;
+        }
+
+        let mut tail = String::new();
+        if self.suppressed_lines > 0 {
+            tail.push_str(&self.format_line(&format!(
+                "…({} earlier lines suppressed)",
+                self.suppressed_lines
+            )));
+            tail.push('\n');
+        }
+        for line in self.tail.drain(..) {
+            tail.push_str(&line);
+            tail.push('\n');
+        }
+        self.suppressed_lines = 0;
+
+        self.write(&tail);
+    }
+
+    /// Time left until the pending batch has to be written out, if there is one
+    pub fn flush_deadline(&self) -> Option<Duration> {
+        (self.batch_lines > 0).then(|| BATCH_INTERVAL.saturating_sub(self.batch_started.elapsed()))
+    }
+
+    /// Writes out all pending lines with a single stdout lock, and terminates an unfinished raw line
+    pub fn flush(&mut self) {
+        if !self.raw_at_line_start {
+            self.raw_at_line_start = true;
+            self.write("\n");
+        }
+
+        if self.batch.is_empty() {
+            return;
+        }
+
+        self.write(&self.batch);
+        self.batch.clear();
+        self.batch_lines = 0;
+    }
+}
+
+impl Drop for TaskOutput {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batched_output() -> TaskOutput {
+        TaskOutput::new(
+            "task".to_string(),
+            Color::Red,
+            &OutputOptions {
+                batched: true,
+                capture: Some(OutputCapture::default()),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_unbatched_output_keeps_nothing_pending() {
+        let options = OutputOptions {
+            capture: Some(OutputCapture::default()),
+            ..Default::default()
+        };
+        let mut output = TaskOutput::new("task".to_string(), Color::Red, &options);
+
+        output.push_line("hello");
+
+        assert!(output.flush_deadline().is_none());
+    }
+
+    #[test]
+    fn test_batched_output_holds_lines_until_flush() {
+        let mut output = batched_output();
+
+        output.push_line("hello");
+        output.push_line("world");
+
+        assert_eq!(output.batch_lines, 2);
+        assert!(output.flush_deadline().is_some());
+
+        output.flush();
+
+        assert_eq!(output.batch_lines, 0);
+        assert!(output.flush_deadline().is_none());
+    }
+
+    #[test]
+    fn test_batched_output_flushes_when_full() {
+        let mut output = batched_output();
+
+        for i in 0..BATCH_MAX_LINES {
+            output.push_line(&format!("line {}", i));
+        }
+
+        assert_eq!(output.batch_lines, 0);
+    }
+
+    #[test]
+    fn test_raw_output_prefixes_only_line_starts() {
+        let mut output = TaskOutput::new(
+            "task".to_string(),
+            Color::Red,
+            &OutputOptions {
+                raw: true,
+                ..Default::default()
+            },
+        );
+        let prefix = output.raw_prefix.clone();
+
+        output.prefix_chunk(b"first\nsec");
+        assert_eq!(
+            output.raw_buffer,
+            format!("{prefix}first\n{prefix}sec").into_bytes()
+        );
+        assert!(!output.raw_at_line_start);
+
+        output.prefix_chunk(b"ond\n");
+        assert_eq!(output.raw_buffer, b"ond\n");
+        assert!(output.raw_at_line_start);
+    }
+
+    #[test]
+    fn test_output_without_prefix_writes_lines_as_they_are() {
+        let options = OutputOptions {
+            batched: true,
+            raw: true,
+            without_prefix: true,
+            ..Default::default()
+        };
+        let mut output = TaskOutput::new("task".to_string(), Color::Red, &options);
+
+        output.push_line("hello");
+        output.prefix_chunk(b"raw\n");
+
+        assert_eq!(output.batch, "hello\n");
+        assert_eq!(output.raw_buffer, b"raw\n");
+    }
+
+    #[test]
+    fn test_quiet_output_holds_nothing_back() {
+        let options = OutputOptions {
+            batched: true,
+            max_lines: Some(2),
+            quiet: true,
+            ..Default::default()
+        };
+        let mut output = TaskOutput::new("task".to_string(), Color::Red, &options);
+
+        output.push_line("hello");
+
+        assert!(output.batch.is_empty());
+        assert!(output.tail.is_empty());
+        assert!(output.flush_deadline().is_none());
+    }
+
+    #[test]
+    fn test_colored_output_colors_the_task_id() {
+        let options = OutputOptions {
+            colored: true,
+            ..Default::default()
+        };
+        let output = TaskOutput::new("task".to_string(), Color::Red, &options);
+
+        assert_eq!(output.format_line("hello"), "\x1b[31m[task]\x1b[0m: hello");
+    }
+
+    #[test]
+    fn test_uncolored_output_keeps_the_task_id_plain() {
+        let output = TaskOutput::new("task".to_string(), Color::Red, &OutputOptions::default());
+
+        assert_eq!(output.format_line("hello"), "[task]: hello");
+    }
+
+    #[test]
+    fn test_log_receives_lines_without_prefix() {
+        let root = tempfile::TempDir::new().unwrap();
+        let path = root.path().join("logs/nested/task.log");
+        let log = TaskLog::create(&path).unwrap();
+        let capture = OutputCapture::default();
+        let options = OutputOptions {
+            capture: Some(capture.clone()),
+            ..Default::default()
+        };
+        let mut output =
+            TaskOutput::new("task".to_string(), Color::Red, &options).with_log(Some(log.clone()));
+
+        output.push_line("hello");
+        output.push_chunk(b"raw\n");
+        drop(log);
+        drop(output);
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "hello\nraw\n");
+        assert!(capture.contents().starts_with("[task]: hello\n"));
+    }
+
+    #[test]
+    fn test_limited_output_keeps_only_the_last_lines() {
+        let options = OutputOptions {
+            without_prefix: true,
+            max_lines: Some(2),
+            ..Default::default()
+        };
+        let mut output = TaskOutput::new("task".to_string(), Color::Red, &options);
+
+        for i in 0..5 {
+            output.push_line(&format!("line {}", i));
+        }
+
+        assert_eq!(output.tail, vec!["line 3", "line 4"]);
+        assert_eq!(output.suppressed_lines, 3);
+
+        output.finish();
+
+        assert!(output.tail.is_empty());
+        assert_eq!(output.suppressed_lines, 0);
+    }
+}
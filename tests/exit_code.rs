@@ -0,0 +1,22 @@
+use std::process::Command;
+
+#[cfg(target_family = "unix")]
+#[test]
+fn test_failed_task_exit_code_becomes_the_exit_status() {
+    let root = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        root.path().join("tasks.yaml"),
+        "tasks:\n  check:\n    command: exit 3",
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_tessy"))
+        .args(["check", "--root"])
+        .arg(root.path())
+        .env_remove("TESSY_ROOT")
+        .env_remove("TESSY_TASK_FILE")
+        .status()
+        .unwrap();
+
+    assert_eq!(status.code(), Some(3));
+}